@@ -3,6 +3,9 @@ use clap::{Parser, Subcommand, CommandFactory};
 use clap_complete::{generate, shells::{Bash, Fish, Zsh}};
 
 use core::commands;
+use core::glyph_encode::{QrEcLevel, QrOptions};
+use core::media::MediaMode;
+use core::glyph_merge::Favor;
 use utils::app_config::AppConfig;
 use utils::error::Result;
 use utils::types::LogLevel;
@@ -80,6 +83,50 @@ enum Commands {
         /// Validate glyph structure
         #[arg(long)]
         validate: bool,
+        
+        /// Fill visual.sigil with a sigil derived from the semantic hash
+        #[arg(long)]
+        sigil: bool,
+        
+        /// Also write the glyph as a QR code
+        #[arg(long)]
+        qr: bool,
+        
+        /// QR code output file (defaults to <input>.png or <input>.svg)
+        #[arg(long, requires = "qr")]
+        qr_output: Option<String>,
+        
+        /// QR module size in pixels (PNG) or user units (SVG)
+        #[arg(long, default_value_t = 8, requires = "qr")]
+        qr_scale: u32,
+        
+        /// QR quiet zone width in modules
+        #[arg(long, default_value_t = 4, requires = "qr")]
+        qr_quiet_zone: u32,
+        
+        /// QR error-correction level
+        #[arg(long, value_enum, ignore_case = true, default_value = "m", requires = "qr")]
+        qr_ec: QrEcLevel,
+        
+        /// Write the QR code as SVG instead of PNG
+        #[arg(long, requires = "qr")]
+        qr_svg: bool,
+        
+        /// Use the compact QR payload (deflate + Base45 in alphanumeric mode)
+        #[arg(long, requires = "qr")]
+        qr_compact: bool,
+        
+        /// Split the glyph across several QR codes of at most this many payload characters
+        #[arg(long, requires = "qr")]
+        qr_chunk_size: Option<usize>,
+        
+        /// Attach audio.file / visual.file: embed the bytes or reference them by digest (embed, reference)
+        #[arg(long)]
+        media: Option<MediaMode>,
+        
+        /// Earlier revision of this glyph; records its concept id, revision and hash as lineage
+        #[arg(long)]
+        parent: Option<String>,
     },
     #[clap(
         name = "decode",
@@ -87,9 +134,9 @@ enum Commands {
         long_about = "Convert binary .glyph files to JSON, YAML, or human-readable text with optional signature verification",
     )]
     Decode {
-        /// Input .glyph file or QR code image
-        #[arg(short, long)]
-        input: String,
+        /// Input .glyph file or QR code image(s); repeat for glyphs split across several codes
+        #[arg(short, long, required = true)]
+        input: Vec<String>,
         
         /// Input is a QR code image
         #[arg(long)]
@@ -130,6 +177,362 @@ enum Commands {
         /// Show only the payload
         #[arg(long)]
         payload_only: bool,
+        
+        /// Write embedded media attachments into this directory
+        #[arg(long)]
+        extract_media: Option<String>,
+        
+        /// Where --verify looks for referenced media (defaults to the glyph's directory)
+        #[arg(long)]
+        media_dir: Option<String>,
+        
+        /// Show the glyph as this agent's contextual overlay sees it (e.g. "glyphos:mythology")
+        #[arg(long)]
+        perspective: Option<String>,
+
+        /// Keep the glyph in this store's quarantine when --validate or --verify fails
+        #[arg(long)]
+        quarantine: Option<String>,
+    },
+    #[clap(
+        name = "sigil",
+        about = "Render the deterministic sigil of a .glyph file",
+        long_about = "Render the symmetric sigil derived from a glyph's semantic hash, tinted by visual.style.color and scaled by visual.style.stroke",
+    )]
+    Sigil {
+        /// Input .glyph file
+        #[arg(short, long)]
+        input: String,
+
+        /// Output format (svg, png)
+        #[arg(short, long, default_value = "svg")]
+        format: String,
+
+        /// Output file (defaults to <input>.sigil.<format>)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// PNG size in pixels (36 to 4096)
+        #[arg(long, default_value_t = 256)]
+        size: u32,
+    },
+    #[clap(
+        name = "scan",
+        about = "Find every glyph in QR images or directories of them",
+        long_about = "Decode every QR code in every image (including GIF frames), reassemble chunked glyphs, drop duplicates and report per-image diagnostics",
+    )]
+    Scan {
+        /// Images or directories of images
+        #[arg(short, long, required = true)]
+        input: Vec<String>,
+
+        /// Directory to write the distinct .glyph files to
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Report format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+    #[clap(
+        name = "fountain",
+        about = "Encode glyphs as fountain-coded animated QR sequences",
+        long_about = "Encode a glyph as an endless-style sequence of LT fountain-coded QR frames (animated GIF or PNG frames) and rebuild it from any sufficient subset of frames",
+    )]
+    Fountain {
+        #[clap(subcommand)]
+        subcommand: FountainSubcommand,
+    },
+    #[clap(
+        name = "pack",
+        about = "Create, verify, extract and list .glyphpack archives",
+        long_about = "Bundle glyphs, JSON companions, media and public keys into a .glyphpack archive with a signed manifest, and check that a pack is complete and unaltered",
+    )]
+    Pack {
+        #[clap(subcommand)]
+        subcommand: PackSubcommand,
+    },
+    #[clap(
+        name = "proof",
+        about = "Merkle roots and inclusion proofs for a glyph corpus",
+        long_about = "Commit to a corpus with a (signed) Merkle root over its sorted semantic hashes, and prove or check that a glyph belongs to it",
+    )]
+    Proof {
+        #[clap(subcommand)]
+        subcommand: ProofSubcommand,
+    },
+    #[clap(
+        name = "diff",
+        about = "Show the semantic differences between two glyphs",
+        long_about = "Compare two glyphs field by field: aliases and classes added or removed, relations added or retargeted, confidence deltas, grounding changes, signature status and whether the semantic hash changed",
+    )]
+    Diff {
+        /// Original .glyph file
+        a: String,
+
+        /// Changed .glyph file
+        b: String,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+
+        /// Public key to check both signatures with
+        #[arg(short, long)]
+        public_key: Option<String>,
+    },
+    #[clap(
+        name = "merge",
+        about = "Three-way merge of glyph edits",
+        long_about = "Merge two edited revisions of a concept against their common ancestor: set-like fields merge element-wise, scalars merge by rule, and contested scalars are reported with conflict markers. The header hash is recomputed and a signature that no longer matches is cleared",
+    )]
+    Merge {
+        /// Common ancestor .glyph file
+        base: String,
+
+        /// Our edited .glyph file
+        ours: String,
+
+        /// Their edited .glyph file
+        theirs: String,
+
+        /// Output .glyph file
+        #[arg(short, long)]
+        output: String,
+
+        /// Settle contested fields in favour of one side (ours, theirs)
+        #[arg(long)]
+        favor: Option<Favor>,
+
+        /// Sign the merged glyph with Ed25519
+        #[arg(short, long)]
+        sign: bool,
+
+        /// Private key file for signing
+        #[arg(long)]
+        private_key: Option<String>,
+    },
+    #[clap(
+        name = "history",
+        about = "Show and check the revision history of a glyph",
+        long_about = "Collect every revision of a concept from a store, order them by revision and check that each one points at the semantic hash of its real predecessor",
+    )]
+    History {
+        /// Concept id, or the header id of any revision
+        id: String,
+
+        /// Store to search: .glyph files, directories or .glyphs streams
+        #[arg(short, long, default_value = ".")]
+        store: Vec<String>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+    #[clap(
+        name = "resolve",
+        about = "Resolve glyph: references against a store",
+        long_about = "Resolve glyph:<label>, glyph:<namespace>/<label>, glyph:<uuid> and glyph:<label>@blake3:<hex> references to concrete glyphs, reporting ambiguous and unresolvable ones. Without references, every relation target in the store is checked",
+    )]
+    Resolve {
+        /// References to resolve (defaults to every relation target in the store)
+        reference: Vec<String>,
+
+        /// Store to search: .glyph files, directories or .glyphs streams
+        #[arg(short, long, default_value = ".")]
+        store: Vec<String>,
+    },
+    #[clap(
+        name = "query",
+        about = "Query and update a glyph store with glyphscript",
+        long_about = "Run glyphscript statements such as `match (a)-[powers]->(b) where a.truth_mode.confidence > 0.9 return b.label` over a store or a directory. Updates (set, add ... to, remove ... from) are only saved with --write",
+    )]
+    Query {
+        /// Glyphscript to run
+        script: String,
+
+        /// Store to query: .glyph files, directories or .glyphs streams
+        #[arg(short, long, default_value = ".")]
+        store: Vec<String>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+
+        /// Write updated glyphs back to their files
+        #[arg(short, long)]
+        write: bool,
+    },
+    #[clap(
+        name = "infer",
+        about = "Derive relations implied by relation properties and rules",
+        long_about = "Compute the closure of a corpus under the symmetric, transitive and inverse-of relation properties and Horn-style rules declared in a JSON5 rules file. Lists inferred relations with their derivations, or answers a question such as \"sun part_of ?x\"",
+    )]
+    Infer {
+        /// JSON5 rules file
+        #[arg(short, long)]
+        rules: String,
+
+        /// Corpus: .glyph files, directories or .glyphs streams
+        #[arg(short, long, default_value = ".")]
+        store: Vec<String>,
+
+        /// Question as "<subject> <relation> <object>"; ?x matches anything
+        #[arg(short, long)]
+        ask: Option<String>,
+
+        /// Show the full derivation of each relation
+        #[arg(short, long)]
+        explain: bool,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+    #[clap(
+        name = "dissonance",
+        about = "Report contradicting claims across a corpus",
+        long_about = "Find relations declared disjoint between the same glyphs (including inferred ones), concepts placed in disjoint classes, versions of one concept with different truth modes, and conflicts declared in truth modes. Each dissonance lists the glyphs involved with their creators and signers",
+    )]
+    Dissonance {
+        /// Corpus: .glyph files, directories or .glyphs streams
+        #[arg(short, long, default_value = ".")]
+        store: Vec<String>,
+
+        /// JSON5 rules file with relation properties and disjoint sets
+        #[arg(short, long)]
+        rules: Option<String>,
+
+        /// Public keys (.pub files or directories of them) to name signers
+        #[arg(short, long)]
+        keys: Vec<String>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+    #[clap(
+        name = "consensus",
+        about = "Merge competing versions of a concept into a consensus glyph",
+        long_about = "Gather every version of a concept (same label or wikidata id) from the store and weigh them by a trust policy. The most trusted value of each field wins, confidence is the weighted mean, and minority labels, truth modes and relations are kept as contextual overlays and scoped relations. Prints per-field attribution",
+    )]
+    Consensus {
+        /// Label or wikidata id of the concept
+        concept: String,
+
+        /// Corpus: .glyph files, directories or .glyphs streams
+        #[arg(short, long, default_value = ".")]
+        store: Vec<String>,
+
+        /// JSON5 trust policy; every version weighs the same without one
+        #[arg(short, long)]
+        trust: Option<String>,
+
+        /// Public keys (.pub files or directories of them) to name signers
+        #[arg(short, long)]
+        keys: Vec<String>,
+
+        /// Write the consensus glyph to this file
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+
+        /// Sign the consensus glyph
+        #[arg(long)]
+        sign: bool,
+
+        /// Private key file for signing
+        #[arg(long)]
+        private_key: Option<String>,
+    },
+    #[clap(
+        name = "ingest",
+        about = "Admit glyphs into a store through a firewall policy",
+        long_about = "Check incoming .glyph files, directories and .glyphs streams against the allow/deny rules of a firewall policy (signers, namespaces, classes, relation patterns, size, validation). Allowed glyphs are copied into the store as <id>.glyph. Rejected glyphs are kept in the store's quarantine, and every rejection is recorded with its reasons in the store's firewall.jsonl",
+    )]
+    Ingest {
+        /// Incoming .glyph files, directories or .glyphs streams
+        #[arg(required = true)]
+        input: Vec<String>,
+
+        /// Store directory to admit glyphs into
+        #[arg(short, long)]
+        store: String,
+
+        /// JSON5 firewall policy
+        #[arg(short, long)]
+        policy: String,
+
+        /// Public keys (.pub files or directories of them) to name signers
+        #[arg(short, long)]
+        keys: Vec<String>,
+
+        /// Report verdicts without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    #[clap(
+        name = "quarantine",
+        about = "Review glyphs rejected on ingest",
+        long_about = "List, inspect, release or purge the glyphs a store's firewall turned away, and judge them again after the policy or the trusted keys change",
+    )]
+    Quarantine {
+        #[clap(subcommand)]
+        subcommand: QuarantineSubcommand,
+    },
+    #[clap(
+        name = "search",
+        about = "Search a store by label, aliases, classes and phonetics",
+        long_about = "Find glyphs whose label, aliases, contextual overlay labels, phonetic strings or classes match the text. Matching is Unicode-normalized and case-insensitive, accepts prefixes and small typos, and ranks hits by score",
+    )]
+    Search {
+        /// Text to search for
+        text: String,
+
+        /// Corpus: .glyph files, directories or .glyphs streams
+        #[arg(short, long, default_value = ".")]
+        store: Vec<String>,
+
+        /// Only glyphs provided under this namespace (e.g. glyph:core or core)
+        #[arg(short, long)]
+        namespace: Option<String>,
+
+        /// Only glyphs with this truth mode
+        #[arg(short, long)]
+        truth_mode: Option<String>,
+
+        /// Only glyphs with at least this confidence
+        #[arg(short, long)]
+        min_confidence: Option<f64>,
+
+        /// Maximum number of hits
+        #[arg(short, long, default_value = "20")]
+        limit: usize,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+    #[clap(
+        name = "stream",
+        about = "Work with .glyphs glyphstream containers",
+        long_about = "Pack, unpack, print and list sequences of glyphs stored in a streamable .glyphs container",
+    )]
+    Stream {
+        #[clap(subcommand)]
+        subcommand: StreamSubcommand,
+    },
+    #[clap(
+        name = "timevector",
+        about = "Encode, decode and validate timevectors",
+        long_about = "Work with timevectors: timestamped sequences of glyph references mapped to media",
+    )]
+    Timevector {
+        #[clap(subcommand)]
+        subcommand: TimevectorSubcommand,
     },
     #[clap(
         name = "completion",
@@ -148,6 +551,368 @@ enum Commands {
     Config,
 }
 
+#[derive(Subcommand, PartialEq, Debug)]
+enum StreamSubcommand {
+    #[clap(about = "pack .glyph files (or directories of them) into a .glyphs stream")]
+    Pack {
+        /// Input .glyph files or directories
+        #[arg(short, long, required = true)]
+        input: Vec<String>,
+
+        /// Output .glyphs file
+        #[arg(short, long)]
+        output: String,
+
+        /// Do not append the index footer
+        #[arg(long)]
+        no_index: bool,
+    },
+    #[clap(about = "unpack a .glyphs stream into individual .glyph files")]
+    Unpack {
+        /// Input .glyphs file
+        #[arg(short, long)]
+        input: String,
+
+        /// Output directory
+        #[arg(short, long)]
+        output: String,
+    },
+    #[clap(about = "print every glyph in a .glyphs stream, one per line")]
+    Cat {
+        /// Input .glyphs file
+        #[arg(short, long)]
+        input: String,
+
+        /// Output format (json, yaml, text)
+        #[arg(short, long, default_value = "json")]
+        format: String,
+    },
+    #[clap(about = "list the glyphs in a .glyphs stream")]
+    Ls {
+        /// Input .glyphs file
+        #[arg(short, long)]
+        input: String,
+    },
+}
+
+#[derive(Subcommand, PartialEq, Debug)]
+enum QuarantineSubcommand {
+    #[clap(about = "list quarantined glyphs, oldest first")]
+    List {
+        /// Store directory
+        #[arg(short, long, default_value = ".")]
+        store: String,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+    #[clap(about = "show a quarantined glyph with its diagnostics")]
+    Show {
+        /// Entry name or a unique prefix of it
+        entry: String,
+
+        /// Store directory
+        #[arg(short, long, default_value = ".")]
+        store: String,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+    #[clap(about = "move quarantined glyphs into the store")]
+    Release {
+        /// Entry names or unique prefixes
+        #[arg(required = true)]
+        entry: Vec<String>,
+
+        /// Store directory
+        #[arg(short, long, default_value = ".")]
+        store: String,
+    },
+    #[clap(about = "delete quarantined glyphs")]
+    Purge {
+        /// Entry names or unique prefixes
+        entry: Vec<String>,
+
+        /// Store directory
+        #[arg(short, long, default_value = ".")]
+        store: String,
+
+        /// Only entries that arrived more than this many days ago
+        #[arg(long)]
+        older_than: Option<i64>,
+
+        /// Every entry
+        #[arg(long)]
+        all: bool,
+    },
+    #[clap(about = "judge quarantined glyphs again under a firewall policy")]
+    Recheck {
+        /// Store directory
+        #[arg(short, long, default_value = ".")]
+        store: String,
+
+        /// JSON5 firewall policy
+        #[arg(short, long)]
+        policy: String,
+
+        /// Public keys (.pub files or directories of them) to name signers
+        #[arg(short, long)]
+        keys: Vec<String>,
+
+        /// Release the glyphs that now pass
+        #[arg(long)]
+        release: bool,
+    },
+}
+
+#[derive(Subcommand, PartialEq, Debug)]
+enum FountainSubcommand {
+    #[clap(about = "render a .glyph file as fountain-coded QR frames")]
+    Encode {
+        /// Input .glyph file
+        #[arg(short, long)]
+        input: String,
+
+        /// Write an animated GIF
+        #[arg(long, conflicts_with = "out_dir", required_unless_present = "out_dir")]
+        gif: Option<String>,
+
+        /// Write a PNG frame sequence into this directory
+        #[arg(long)]
+        out_dir: Option<String>,
+
+        /// Number of frames to generate (defaults to twice the number of source blocks)
+        #[arg(long)]
+        frames: Option<usize>,
+
+        /// Source block size in bytes
+        #[arg(long, default_value_t = core::qr_fountain::DEFAULT_BLOCK_SIZE)]
+        block_size: usize,
+
+        /// Delay between GIF frames in milliseconds
+        #[arg(long, default_value_t = 200)]
+        delay: u32,
+
+        /// Pixels per QR module
+        #[arg(long, default_value_t = 8)]
+        scale: u32,
+
+        /// QR error-correction level
+        #[arg(long, value_enum, ignore_case = true, default_value = "m")]
+        ec: QrEcLevel,
+    },
+    #[clap(about = "rebuild a .glyph file from fountain-coded QR frames")]
+    Decode {
+        /// Animated GIF, single image or directory of frame images
+        #[arg(short, long)]
+        input: String,
+
+        /// Output .glyph file (defaults to <label>.glyph)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+}
+
+#[derive(Subcommand, PartialEq, Debug)]
+enum PackSubcommand {
+    #[clap(about = "bundle files and directories into a .glyphpack archive")]
+    Create {
+        /// Input files or directories
+        #[arg(short, long, required = true)]
+        input: Vec<String>,
+
+        /// Leave out files by archive path, file name or "*suffix"; files that look like private keys must be excluded
+        #[arg(short = 'x', long)]
+        exclude: Vec<String>,
+
+        /// Output .glyphpack file
+        #[arg(short, long)]
+        output: String,
+
+        /// Sign the manifest with Ed25519
+        #[arg(short, long)]
+        sign: bool,
+
+        /// Private key file for signing
+        #[arg(long)]
+        private_key: Option<String>,
+    },
+    #[clap(about = "check that a .glyphpack is complete and unaltered")]
+    Verify {
+        /// Input .glyphpack file
+        #[arg(short, long)]
+        input: String,
+
+        /// Public key file to check the manifest signature against
+        #[arg(long)]
+        public_key: Option<String>,
+    },
+    #[clap(about = "verify a .glyphpack and extract its files")]
+    Extract {
+        /// Input .glyphpack file
+        #[arg(short, long)]
+        input: String,
+
+        /// Output directory
+        #[arg(short, long)]
+        output: String,
+    },
+    #[clap(about = "list the files in a .glyphpack")]
+    List {
+        /// Input .glyphpack file
+        #[arg(short, long)]
+        input: String,
+    },
+}
+
+#[derive(Subcommand, PartialEq, Debug)]
+enum ProofSubcommand {
+    #[clap(about = "compute the Merkle root of a corpus")]
+    Root {
+        /// Corpus: .glyph files, directories or .glyphs streams
+        #[arg(short, long, required = true)]
+        corpus: Vec<String>,
+
+        /// Output root file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Sign the root with Ed25519
+        #[arg(short, long)]
+        sign: bool,
+
+        /// Private key file for signing
+        #[arg(long)]
+        private_key: Option<String>,
+    },
+    #[clap(about = "emit an inclusion proof for a glyph")]
+    Create {
+        /// The .glyph file to prove
+        glyph: String,
+
+        /// Corpus: .glyph files, directories or .glyphs streams
+        #[arg(short, long, required = true)]
+        corpus: Vec<String>,
+
+        /// Output proof file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    #[clap(about = "check an inclusion proof against a root")]
+    Verify {
+        /// Proof file
+        proof: String,
+
+        /// Signed root file, or a bare "blake3:<hex>" root
+        #[arg(short, long)]
+        root: String,
+
+        /// Public key file to check the root signature against
+        #[arg(long)]
+        public_key: Option<String>,
+
+        /// Also check that the proof is for this .glyph file
+        #[arg(short, long)]
+        glyph: Option<String>,
+    },
+}
+
+#[derive(Subcommand, PartialEq, Debug)]
+enum TimevectorSubcommand {
+    #[clap(about = "encode a JSON5 timevector to binary .timevector format")]
+    Encode {
+        /// Input JSON5 file
+        #[arg(short, long)]
+        input: String,
+
+        /// Output .timevector file
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Sign the timevector with Ed25519
+        #[arg(short, long)]
+        sign: bool,
+
+        /// Private key file for signing
+        #[arg(long)]
+        private_key: Option<String>,
+    },
+    #[clap(about = "decode a binary .timevector file")]
+    Decode {
+        /// Input .timevector file
+        #[arg(short, long)]
+        input: String,
+
+        /// Output format (json, yaml)
+        #[arg(short, long, default_value = "json")]
+        format: String,
+
+        /// Output file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Verify signature if present
+        #[arg(short, long)]
+        verify: bool,
+
+        /// Public key file for verification
+        #[arg(long)]
+        public_key: Option<String>,
+    },
+    #[clap(about = "validate ranges and glyph references of a .timevector file")]
+    Validate {
+        /// Input .timevector file
+        #[arg(short, long)]
+        input: String,
+
+        /// Glyphs to resolve references against (.glyph files, directories or .glyphs streams)
+        #[arg(short, long)]
+        glyphs: Vec<String>,
+    },
+    #[clap(about = "export a .timevector file as a WebVTT or SRT caption track")]
+    Export {
+        /// Input .timevector file
+        #[arg(short, long)]
+        input: String,
+
+        /// Caption format (vtt, srt)
+        #[arg(short, long, default_value = "vtt")]
+        format: String,
+
+        /// Output file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Glyphs to take labels, ids and hashes from (.glyph files, directories or .glyphs streams)
+        #[arg(short, long)]
+        glyphs: Vec<String>,
+    },
+    #[clap(about = "rebuild a .timevector file from a WebVTT or SRT caption track")]
+    Import {
+        /// Input caption file
+        #[arg(short, long)]
+        input: String,
+
+        /// Video the captions belong to (required when the track does not name one)
+        #[arg(long)]
+        video: Option<String>,
+
+        /// Output .timevector file
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Sign the timevector with Ed25519
+        #[arg(short, long)]
+        sign: bool,
+
+        /// Private key file for signing
+        #[arg(long)]
+        private_key: Option<String>,
+    },
+}
+
 #[derive(Subcommand, PartialEq, Debug)]
 enum CompletionSubcommand {
     #[clap(about = "generate the autocompletion script for bash")]
@@ -174,24 +939,174 @@ pub fn cli_match() -> Result<()> {
     match &cli.command {
         Commands::Hazard => commands::hazard()?,
         Commands::Error => commands::simulate_error()?,
-        Commands::Encode { input, output, sign, private_key, generate_id, validate } => {
-            commands::encode(input, output.as_deref(), *sign, private_key.as_deref(), *generate_id, *validate)?
+        Commands::Encode {
+            input, output, sign, private_key, generate_id, validate, sigil,
+            qr, qr_output, qr_scale, qr_quiet_zone, qr_ec, qr_svg, qr_compact, qr_chunk_size, media, parent,
+        } => {
+            let options = commands::EncodeOptions {
+                sigil: *sigil,
+                qr: qr.then(|| QrOptions {
+                    scale: *qr_scale,
+                    quiet_zone: *qr_quiet_zone,
+                    ec_level: *qr_ec,
+                    svg: *qr_svg,
+                    compact: *qr_compact,
+                }),
+                qr_output: qr_output.as_deref(),
+                qr_chunk_size: *qr_chunk_size,
+                media: *media,
+                parent: parent.as_deref(),
+            };
+            commands::encode(
+                input,
+                output.as_deref(),
+                *sign,
+                private_key.as_deref(),
+                *generate_id,
+                *validate,
+                &options,
+            )?
         },
-        Commands::Decode { input, qr, format, output, verify, public_key, verbose, validate, extract, header_only, payload_only } => {
+        Commands::Decode {
+            input, qr, format, output, verify, public_key, verbose, validate, extract, header_only, payload_only,
+            extract_media, media_dir, perspective, quarantine,
+        } => {
+            let checks = commands::DecodeChecks {
+                validate: *validate,
+                verify: *verify,
+                public_key: public_key.as_deref(),
+                media_dir: media_dir.as_deref(),
+                quarantine: quarantine.as_deref(),
+            };
             commands::decode(
                 input, 
                 *qr, 
                 format, 
                 output.as_deref(), 
-                *verify, 
-                public_key.as_deref(), 
                 *verbose, 
-                *validate, 
+                &checks,
                 extract.as_deref(), 
                 *header_only, 
-                *payload_only
+                *payload_only,
+                extract_media.as_deref(),
+                perspective.as_deref(),
             )?
         },
+        Commands::Sigil { input, format, output, size } => {
+            commands::sigil(input, format, output.as_deref(), *size)?
+        }
+        Commands::Scan { input, output, format } => {
+            commands::scan(input, output.as_deref(), format)?
+        }
+        Commands::Fountain { subcommand } => match subcommand {
+            FountainSubcommand::Encode { input, gif, out_dir, frames, block_size, delay, scale, ec } => {
+                let qr_options = QrOptions {
+                    scale: *scale,
+                    ec_level: *ec,
+                    ..QrOptions::default()
+                };
+                commands::fountain_encode(
+                    input,
+                    gif.as_deref(),
+                    out_dir.as_deref(),
+                    *frames,
+                    *block_size,
+                    *delay,
+                    &qr_options,
+                )?
+            }
+            FountainSubcommand::Decode { input, output } => {
+                commands::fountain_decode(input, output.as_deref())?
+            }
+        },
+        Commands::Pack { subcommand } => match subcommand {
+            PackSubcommand::Create { input, exclude, output, sign, private_key } => {
+                commands::pack_create(input, exclude, output, *sign, private_key.as_deref())?
+            }
+            PackSubcommand::Verify { input, public_key } => {
+                commands::pack_verify(input, public_key.as_deref())?
+            }
+            PackSubcommand::Extract { input, output } => commands::pack_extract(input, output)?,
+            PackSubcommand::List { input } => commands::pack_list(input)?,
+        },
+        Commands::Proof { subcommand } => match subcommand {
+            ProofSubcommand::Root { corpus, output, sign, private_key } => {
+                commands::proof_root(corpus, output.as_deref(), *sign, private_key.as_deref())?
+            }
+            ProofSubcommand::Create { glyph, corpus, output } => {
+                commands::proof_create(glyph, corpus, output.as_deref())?
+            }
+            ProofSubcommand::Verify { proof, root, public_key, glyph } => {
+                commands::proof_verify(proof, root, public_key.as_deref(), glyph.as_deref())?
+            }
+        },
+        Commands::Diff { a, b, format, public_key } => {
+            commands::diff(a, b, format, public_key.as_deref())?
+        }
+        Commands::Merge { base, ours, theirs, output, favor, sign, private_key } => {
+            commands::merge(base, ours, theirs, output, *favor, *sign, private_key.as_deref())?
+        }
+        Commands::History { id, store, format } => commands::history(id, store, format)?,
+        Commands::Resolve { reference, store } => commands::resolve(reference, store)?,
+        Commands::Query { script, store, format, write } => commands::query(script, store, format, *write)?,
+        Commands::Infer { rules, store, ask, explain, format } => {
+            commands::infer(rules, store, ask.as_deref(), *explain, format)?
+        }
+        Commands::Dissonance { store, rules, keys, format } => {
+            commands::dissonance(store, rules.as_deref(), keys, format)?
+        }
+        Commands::Consensus { concept, store, trust, keys, output, format, sign, private_key } => {
+            let options = commands::ConsensusOptions {
+                trust: trust.as_deref(),
+                keys,
+                output: output.as_deref(),
+                sign: *sign,
+                private_key: private_key.as_deref(),
+            };
+            commands::consensus(concept, store, format, &options)?
+        }
+        Commands::Ingest { input, store, policy, keys, dry_run } => {
+            commands::ingest(input, store, policy, keys, *dry_run)?
+        }
+        Commands::Quarantine { subcommand } => match subcommand {
+            QuarantineSubcommand::List { store, format } => commands::quarantine_list(store, format)?,
+            QuarantineSubcommand::Show { entry, store, format } => commands::quarantine_show(entry, store, format)?,
+            QuarantineSubcommand::Release { entry, store } => commands::quarantine_release(entry, store)?,
+            QuarantineSubcommand::Purge { entry, store, older_than, all } => {
+                commands::quarantine_purge(entry, store, *older_than, *all)?
+            }
+            QuarantineSubcommand::Recheck { store, policy, keys, release } => {
+                commands::quarantine_recheck(store, policy, keys, *release)?
+            }
+        },
+        Commands::Search { text, store, namespace, truth_mode, min_confidence, limit, format } => {
+            commands::search(text, store, namespace.as_deref(), truth_mode.as_deref(), *min_confidence, *limit, format)?
+        }
+        Commands::Stream { subcommand } => match subcommand {
+            StreamSubcommand::Pack { input, output, no_index } => {
+                commands::stream_pack(input, output, !*no_index)?
+            }
+            StreamSubcommand::Unpack { input, output } => commands::stream_unpack(input, output)?,
+            StreamSubcommand::Cat { input, format } => commands::stream_cat(input, format)?,
+            StreamSubcommand::Ls { input } => commands::stream_ls(input)?,
+        },
+        Commands::Timevector { subcommand } => match subcommand {
+            TimevectorSubcommand::Encode { input, output, sign, private_key } => {
+                commands::timevector_encode(input, output.as_deref(), *sign, private_key.as_deref())?
+            }
+            TimevectorSubcommand::Decode { input, format, output, verify, public_key } => {
+                commands::timevector_decode(input, format, output.as_deref(), *verify, public_key.as_deref())?
+            }
+            TimevectorSubcommand::Validate { input, glyphs } => {
+                commands::timevector_validate(input, glyphs)?
+            }
+            TimevectorSubcommand::Export { input, format, output, glyphs } => {
+                commands::timevector_export(input, format, output.as_deref(), glyphs)?
+            }
+            TimevectorSubcommand::Import { input, video, output, sign, private_key } => {
+                commands::timevector_import(input, video.as_deref(), output.as_deref(), *sign, private_key.as_deref())?
+            }
+        },
         Commands::Completion {subcommand} => {
             let mut app = Cli::command();
             match subcommand {
@@ -0,0 +1,229 @@
+// Timevector <-> caption track (WebVTT / SRT) conversion.
+//
+// Every cue carries the human label followed by a machine-readable marker:
+//
+//   00:00:00.000 --> 00:00:04.000
+//   sun [[glyph=glyph:sun hash=blake3:9f2c... id=550e8400-...]]
+//
+// so glyph annotations can ride on standard subtitle tracks and be rebuilt
+// into a timevector on the other side. Cues without a marker are ignored on
+// import, which lets glyph cues coexist with ordinary subtitles.
+
+use std::str::FromStr;
+
+use super::glyph_decode::Glyph;
+use super::glyph_ref::Resolver;
+use super::timevector::{self, GlyphMapEntry, Timevector, TimevectorPayload};
+
+const MARKER_OPEN: &str = "[[";
+const MARKER_CLOSE: &str = "]]";
+
+// ============================================================================
+// DATA STRUCTURES
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CaptionFormat {
+    Vtt,
+    Srt,
+}
+
+impl FromStr for CaptionFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "vtt" | "webvtt" => Ok(CaptionFormat::Vtt),
+            "srt" => Ok(CaptionFormat::Srt),
+            _ => Err(format!("Unsupported caption format: {}", s)),
+        }
+    }
+}
+
+// ============================================================================
+// EXPORT
+// ============================================================================
+
+/// Render a timevector as a caption track. When `glyphs` is given, labels,
+/// ids and hashes are filled in from the glyphs each entry resolves to.
+pub fn export(tv: &Timevector, format: CaptionFormat, glyphs: Option<&[Glyph]>) -> String {
+    let mut out = String::new();
+
+    if format == CaptionFormat::Vtt {
+        out.push_str("WEBVTT\n\n");
+        out.push_str(&format!("NOTE video={}\n\n", tv.payload.video));
+    }
+
+    let resolver = glyphs.map(Resolver::new);
+    for (position, entry) in tv.payload.glyph_map.iter().enumerate() {
+        let glyph = resolver.as_ref().and_then(|resolver| timevector::resolve_reference(entry, resolver).ok());
+
+        let label = glyph
+            .map(|g| g.payload.label.clone())
+            .unwrap_or_else(|| entry.glyph.strip_prefix("glyph:").unwrap_or(&entry.glyph).to_string());
+
+        let mut marker = format!("glyph={}", entry.glyph);
+        if let Some(hash) = entry.hash.clone().or_else(|| glyph.map(|g| g.semantic_hash())) {
+            marker.push_str(&format!(" hash={}", hash));
+        }
+        if let Some(glyph) = glyph {
+            marker.push_str(&format!(" id={}", glyph.header.id));
+        }
+
+        if format == CaptionFormat::Srt {
+            out.push_str(&format!("{}\n", position + 1));
+        }
+        out.push_str(&format!(
+            "{} --> {}\n",
+            format_timestamp(entry.start, format),
+            format_timestamp(entry.end, format)
+        ));
+        out.push_str(&format!("{} {}{}{}\n\n", label, MARKER_OPEN, marker, MARKER_CLOSE));
+    }
+
+    out
+}
+
+fn format_timestamp(seconds: f64, format: CaptionFormat) -> String {
+    let total_ms = (seconds.max(0.0) * 1000.0).round() as u64;
+    let (hours, rest) = (total_ms / 3_600_000, total_ms % 3_600_000);
+    let (minutes, rest) = (rest / 60_000, rest % 60_000);
+    let (secs, ms) = (rest / 1000, rest % 1000);
+    let separator = if format == CaptionFormat::Srt { ',' } else { '.' };
+
+    format!("{:02}:{:02}:{:02}{}{:03}", hours, minutes, secs, separator, ms)
+}
+
+// ============================================================================
+// IMPORT
+// ============================================================================
+
+/// Rebuild a timevector from a WebVTT or SRT caption track. The video is
+/// taken from a `NOTE video=` block when present, otherwise from `video`.
+pub fn import(content: &str, video: Option<&str>) -> Result<Timevector, Box<dyn std::error::Error>> {
+    let content = content.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+
+    let mut noted_video = None;
+    let mut glyph_map = Vec::new();
+
+    for block in content.split("\n\n") {
+        let lines: Vec<&str> = block.lines().filter(|line| !line.trim().is_empty()).collect();
+        if lines.is_empty() {
+            continue;
+        }
+
+        if let Some(note) = lines[0].strip_prefix("NOTE") {
+            if let Some(url) = note.trim().strip_prefix("video=") {
+                noted_video = Some(url.trim().to_string());
+            }
+            continue;
+        }
+
+        // The timing line may be preceded by a cue number or identifier
+        let Some(timing_pos) = lines.iter().position(|line| line.contains("-->")) else {
+            continue;
+        };
+        let (start, end) = parse_timing(lines[timing_pos])?;
+
+        let text = lines[timing_pos + 1..].join(" ");
+        if let Some(entry) = parse_marker(&text, start, end)? {
+            glyph_map.push(entry);
+        }
+    }
+
+    let video = noted_video
+        .or_else(|| video.map(|v| v.to_string()))
+        .ok_or("Caption track does not name a video; pass one explicitly")?;
+
+    Timevector::from_payload(TimevectorPayload { video, glyph_map })
+}
+
+fn parse_timing(line: &str) -> Result<(f64, f64), Box<dyn std::error::Error>> {
+    let (start, rest) = line.split_once("-->").ok_or("Invalid cue timing")?;
+    // WebVTT allows cue settings after the end timestamp
+    let end = rest.split_whitespace().next().ok_or("Invalid cue timing")?;
+
+    Ok((parse_timestamp(start.trim())?, parse_timestamp(end)?))
+}
+
+fn parse_timestamp(timestamp: &str) -> Result<f64, Box<dyn std::error::Error>> {
+    let normalized = timestamp.replace(',', ".");
+    let parts: Vec<&str> = normalized.split(':').collect();
+
+    let (hours, minutes, seconds) = match parts.as_slice() {
+        [h, m, s] => (h.parse::<f64>()?, m.parse::<f64>()?, s.parse::<f64>()?),
+        [m, s] => (0.0, m.parse::<f64>()?, s.parse::<f64>()?),
+        _ => return Err(format!("Invalid timestamp: {}", timestamp).into()),
+    };
+
+    Ok(hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+fn parse_marker(text: &str, start: f64, end: f64) -> Result<Option<GlyphMapEntry>, Box<dyn std::error::Error>> {
+    let Some(open) = text.rfind(MARKER_OPEN) else {
+        return Ok(None);
+    };
+    let body = &text[open + MARKER_OPEN.len()..];
+    let close = body.find(MARKER_CLOSE).ok_or("Unterminated glyph marker in cue")?;
+
+    let mut glyph = None;
+    let mut hash = None;
+    let mut id = None;
+    for pair in body[..close].split_whitespace() {
+        match pair.split_once('=') {
+            Some(("glyph", value)) => glyph = Some(value.to_string()),
+            Some(("hash", value)) => hash = Some(value.to_string()),
+            Some(("id", value)) => id = Some(value.to_string()),
+            _ => {} // Unknown keys are reserved for future use
+        }
+    }
+
+    let glyph = glyph
+        .or_else(|| id.map(|id| format!("glyph:{}", id)))
+        .ok_or("Glyph marker carries neither a glyph reference nor an id")?;
+
+    Ok(Some(GlyphMapEntry { glyph, start, end, hash }))
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_timevector() -> Timevector {
+        Timevector::from_payload(TimevectorPayload {
+            video: "https://youtu.be/abc123".to_string(),
+            glyph_map: vec![
+                GlyphMapEntry { glyph: "glyph:sun".to_string(), start: 0.0, end: 4.0, hash: Some("blake3:aa".to_string()) },
+                GlyphMapEntry { glyph: "glyph:tree".to_string(), start: 5.0, end: 3723.25, hash: None },
+            ],
+        }).unwrap()
+    }
+
+    #[test]
+    fn test_vtt_roundtrip() {
+        let tv = test_timevector();
+        let vtt = export(&tv, CaptionFormat::Vtt, None);
+        assert!(vtt.starts_with("WEBVTT"));
+        assert!(vtt.contains("01:02:03.250"));
+        assert!(vtt.contains("sun [[glyph=glyph:sun hash=blake3:aa]]"));
+
+        let imported = import(&vtt, None).unwrap();
+        assert_eq!(imported.payload.video, tv.payload.video);
+        assert_eq!(imported.header.hash, tv.header.hash);
+    }
+
+    #[test]
+    fn test_srt_import_requires_video() {
+        let srt = export(&test_timevector(), CaptionFormat::Srt, None);
+        assert!(srt.starts_with("1\n00:00:00,000 --> 00:00:04,000"));
+        assert!(import(&srt, None).is_err());
+
+        let imported = import(&srt, Some("https://youtu.be/abc123")).unwrap();
+        assert_eq!(imported.payload.glyph_map.len(), 2);
+        assert_eq!(imported.payload.glyph_map[1].end, 3723.25);
+    }
+}
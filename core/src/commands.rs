@@ -2,10 +2,35 @@ use super::error;
 use super::hazard;
 use super::glyph_encode;
 use super::glyph_decode;
+use super::glyph_stream;
+use super::timevector;
+use super::captions;
+use super::sigil;
+use super::qr_chunk;
+use super::qr_fountain;
+use super::qr_scan;
+use super::svg_sanitize;
+use super::media;
+use super::glyph_pack;
+use super::merkle;
+use super::glyph_diff;
+use super::glyph_merge;
+use super::glyph_sign;
+use super::lineage;
+use super::glyph_ref;
+use super::glyphscript;
+use super::inference;
+use super::trust;
+use super::dissonance;
+use super::consensus;
+use super::firewall;
+use super::quarantine;
+use super::search;
 
 use utils::app_config::AppConfig;
 use utils::error::Result;
 use std::fs;
+use std::path::Path;
 
 /// Show the configuration file
 pub fn hazard() -> Result<()> {
@@ -41,6 +66,22 @@ pub fn simulate_error() -> Result<()> {
     Ok(())
 }
 
+/// Sigil, QR, media and lineage options of `encode`
+#[derive(Debug, Default)]
+pub struct EncodeOptions<'a> {
+    /// Fill visual.sigil from the semantic hash
+    pub sigil: bool,
+    /// Also render the glyph as one or more QR codes
+    pub qr: Option<glyph_encode::QrOptions>,
+    pub qr_output: Option<&'a str>,
+    /// Split over several codes of at most this many bytes
+    pub qr_chunk_size: Option<usize>,
+    /// Attach the files named by audio.file / visual.file
+    pub media: Option<media::MediaMode>,
+    /// Earlier revision of the glyph
+    pub parent: Option<&'a str>,
+}
+
 /// Encode JSON5 glyph files to binary .glyph format
 pub fn encode(
     input: &str,
@@ -49,6 +90,7 @@ pub fn encode(
     private_key: Option<&str>,
     generate_id: bool,
     validate: bool,
+    options: &EncodeOptions,
 ) -> Result<()> {
     // Read input file
     let json5_content = fs::read_to_string(input)?;
@@ -60,6 +102,73 @@ pub fn encode(
     // Generate ID if requested
     if generate_id {
         glyph.header.id = uuid::Uuid::new_v4().to_string();
+        glyph.header.lineage = Some(lineage::Lineage::root(&glyph.header.id));
+    }
+    
+    // Record this glyph as the next revision of an earlier one
+    if let Some(parent_path) = options.parent {
+        let parent_glyph = glyph_decode::Glyph::from_cbor(&fs::read(parent_path)?)
+            .map_err(|e| utils::error::Error::new(&format!("{}: {}", parent_path, e)))?;
+        // The parent link names the stored hash, so it has to be the real one
+        if parent_glyph.header.hash != parent_glyph.semantic_hash() {
+            return Err(utils::error::Error::new(&format!(
+                "{}: stored hash {} does not match its payload ({})",
+                parent_path, parent_glyph.header.hash, parent_glyph.semantic_hash()
+            )));
+        }
+        let next = lineage::Lineage::next(&parent_glyph);
+        println!("✓ Revision {} of {} (parent {})", next.revision, next.concept_id, parent_glyph.header.hash);
+        glyph.header.lineage = Some(next);
+    }
+    
+    // Attach the files named by audio.file / visual.file, resolved next to the input
+    if let Some(mode) = options.media {
+        let base = Path::new(input).parent().unwrap_or(Path::new(""));
+        let audio_file = glyph.payload.audio.file.clone();
+        let visual_file = glyph.payload.visual.file.clone();
+        
+        if audio_file.is_none() && visual_file.is_none() {
+            eprintln!("Warning: --media specified but neither audio.file nor visual.file is set");
+        }
+        for (file, slot) in [
+            (audio_file, &mut glyph.payload.audio.media),
+            (visual_file, &mut glyph.payload.visual.media),
+        ] {
+            let Some(file) = file else { continue };
+            let attachment = media::MediaAttachment::from_file(&base.join(&file), mode)
+                .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+            println!(
+                "✓ Media {} {} ({} bytes, {})",
+                if attachment.is_embedded() { "embedded:" } else { "referenced:" },
+                attachment.name,
+                attachment.size,
+                attachment.digest
+            );
+            *slot = Some(attachment);
+        }
+        // Attachment digests are part of the semantic hash
+        glyph.header.hash = glyph.semantic_hash();
+    }
+    
+    // Inline SVG is always sanitized before it is stored
+    if let Some(svg) = &glyph.payload.visual.svg {
+        let sanitized = svg_sanitize::sanitize(svg)
+            .map_err(|e| utils::error::Error::new(&format!("visual.svg rejected: {}", e)))?;
+        for removed in &sanitized.removed {
+            eprintln!("⚠ Removed from visual.svg: {}", removed);
+        }
+        glyph.payload.visual.svg = Some(sanitized.svg);
+    }
+    
+    // Fill visual.sigil from the semantic hash if requested
+    if options.sigil {
+        let style = glyph.payload.visual.style.as_ref();
+        let generated = sigil::Sigil::generate(
+            &glyph.header.hash,
+            style.and_then(|s| s.color.as_deref()),
+            style.and_then(|s| s.stroke),
+        ).map_err(|e| utils::error::Error::new(&e.to_string()))?;
+        glyph.payload.visual.sigil = Some(generated.to_data_uri());
     }
     
     // Sign if requested
@@ -104,6 +213,44 @@ pub fn encode(
     fs::write(&output_path, cbor_data)?;
     println!("✓ Encoded glyph written to: {}", output_path);
     
+    // Generate QR code if requested
+    if let Some(qr_options) = &options.qr {
+        let qr_output_path = if let Some(qr_output) = options.qr_output {
+            qr_output.to_string()
+        } else {
+            let input_path = std::path::Path::new(input);
+            let stem = input_path.file_stem().unwrap().to_str().unwrap();
+            format!("{}.{}", stem, if qr_options.svg { "svg" } else { "png" })
+        };
+        
+        // Fall back to several codes when the glyph does not fit in one
+        let single = match options.qr_chunk_size {
+            Some(_) => None,
+            None => Some(glyph.to_qr_code_with(qr_options, None)),
+        };
+        
+        match single {
+            Some(Ok(qr_data)) => {
+                fs::write(&qr_output_path, qr_data)?;
+                println!("✓ QR code written to: {}", qr_output_path);
+            }
+            _ => {
+                let chunk_size = options.qr_chunk_size.unwrap_or(qr_chunk::DEFAULT_CHUNK_SIZE);
+                let images = glyph.to_qr_codes(qr_options, chunk_size)
+                    .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+                
+                let path = Path::new(&qr_output_path);
+                let stem = path.file_stem().unwrap().to_str().unwrap();
+                let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("png");
+                for (index, qr_data) in images.iter().enumerate() {
+                    let chunk_path = path.with_file_name(format!("{}-{}of{}.{}", stem, index + 1, images.len(), extension));
+                    fs::write(&chunk_path, qr_data)?;
+                    println!("✓ QR code {}/{} written to: {}", index + 1, images.len(), chunk_path.display());
+                }
+            }
+        }
+    }
+    
     // Also write JSON companion
     let json_companion = format!("{}.json", output_path);
     let json_content = serde_json::to_string_pretty(&glyph)?;
@@ -113,29 +260,48 @@ pub fn encode(
     Ok(())
 }
 
+/// Validation and verification options of `decode`
+#[derive(Debug, Default)]
+pub struct DecodeChecks<'a> {
+    pub validate: bool,
+    /// Check the hash, the signature against `public_key` and media digests
+    pub verify: bool,
+    pub public_key: Option<&'a str>,
+    /// Where referenced media is looked up (defaults to the glyph's directory)
+    pub media_dir: Option<&'a str>,
+    /// Store whose quarantine receives glyphs that fail a check
+    pub quarantine: Option<&'a str>,
+}
+
 /// Decode binary .glyph files to human-readable formats
 pub fn decode(
-    input: &str,
+    inputs: &[String],
     qr: bool,
     format: &str,
     output: Option<&str>,
-    verify: bool,
-    public_key: Option<&str>,
     verbose: bool,
-    validate: bool,
+    checks: &DecodeChecks,
     extract: Option<&str>,
     header_only: bool,
     payload_only: bool,
+    extract_media: Option<&str>,
+    perspective: Option<&str>,
 ) -> Result<()> {
-    // Read input file
-    let input_data = fs::read(input)?;
+    // Read input files; only QR images may be split over several inputs
+    if inputs.len() != 1 && !qr {
+        return Err(utils::error::Error::new("Multiple inputs are only supported with --qr"));
+    }
+    let input_data = inputs.iter()
+        .map(fs::read)
+        .collect::<std::io::Result<Vec<Vec<u8>>>>()?;
     
     // Decode glyph based on input type
     let glyph = if qr {
-        glyph_decode::Glyph::from_qr_code(&input_data)
+        let images: Vec<&[u8]> = input_data.iter().map(|data| data.as_slice()).collect();
+        glyph_decode::Glyph::from_qr_codes(&images)
             .map_err(|e| utils::error::Error::new(&e.to_string()))?
     } else {
-        glyph_decode::Glyph::from_cbor(&input_data)
+        glyph_decode::Glyph::from_cbor(&input_data[0])
             .map_err(|e| utils::error::Error::new(&e.to_string()))?
     };
     
@@ -145,25 +311,32 @@ pub fn decode(
         println!();
     }
     
+    // Failed checks, collected so the glyph can be quarantined before exiting
+    let mut failures = Vec::new();
+
     // Validate if requested
-    if validate {
+    if checks.validate {
         match glyph.validate() {
             Ok(()) => println!("✓ Glyph validation passed"),
             Err(errors) => {
                 eprintln!("✗ Glyph validation failed:");
-                for error in errors {
+                for error in &errors {
                     eprintln!("  - {}", error);
                 }
-                if !verbose {
-                    std::process::exit(1);
-                }
+                failures.extend(errors);
             }
         }
     }
     
     // Verify signature if requested
-    if verify {
-        if let Some(key_path) = public_key {
+    if checks.verify {
+        // The signature covers the stored hash, which must match the payload
+        if glyph.header.hash != glyph.semantic_hash() {
+            eprintln!("✗ Hash does not match the payload");
+            failures.push("hash does not match the payload".to_string());
+        }
+        
+        if let Some(key_path) = checks.public_key {
             let public_key_bytes = fs::read(key_path)?;
             match glyph.verify_signature(&public_key_bytes)
                 .map_err(|e| utils::error::Error::new(&e.to_string())) {
@@ -171,25 +344,79 @@ pub fn decode(
                 Ok(false) => {
                     if glyph.header.signature.is_some() {
                         eprintln!("✗ Signature verification failed");
-                        if !verbose {
-                            std::process::exit(1);
-                        }
+                        failures.push("signature verification failed".to_string());
                     } else {
                         println!("ℹ No signature to verify");
                     }
                 }
                 Err(e) => {
                     eprintln!("✗ Signature verification error: {}", e);
-                    if !verbose {
-                        std::process::exit(1);
-                    }
+                    failures.push(format!("signature verification error: {}", e));
                 }
             }
         } else {
             eprintln!("Warning: --verify specified but no --public-key provided");
         }
+        
+        // Referenced media is looked up next to the glyph unless told otherwise
+        let media_dir = match checks.media_dir {
+            Some(dir) => Path::new(dir).to_path_buf(),
+            None => Path::new(&inputs[0]).parent().unwrap_or(Path::new("")).to_path_buf(),
+        };
+        for attachment in [&glyph.payload.audio.media, &glyph.payload.visual.media].into_iter().flatten() {
+            match attachment.verify(Some(&media_dir)) {
+                Ok(()) => println!("✓ Media verification passed: {}", attachment.name),
+                Err(e) => {
+                    eprintln!("✗ Media verification failed: {}", e);
+                    failures.push(format!("media verification failed: {}", e));
+                }
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        if let Some(store) = checks.quarantine {
+            // QR input has no single file, so the decoded glyph is kept instead
+            let bytes = if qr {
+                glyph.to_cbor().map_err(|e| utils::error::Error::new(&e.to_string()))?
+            } else {
+                input_data[0].clone()
+            };
+            let entry = quarantine::Quarantine::of_store(store)
+                .add(&inputs.join(", "), &bytes, Some(&glyph), None, failures)
+                .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+            eprintln!("ℹ Quarantined as {} in {}", entry.entry, store);
+        }
+        if !verbose {
+            std::process::exit(1);
+        }
+    }
+    
+    // Write embedded media out if requested
+    if let Some(dir) = extract_media {
+        for attachment in [&glyph.payload.audio.media, &glyph.payload.visual.media].into_iter().flatten() {
+            if attachment.is_embedded() {
+                let path = attachment.extract(Path::new(dir))
+                    .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+                println!("✓ Media extracted to: {}", path.display());
+            } else {
+                println!("ℹ {} is referenced, not embedded ({})", attachment.name, attachment.digest);
+            }
+        }
     }
     
+    // Show the glyph as one perspective sees it; verification above used the canonical glyph
+    let glyph = match perspective {
+        Some(agent) => {
+            if !glyph.payload.namespace.contextual_overlays.iter().any(|overlay| overlay.agent == agent) {
+                eprintln!("Warning: no contextual overlay for {}; keeping the canonical label", agent);
+            }
+            glyph.view(agent)
+                .map_err(|e| utils::error::Error::new(&e.to_string()))?
+        }
+        None => glyph,
+    };
+    
     // Extract specific field if requested
     if let Some(field_path) = extract {
         let value = glyph.extract_field(field_path)
@@ -231,3 +458,1504 @@ pub fn decode(
     
     Ok(())
 }
+
+/// A glyph label reduced to one safe file name component: anything but
+/// letters, digits, `-` and `_` becomes `_`
+fn label_file_stem(label: &str) -> String {
+    let stem: String = label.chars()
+        .take(64)
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    if stem.is_empty() { "glyph".to_string() } else { stem }
+}
+
+/// Collect .glyph files from a list of files and directories
+fn collect_glyph_files(inputs: &[String]) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+
+    for input in inputs {
+        let path = Path::new(input);
+        if path.is_dir() {
+            let mut entries: Vec<String> = fs::read_dir(path)?
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|p| p.extension().is_some_and(|ext| ext == "glyph"))
+                .map(|p| p.to_string_lossy().to_string())
+                .collect();
+            entries.sort();
+            files.extend(entries);
+        } else {
+            files.push(input.clone());
+        }
+    }
+
+    Ok(files)
+}
+
+/// Pack .glyph files into a .glyphs stream
+pub fn stream_pack(inputs: &[String], output: &str, index: bool) -> Result<()> {
+    let files = collect_glyph_files(inputs)?;
+
+    let file = std::io::BufWriter::new(fs::File::create(output)?);
+    let mut writer = glyph_stream::GlyphStreamWriter::new(file)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+    for path in &files {
+        let cbor_data = fs::read(path)?;
+        writer.write_raw(&cbor_data)
+            .map_err(|e| utils::error::Error::new(&format!("{}: {}", path, e)))?;
+    }
+
+    let count = writer.len();
+    writer.finish(index)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+    println!("✓ Packed {} glyphs into: {}", count, output);
+
+    Ok(())
+}
+
+/// Unpack a .glyphs stream into individual .glyph files
+pub fn stream_unpack(input: &str, output_dir: &str) -> Result<()> {
+    let file = std::io::BufReader::new(fs::File::open(input)?);
+    let mut reader = glyph_stream::GlyphStreamReader::new(file)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+    fs::create_dir_all(output_dir)?;
+
+    let mut count = 0;
+    while let Some(record) = reader.next_raw() {
+        let cbor_data = record.map_err(|e| utils::error::Error::new(&e.to_string()))?;
+        let glyph = glyph_decode::Glyph::from_cbor(&cbor_data)
+            .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+        let file_name = glyph.file_name()
+            .map_err(|e| utils::error::Error::new(&format!("Record {}: {}", count, e)))?;
+        let output_path = Path::new(output_dir).join(file_name);
+        // A repeated id must not silently replace an earlier record
+        if output_path.exists() && fs::read(&output_path)? != cbor_data {
+            return Err(utils::error::Error::new(&format!(
+                "Record {}: a different glyph is already at {}", count, output_path.display()
+            )));
+        }
+        fs::write(&output_path, &cbor_data)?;
+        count += 1;
+    }
+
+    println!("✓ Unpacked {} glyphs into: {}", count, output_dir);
+
+    Ok(())
+}
+
+/// Print every glyph in a .glyphs stream
+pub fn stream_cat(input: &str, format: &str) -> Result<()> {
+    let file = std::io::BufReader::new(fs::File::open(input)?);
+    let reader = glyph_stream::GlyphStreamReader::new(file)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+    for record in reader {
+        let glyph = record.map_err(|e| utils::error::Error::new(&e.to_string()))?;
+        let content = match format {
+            "json" => serde_json::to_string(&glyph)?,
+            "yaml" => glyph.to_yaml()
+                .map_err(|e| utils::error::Error::new(&e.to_string()))?,
+            "text" => glyph.to_text()
+                .map_err(|e| utils::error::Error::new(&e.to_string()))?,
+            _ => {
+                eprintln!("Unsupported format: {}. Using JSON.", format);
+                serde_json::to_string(&glyph)?
+            }
+        };
+        println!("{}", content);
+    }
+
+    Ok(())
+}
+
+/// List the glyphs in a .glyphs stream
+pub fn stream_ls(input: &str) -> Result<()> {
+    let mut file = fs::File::open(input)?;
+
+    // Prefer the index footer, fall back to a sequential scan
+    let index = glyph_stream::read_index(&mut file)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+    if let Some(index) = index {
+        for (position, entry) in index.entries.iter().enumerate() {
+            println!("{:>6}  {}  {}  {}", position, entry.id, entry.hash, entry.label);
+        }
+        return Ok(());
+    }
+
+    let file = std::io::BufReader::new(fs::File::open(input)?);
+    let reader = glyph_stream::GlyphStreamReader::new(file)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+    for (position, record) in reader.enumerate() {
+        let glyph = record.map_err(|e| utils::error::Error::new(&e.to_string()))?;
+        println!("{:>6}  {}  {}  {}", position, glyph.header.id, glyph.header.hash, glyph.payload.label);
+    }
+
+    Ok(())
+}
+
+/// Load glyphs from .glyph files, directories and .glyphs streams
+fn load_glyphs(inputs: &[String]) -> Result<Vec<glyph_decode::Glyph>> {
+    let mut glyphs = Vec::new();
+
+    let (streams, files): (Vec<String>, Vec<String>) = inputs.iter()
+        .cloned()
+        .partition(|input| input.ends_with(".glyphs"));
+
+    for path in collect_glyph_files(&files)? {
+        let cbor_data = fs::read(&path)?;
+        let glyph = glyph_decode::Glyph::from_cbor(&cbor_data)
+            .map_err(|e| utils::error::Error::new(&format!("{}: {}", path, e)))?;
+        glyphs.push(glyph);
+    }
+
+    for path in streams {
+        let file = std::io::BufReader::new(fs::File::open(&path)?);
+        let reader = glyph_stream::GlyphStreamReader::new(file)
+            .map_err(|e| utils::error::Error::new(&format!("{}: {}", path, e)))?;
+        for record in reader {
+            glyphs.push(record.map_err(|e| utils::error::Error::new(&format!("{}: {}", path, e)))?);
+        }
+    }
+
+    Ok(glyphs)
+}
+
+/// Encode a JSON5 timevector to binary .timevector format
+pub fn timevector_encode(
+    input: &str,
+    output: Option<&str>,
+    sign: bool,
+    private_key: Option<&str>,
+) -> Result<()> {
+    let json5_content = fs::read_to_string(input)?;
+    let mut tv = timevector::Timevector::from_json5(&json5_content)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+    if sign {
+        if let Some(key_path) = private_key {
+            let private_key_bytes = fs::read(key_path)?;
+            tv.sign(&private_key_bytes)
+                .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+        } else {
+            eprintln!("Warning: --sign specified but no --private-key provided");
+        }
+    }
+
+    let cbor_data = tv.to_cbor()
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+    let output_path = if let Some(output) = output {
+        output.to_string()
+    } else {
+        let input_path = Path::new(input);
+        let stem = input_path.file_stem().unwrap().to_str().unwrap();
+        format!("{}.timevector", stem.trim_end_matches(".timevector"))
+    };
+
+    fs::write(&output_path, cbor_data)?;
+    println!("✓ Encoded timevector written to: {}", output_path);
+
+    Ok(())
+}
+
+/// Decode a binary .timevector file
+pub fn timevector_decode(
+    input: &str,
+    format: &str,
+    output: Option<&str>,
+    verify: bool,
+    public_key: Option<&str>,
+) -> Result<()> {
+    let cbor_data = fs::read(input)?;
+    let tv = timevector::Timevector::from_cbor(&cbor_data)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+    if verify {
+        // The signature covers the stored hash, so that hash must match the content
+        let content_hash = tv.content_hash()
+            .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+        if tv.header.hash != content_hash {
+            eprintln!("✗ Hash does not match timevector content");
+            std::process::exit(1);
+        }
+        println!("✓ Content hash verified");
+
+        if let Some(key_path) = public_key {
+            let public_key_bytes = fs::read(key_path)?;
+            match tv.verify_signature(&public_key_bytes) {
+                Ok(true) => println!("✓ Signature verification passed"),
+                Ok(false) => {
+                    if tv.header.signature.is_some() {
+                        eprintln!("✗ Signature verification failed");
+                        std::process::exit(1);
+                    } else {
+                        println!("ℹ No signature to verify");
+                    }
+                }
+                Err(e) => {
+                    eprintln!("✗ Signature verification error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        } else {
+            eprintln!("Warning: --verify specified but no --public-key provided");
+        }
+    }
+
+    let output_content = match format {
+        "json" | "json5" => tv.to_json()
+            .map_err(|e| utils::error::Error::new(&e.to_string()))?,
+        "yaml" => tv.to_yaml()
+            .map_err(|e| utils::error::Error::new(&e.to_string()))?,
+        _ => {
+            eprintln!("Unsupported format: {}. Using JSON.", format);
+            tv.to_json()
+                .map_err(|e| utils::error::Error::new(&e.to_string()))?
+        }
+    };
+
+    if let Some(output_path) = output {
+        fs::write(output_path, output_content)?;
+        println!("✓ Decoded timevector written to output file");
+    } else {
+        println!("{}", output_content);
+    }
+
+    Ok(())
+}
+
+/// Validate a .timevector file, optionally resolving its glyph references
+pub fn timevector_validate(input: &str, glyphs: &[String]) -> Result<()> {
+    let cbor_data = fs::read(input)?;
+    let tv = timevector::Timevector::from_cbor(&cbor_data)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+    let known = if glyphs.is_empty() {
+        None
+    } else {
+        Some(load_glyphs(glyphs)?)
+    };
+
+    match tv.validate(known.as_deref()) {
+        Ok(()) => println!("✓ Timevector validation passed"),
+        Err(errors) => {
+            eprintln!("✗ Timevector validation failed:");
+            for error in errors {
+                eprintln!("  - {}", error);
+            }
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
+
+/// Export a .timevector file as a WebVTT or SRT caption track
+pub fn timevector_export(input: &str, format: &str, output: Option<&str>, glyphs: &[String]) -> Result<()> {
+    let caption_format: captions::CaptionFormat = format.parse()
+        .map_err(|e: String| utils::error::Error::new(&e))?;
+
+    let cbor_data = fs::read(input)?;
+    let tv = timevector::Timevector::from_cbor(&cbor_data)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+    let known = if glyphs.is_empty() {
+        None
+    } else {
+        Some(load_glyphs(glyphs)?)
+    };
+
+    let track = captions::export(&tv, caption_format, known.as_deref());
+
+    if let Some(output_path) = output {
+        fs::write(output_path, track)?;
+        println!("✓ Caption track written to: {}", output_path);
+    } else {
+        print!("{}", track);
+    }
+
+    Ok(())
+}
+
+/// Rebuild a .timevector file from a WebVTT or SRT caption track
+pub fn timevector_import(
+    input: &str,
+    video: Option<&str>,
+    output: Option<&str>,
+    sign: bool,
+    private_key: Option<&str>,
+) -> Result<()> {
+    let content = fs::read_to_string(input)?;
+    let mut tv = captions::import(&content, video)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+    if sign {
+        if let Some(key_path) = private_key {
+            let private_key_bytes = fs::read(key_path)?;
+            tv.sign(&private_key_bytes)
+                .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+        } else {
+            eprintln!("Warning: --sign specified but no --private-key provided");
+        }
+    }
+
+    let cbor_data = tv.to_cbor()
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+    let output_path = if let Some(output) = output {
+        output.to_string()
+    } else {
+        let stem = Path::new(input).file_stem().unwrap().to_str().unwrap();
+        format!("{}.timevector", stem)
+    };
+
+    fs::write(&output_path, cbor_data)?;
+    println!("✓ Imported {} glyph references into: {}", tv.payload.glyph_map.len(), output_path);
+
+    Ok(())
+}
+
+/// Render the deterministic sigil of a .glyph file as SVG or PNG
+pub fn sigil(input: &str, format: &str, output: Option<&str>, size: u32) -> Result<()> {
+    let cbor_data = fs::read(input)?;
+    let glyph = glyph_decode::Glyph::from_cbor(&cbor_data)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+    let style = glyph.payload.visual.style.as_ref();
+    let generated = sigil::Sigil::generate(
+        &glyph.header.hash,
+        style.and_then(|s| s.color.as_deref()),
+        style.and_then(|s| s.stroke),
+    ).map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+    let (content, extension) = match format {
+        "svg" => (generated.to_svg().into_bytes(), "svg"),
+        "png" => (
+            generated.to_png(size).map_err(|e| utils::error::Error::new(&e.to_string()))?,
+            "png",
+        ),
+        _ => return Err(utils::error::Error::new(&format!("Unsupported sigil format: {}", format))),
+    };
+
+    let output_path = if let Some(output) = output {
+        output.to_string()
+    } else {
+        let stem = Path::new(input).file_stem().unwrap().to_str().unwrap();
+        format!("{}.sigil.{}", stem, extension)
+    };
+
+    fs::write(&output_path, content)?;
+    println!("✓ Sigil written to: {}", output_path);
+
+    Ok(())
+}
+
+/// Render a .glyph file as a fountain-coded QR sequence (animated GIF or PNG frames)
+pub fn fountain_encode(
+    input: &str,
+    gif: Option<&str>,
+    out_dir: Option<&str>,
+    frames: Option<usize>,
+    block_size: usize,
+    frame_delay_ms: u32,
+    qr: &glyph_encode::QrOptions,
+) -> Result<()> {
+    let cbor_data = fs::read(input)?;
+    // Make sure the input really is a glyph before spending time on frames
+    glyph_decode::Glyph::from_cbor(&cbor_data)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+    let mut encoder = qr_fountain::FountainEncoder::new(&cbor_data, block_size)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+    let k = encoder.k();
+    // Twice the source blocks leaves room for plenty of dropped frames
+    let count = frames.unwrap_or(k * 2).max(k);
+
+    let images = qr_fountain::render_frames(&mut encoder, count, qr)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+    match (gif, out_dir) {
+        (Some(gif), None) => {
+            let file = fs::File::create(gif)?;
+            qr_fountain::write_gif(&images, frame_delay_ms, std::io::BufWriter::new(file))
+                .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+            println!("✓ Fountain QR animation written to: {} ({} frames, {} needed)", gif, count, k);
+        }
+        (None, Some(dir)) => {
+            fs::create_dir_all(dir)?;
+            for (index, image) in images.iter().enumerate() {
+                let path = Path::new(dir).join(format!("frame_{:05}.png", index));
+                image.save(&path).map_err(|e| utils::error::Error::new(&e.to_string()))?;
+            }
+            println!("✓ Fountain QR frames written to: {} ({} frames, {} needed)", dir, count, k);
+        }
+        _ => return Err(utils::error::Error::new("Specify exactly one of --gif or --out-dir")),
+    }
+
+    Ok(())
+}
+
+/// Rebuild a .glyph file from fountain QR frames (a GIF, or a directory of images)
+pub fn fountain_decode(input: &str, output: Option<&str>) -> Result<()> {
+    let paths: Vec<std::path::PathBuf> = if Path::new(input).is_dir() {
+        let mut paths: Vec<_> = fs::read_dir(input)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.is_file())
+            .collect();
+        paths.sort();
+        paths
+    } else {
+        vec![Path::new(input).to_path_buf()]
+    };
+
+    // Frames are loaded lazily so decoding stops at the first sufficient subset
+    let frames = paths.iter().flat_map(|path| {
+        match fs::File::open(path).map_err(Box::<dyn std::error::Error>::from)
+            .and_then(|file| qr_fountain::load_frames(std::io::BufReader::new(file)))
+        {
+            Ok(frames) => frames,
+            Err(e) => {
+                warn!("Skipping {}: {}", path.display(), e);
+                Vec::new()
+            }
+        }
+    });
+
+    let mut diagnostics = Vec::new();
+    let decoded = qr_fountain::decode_frames(frames, &mut diagnostics);
+    for diagnostic in &diagnostics {
+        eprintln!("⚠ Skipped droplet in {}", diagnostic);
+    }
+    let cbor_data = decoded.map_err(|e| utils::error::Error::new(&e.to_string()))?;
+    let glyph = glyph_decode::Glyph::from_cbor(&cbor_data)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+    let output_path = match output {
+        Some(output) => output.to_string(),
+        None => format!("{}.glyph", label_file_stem(&glyph.payload.label)),
+    };
+    fs::write(&output_path, &cbor_data)?;
+    println!("✓ Glyph '{}' recovered to: {}", glyph.payload.label, output_path);
+
+    Ok(())
+}
+
+/// Scan QR images (files or directories) for every glyph they contain
+pub fn scan(inputs: &[String], output: Option<&str>, format: &str) -> Result<()> {
+    let mut scanner = qr_scan::Scanner::new();
+    for input in inputs {
+        scanner.scan_path(Path::new(input));
+    }
+    let result = scanner.finish();
+
+    match format {
+        "json" => {
+            let glyphs: Vec<serde_json::Value> = result.glyphs.iter()
+                .map(|scanned| serde_json::json!({
+                    "label": scanned.glyph.payload.label,
+                    "id": scanned.glyph.header.id,
+                    "hash": scanned.glyph.header.hash,
+                    "sources": scanned.sources,
+                }))
+                .collect();
+            let report = serde_json::json!({
+                "glyphs": glyphs,
+                "images": result.reports,
+                "errors": result.errors,
+            });
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        "text" => {
+            for report in &result.reports {
+                let mut line = format!("{}: {} code(s)", report.source, report.codes);
+                if !report.glyphs.is_empty() {
+                    line.push_str(&format!(", glyphs: {}", report.glyphs.join(", ")));
+                }
+                if report.chunks > 0 {
+                    line.push_str(&format!(", {} chunk(s)", report.chunks));
+                }
+                println!("{}", line);
+                for diagnostic in &report.diagnostics {
+                    println!("  ✗ {}", diagnostic);
+                }
+            }
+            for error in &result.errors {
+                eprintln!("✗ {}", error);
+            }
+            println!();
+            for scanned in &result.glyphs {
+                println!(
+                    "✓ {} {} (seen in {} image(s))",
+                    scanned.glyph.payload.label,
+                    scanned.glyph.header.hash,
+                    scanned.sources.len()
+                );
+            }
+        }
+        _ => return Err(utils::error::Error::new(&format!("Unsupported format: {}", format))),
+    }
+
+    if let Some(dir) = output {
+        fs::create_dir_all(dir)?;
+        for scanned in &result.glyphs {
+            // Different glyphs may share a file name; keep them apart by content hash
+            let stem = label_file_stem(&scanned.glyph.payload.label);
+            let same_stem = result.glyphs.iter().filter(|g| label_file_stem(&g.glyph.payload.label) == stem).count();
+            let name = if same_stem > 1 {
+                format!("{}-{}.glyph", stem, &qr_chunk::content_hash(&scanned.cbor)[..12])
+            } else {
+                format!("{}.glyph", stem)
+            };
+            fs::write(Path::new(dir).join(name), &scanned.cbor)?;
+        }
+        if format == "text" {
+            println!("✓ {} glyph(s) written to: {}", result.glyphs.len(), dir);
+        }
+    }
+
+    if result.glyphs.is_empty() {
+        eprintln!("✗ No glyphs found");
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+// Files under `input` with their archive paths: relative to a directory
+// input, or the bare file name for a file input
+fn collect_pack_files(input: &Path, prefix: &str, files: &mut Vec<(std::path::PathBuf, String)>) -> Result<()> {
+    if input.is_dir() {
+        let mut entries: Vec<_> = fs::read_dir(input)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .collect();
+        entries.sort();
+        for entry in entries {
+            let name = entry.file_name().unwrap().to_string_lossy().to_string();
+            let archive_path = if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) };
+            collect_pack_files(&entry, &archive_path, files)?;
+        }
+    } else {
+        let archive_path = if prefix.is_empty() {
+            input.file_name().unwrap().to_string_lossy().to_string()
+        } else {
+            prefix.to_string()
+        };
+        files.push((input.to_path_buf(), archive_path));
+    }
+    Ok(())
+}
+
+// An --exclude pattern names an archive path, a file name, or a file name
+// suffix after a leading '*' ("*.key")
+fn pack_excludes(archive_path: &str, excludes: &[String]) -> bool {
+    let name = archive_path.rsplit('/').next().unwrap_or(archive_path);
+    excludes.iter().any(|pattern| match pattern.strip_prefix('*') {
+        Some(suffix) => name.ends_with(suffix),
+        None => pattern == archive_path || pattern == name,
+    })
+}
+
+// Ed25519 private keys are 32 raw bytes; public keys travel as .pub
+fn looks_like_private_key(name: &str, data: &[u8]) -> bool {
+    name.ends_with(".key") || name.contains("private") || (data.len() == 32 && !name.ends_with(".pub"))
+}
+
+/// Bundle glyphs, companions, media and public keys into a .glyphpack archive
+pub fn pack_create(inputs: &[String], exclude: &[String], output: &str, sign: bool, private_key: Option<&str>) -> Result<()> {
+    let mut files = Vec::new();
+    for input in inputs {
+        collect_pack_files(Path::new(input), "", &mut files)?;
+    }
+
+    let mut builder = glyph_pack::PackBuilder::new();
+    let mut glyph_dirs = std::collections::HashMap::new();
+    for (path, archive_path) in &files {
+        if pack_excludes(archive_path, exclude) {
+            continue;
+        }
+        // Private keys never travel in a pack; leaving one out is the
+        // caller's explicit choice, never a silent one
+        let data = fs::read(path)?;
+        let name = archive_path.rsplit('/').next().unwrap_or(archive_path);
+        if looks_like_private_key(name, &data) {
+            return Err(utils::error::Error::new(&format!(
+                "{} looks like a private key; leave it out with --exclude, or give public keys a .pub name",
+                path.display()
+            )));
+        }
+        builder.add(archive_path, &data)
+            .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+        if archive_path.ends_with(".glyph") {
+            glyph_dirs.insert(archive_path.clone(), path.parent().unwrap_or(Path::new("")).to_path_buf());
+        }
+    }
+
+    // Pull in referenced media that sits next to its glyph
+    for (name, glyph_path) in builder.missing_media() {
+        let Some(dir) = glyph_dirs.get(&glyph_path) else { continue };
+        let source = dir.join(&name);
+        let archive_path = match glyph_path.rsplit_once('/') {
+            Some((parent, _)) => format!("{}/{}", parent, name),
+            None => name.clone(),
+        };
+        if source.is_file() && !builder.contains(&archive_path) {
+            builder.add(&archive_path, &fs::read(&source)?)
+                .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+        }
+    }
+    for (name, glyph_path) in builder.missing_media() {
+        eprintln!("Warning: media {} referenced by {} was not found", name, glyph_path);
+    }
+
+    let private_key_bytes = match (sign, private_key) {
+        (true, Some(key_path)) => Some(fs::read(key_path)?),
+        (true, None) => {
+            eprintln!("Warning: --sign specified but no --private-key provided");
+            None
+        }
+        _ => None,
+    };
+
+    let pack = builder.finish(private_key_bytes.as_deref())
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+    let file = std::io::BufWriter::new(fs::File::create(output)?);
+    pack.write_to(file)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+    println!("✓ Packed {} files into: {}", pack.manifest.entries.len(), output);
+    Ok(())
+}
+
+fn read_pack(input: &str) -> Result<glyph_pack::GlyphPack> {
+    let file = std::io::BufReader::new(fs::File::open(input)?);
+    glyph_pack::GlyphPack::read_from(file)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))
+}
+
+/// Verify that a .glyphpack is complete, unaltered and (optionally) signed by a key
+pub fn pack_verify(input: &str, public_key: Option<&str>) -> Result<()> {
+    let pack = read_pack(input)?;
+
+    match pack.verify() {
+        Ok(()) => println!("✓ Pack verification passed ({} files)", pack.manifest.entries.len()),
+        Err(errors) => {
+            eprintln!("✗ Pack verification failed:");
+            for error in errors {
+                eprintln!("  - {}", error);
+            }
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(key_path) = public_key {
+        let public_key_bytes = fs::read(key_path)?;
+        match pack.verify_signature(&public_key_bytes) {
+            Ok(true) => println!("✓ Manifest signature verification passed"),
+            Ok(false) if pack.manifest.header.signature.is_none() => {
+                eprintln!("✗ Pack manifest is not signed");
+                std::process::exit(1);
+            }
+            Ok(false) => {
+                eprintln!("✗ Manifest signature verification failed");
+                std::process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("✗ Manifest signature verification error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else if pack.manifest.header.signature.is_some() {
+        println!("ℹ Pack is signed; pass --public-key to check the signature");
+    }
+
+    Ok(())
+}
+
+/// Verify a .glyphpack and extract its files into a directory
+pub fn pack_extract(input: &str, output_dir: &str) -> Result<()> {
+    let pack = read_pack(input)?;
+
+    if let Err(errors) = pack.verify() {
+        eprintln!("✗ Refusing to extract a pack that fails verification:");
+        for error in errors {
+            eprintln!("  - {}", error);
+        }
+        std::process::exit(1);
+    }
+
+    let written = pack.extract(Path::new(output_dir))
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+    println!("✓ Extracted {} files into: {}", written.len(), output_dir);
+    Ok(())
+}
+
+/// List the files in a .glyphpack
+pub fn pack_list(input: &str) -> Result<()> {
+    let pack = read_pack(input)?;
+    let header = &pack.manifest.header;
+
+    println!(
+        "PACK {} ({}, {})",
+        header.id,
+        header.created_at,
+        if header.signature.is_some() { "signed" } else { "unsigned" }
+    );
+    for entry in &pack.manifest.entries {
+        println!(
+            "{:<9} {:>10}  {}  {}",
+            entry.kind,
+            entry.size,
+            entry.digest.chars().take(19).collect::<String>(),
+            entry.path
+        );
+    }
+    Ok(())
+}
+
+// Leaves are recomputed semantic hashes; a stored hash that disagrees with
+// its payload would put a claim in the tree that the content does not back
+fn corpus_tree(corpus: &[String]) -> Result<merkle::MerkleTree> {
+    let glyphs = load_glyphs(corpus)?;
+    let leaves = glyphs.iter().map(checked_hash).collect::<Result<Vec<_>>>()?;
+    merkle::MerkleTree::build(leaves)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))
+}
+
+fn checked_hash(glyph: &glyph_decode::Glyph) -> Result<String> {
+    let hash = glyph.semantic_hash();
+    if glyph.header.hash != hash {
+        return Err(utils::error::Error::new(&format!(
+            "Glyph {} ({}) has a stored hash {} that does not match its payload ({})",
+            glyph.payload.label, glyph.header.id, glyph.header.hash, hash
+        )));
+    }
+    Ok(hash)
+}
+
+/// Compute (and optionally sign) the Merkle root of a corpus
+pub fn proof_root(corpus: &[String], output: Option<&str>, sign: bool, private_key: Option<&str>) -> Result<()> {
+    let tree = corpus_tree(corpus)?;
+
+    let private_key_bytes = match (sign, private_key) {
+        (true, Some(key_path)) => Some(fs::read(key_path)?),
+        (true, None) => {
+            eprintln!("Warning: --sign specified but no --private-key provided");
+            None
+        }
+        _ => None,
+    };
+    let signed_root = tree.signed_root(private_key_bytes.as_deref())
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+    let content = serde_json::to_string_pretty(&signed_root)?;
+
+    match output {
+        Some(output) => {
+            fs::write(output, content)?;
+            println!("✓ Merkle root over {} glyphs written to: {}", tree.len(), output);
+        }
+        None => println!("{}", content),
+    }
+    Ok(())
+}
+
+/// Emit an inclusion proof for a glyph in a corpus
+pub fn proof_create(glyph_path: &str, corpus: &[String], output: Option<&str>) -> Result<()> {
+    let glyph = glyph_decode::Glyph::from_cbor(&fs::read(glyph_path)?)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+    let hash = checked_hash(&glyph)?;
+    let tree = corpus_tree(corpus)?;
+
+    let proof = tree.proof(&hash)
+        .ok_or_else(|| utils::error::Error::new(&format!("{} is not part of the corpus", hash)))?;
+    let content = serde_json::to_string_pretty(&proof)?;
+
+    match output {
+        Some(output) => {
+            fs::write(output, content)?;
+            println!("✓ Inclusion proof written to: {}", output);
+        }
+        None => println!("{}", content),
+    }
+    Ok(())
+}
+
+/// Check an inclusion proof against a root (a signed root file or a bare hash)
+pub fn proof_verify(proof_path: &str, root: &str, public_key: Option<&str>, glyph_path: Option<&str>) -> Result<()> {
+    let proof: merkle::InclusionProof = serde_json::from_str(&fs::read_to_string(proof_path)?)?;
+
+    let root_hash = if root.starts_with("blake3:") {
+        if public_key.is_some() {
+            eprintln!("Warning: --public-key needs a signed root file; the bare root hash is not signed");
+        }
+        root.to_string()
+    } else {
+        let signed_root: merkle::SignedRoot = serde_json::from_str(&fs::read_to_string(root)?)?;
+        if let Some(key_path) = public_key {
+            let public_key_bytes = fs::read(key_path)?;
+            match signed_root.verify_signature(&public_key_bytes) {
+                Ok(true) => println!("✓ Root signature verification passed"),
+                Ok(false) => {
+                    eprintln!("✗ Root signature verification failed");
+                    std::process::exit(1);
+                }
+                Err(e) => {
+                    eprintln!("✗ Root signature verification error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        signed_root.root
+    };
+
+    if let Some(glyph_path) = glyph_path {
+        let glyph = glyph_decode::Glyph::from_cbor(&fs::read(glyph_path)?)
+            .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+        // The leaf must be the hash of the payload, not just what the header claims
+        let hash = glyph.semantic_hash();
+        if glyph.header.hash != hash {
+            eprintln!("✗ Glyph hash {} does not match its payload ({})", glyph.header.hash, hash);
+            std::process::exit(1);
+        }
+        if hash != proof.leaf {
+            eprintln!("✗ Proof is for {}, not for {}", proof.leaf, hash);
+            std::process::exit(1);
+        }
+    }
+
+    if proof.verify(&root_hash) {
+        println!("✓ Inclusion proof verified: {} is in {}", proof.leaf, root_hash);
+    } else {
+        eprintln!("✗ Inclusion proof does not match root {}", root_hash);
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Compare two glyphs field by field
+pub fn diff(a: &str, b: &str, format: &str, public_key: Option<&str>) -> Result<()> {
+    let load = |path: &str| glyph_decode::Glyph::from_cbor(&fs::read(path)?)
+        .map_err(|e| utils::error::Error::new(&format!("{}: {}", path, e)));
+    let (glyph_a, glyph_b) = (load(a)?, load(b)?);
+    let public_key_bytes = match public_key {
+        Some(key_path) => Some(fs::read(key_path)?),
+        None => None,
+    };
+
+    let result = glyph_diff::diff(&glyph_a, &glyph_b, public_key_bytes.as_deref());
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(&result)?),
+        "text" => {
+            println!("--- {}\n+++ {}", a, b);
+            print!("{}", result.to_text());
+        }
+        _ => return Err(utils::error::Error::new(&format!("Unsupported format: {}", format))),
+    }
+    Ok(())
+}
+
+/// Three-way merge of two edited glyphs against their common ancestor
+pub fn merge(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    output: &str,
+    favor: Option<glyph_merge::Favor>,
+    sign: bool,
+    private_key: Option<&str>,
+) -> Result<()> {
+    let load = |path: &str| glyph_decode::Glyph::from_cbor(&fs::read(path)?)
+        .map_err(|e| utils::error::Error::new(&format!("{}: {}", path, e)));
+    let (base_glyph, ours_glyph, theirs_glyph) = (load(base)?, load(ours)?, load(theirs)?);
+
+    let mut result = glyph_merge::merge(&base_glyph, &ours_glyph, &theirs_glyph, favor)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+    if result.has_conflicts() {
+        eprint!("{}", result.conflict_markers());
+        if !result.resolved {
+            eprintln!("✗ {} conflicting field(s); resolve them with --favor ours|theirs", result.conflicts.len());
+            std::process::exit(1);
+        }
+        println!("ℹ Resolved {} conflicting field(s) with --favor", result.conflicts.len());
+    }
+
+    if result.signature_cleared {
+        println!("ℹ Semantic hash changed; the previous signature was cleared");
+    }
+    if sign {
+        match private_key {
+            Some(key_path) => {
+                let private_key_bytes = fs::read(key_path)?;
+                let signature = glyph_sign::sign_hash(&private_key_bytes, &result.glyph.header.hash)
+                    .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+                result.glyph.header.signature = Some(signature);
+                println!("✓ Merged glyph signed");
+            }
+            None => eprintln!("Warning: --sign specified but no --private-key provided"),
+        }
+    }
+
+    let cbor_data = result.glyph.to_cbor()
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+    fs::write(output, cbor_data)?;
+    println!("✓ Merged glyph written to: {} ({})", output, result.glyph.header.hash);
+    Ok(())
+}
+
+/// Walk the revision chain of a concept through a store
+pub fn history(id: &str, store: &[String], format: &str) -> Result<()> {
+    let glyphs = load_glyphs(store)?;
+    let history = lineage::history(&glyphs, id)
+        .ok_or_else(|| utils::error::Error::new(&format!("No glyph with concept or header id {} in the store", id)))?;
+
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(&history)?),
+        "text" => print!("{}", history.to_text()),
+        _ => return Err(utils::error::Error::new(&format!("Unsupported format: {}", format))),
+    }
+
+    if history.is_valid() {
+        // Keep JSON output parseable
+        if format == "text" {
+            println!("✓ Revision chain verified");
+        }
+    } else {
+        eprintln!("✗ Revision chain is broken:");
+        for problem in &history.problems {
+            eprintln!("  - {}", problem);
+        }
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Resolve glyph references against a store; without references, check every
+/// relation target in the store
+pub fn resolve(references: &[String], store: &[String]) -> Result<()> {
+    let glyphs = load_glyphs(store)?;
+    let resolver = glyph_ref::Resolver::new(&glyphs);
+
+    // (reference, where it was found)
+    let checks: Vec<(String, Option<String>)> = if references.is_empty() {
+        glyphs.iter()
+            .flat_map(|glyph| glyph.payload.relations.iter()
+                .filter(|relation| relation.target.starts_with("glyph:"))
+                .map(move |relation| (relation.target.clone(), Some(format!("{} {}", glyph.payload.label, relation.r#type)))))
+            .collect()
+    } else {
+        references.iter().map(|reference| (reference.clone(), None)).collect()
+    };
+
+    let mut failures = 0;
+    for (reference, source) in &checks {
+        let context = source.as_ref().map(|source| format!(" ({})", source)).unwrap_or_default();
+        match resolver.resolve_str(reference) {
+            Ok(glyph) => println!("✓ {}{} → {} {} {}", reference, context, glyph.payload.label, glyph.header.id, glyph.header.hash),
+            Err(e) => {
+                eprintln!("✗ {}{}: {}", reference, context, e);
+                failures += 1;
+            }
+        }
+    }
+
+    if failures > 0 {
+        eprintln!("✗ {} of {} reference(s) could not be resolved", failures, checks.len());
+        std::process::exit(1);
+    }
+    println!("✓ {} reference(s) resolved", checks.len());
+    Ok(())
+}
+
+/// Run a glyphscript over a store, optionally writing updated glyphs back
+pub fn query(script: &str, store: &[String], format: &str, write: bool) -> Result<()> {
+    let script = glyphscript::parse(script)
+        .map_err(|e| utils::error::Error::new(&format!("Glyphscript: {}", e)))?;
+    let mut glyphs = load_glyphs(store)?;
+
+    let results = glyphscript::run(&script, &mut glyphs)
+        .map_err(|e| utils::error::Error::new(&format!("Glyphscript: {}", e)))?;
+    let mut updated: Vec<usize> = results.iter().flat_map(|result| result.updated.iter().copied()).collect();
+    updated.sort();
+    updated.dedup();
+
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(&results)?),
+        "text" => {
+            for result in &results {
+                if !result.columns.is_empty() {
+                    print!("{}", result.to_text());
+                    println!("({} row(s))", result.rows.len());
+                }
+            }
+        }
+        _ => return Err(utils::error::Error::new(&format!("Unsupported format: {}", format))),
+    }
+
+    if updated.is_empty() {
+        return Ok(());
+    }
+    // Status lines would break the JSON document on stdout
+    if !write {
+        if format == "text" {
+            println!("ℹ {} glyph(s) updated in memory; pass --write to save them", updated.len());
+        }
+        return Ok(());
+    }
+
+    // load_glyphs reads plain files first, in this order, then streams
+    let files: Vec<String> = store.iter().filter(|input| !input.ends_with(".glyphs")).cloned().collect();
+    let paths = collect_glyph_files(&files)?;
+
+    // Check every update before writing any, so a bad one leaves the store untouched
+    let mut writes = Vec::new();
+    for &index in &updated {
+        let glyph = &glyphs[index];
+        let path = paths.get(index).ok_or_else(|| utils::error::Error::new(&format!(
+            "{} comes from a .glyphs stream and cannot be written back", glyph.payload.label
+        )))?;
+        if let Err(errors) = glyph.validate() {
+            return Err(utils::error::Error::new(&format!(
+                "Updated {} is not valid, nothing written: {}", glyph.payload.label, errors.join("; ")
+            )));
+        }
+        writes.push((glyph, path));
+    }
+
+    for (glyph, path) in writes {
+        let cbor_data = glyph.to_cbor()
+            .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+        fs::write(path, cbor_data)?;
+
+        let json_companion = format!("{}.json", path);
+        if Path::new(&json_companion).exists() {
+            fs::write(&json_companion, serde_json::to_string_pretty(glyph)?)?;
+        }
+        if format == "text" {
+            println!("✓ Updated glyph written to: {}", path);
+        }
+    }
+    Ok(())
+}
+
+/// Derive implied relations from a rules file, or answer a question against the closure
+pub fn infer(rules_path: &str, store: &[String], ask: Option<&str>, explain: bool, format: &str) -> Result<()> {
+    let rules = inference::RuleSet::from_json5(&fs::read_to_string(rules_path)?)
+        .map_err(|e| utils::error::Error::new(&format!("{}: {}", rules_path, e)))?;
+    let glyphs = load_glyphs(store)?;
+    let closure = inference::Closure::compute(&glyphs, &rules)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+    let facts: Vec<usize> = match ask {
+        Some(question) => closure.ask(question)
+            .map_err(|e| utils::error::Error::new(&e.to_string()))?,
+        None => closure.inferred().map(|(i, _)| i).collect(),
+    };
+
+    match format {
+        "json" => {
+            let facts: Vec<serde_json::Value> = facts.iter()
+                .map(|&i| {
+                    let fact = &closure.facts[i];
+                    serde_json::json!({
+                        "subject": closure.name(&fact.subject),
+                        "relation": fact.relation,
+                        "object": closure.name(&fact.object),
+                        "derivation": fact.derivation.to_string(),
+                        "explanation": closure.explain(i).lines().collect::<Vec<_>>(),
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&facts)?);
+        }
+        "text" => {
+            for &i in &facts {
+                if explain {
+                    print!("{}", closure.explain(i));
+                } else {
+                    println!("{}  [{}]", closure.describe(i), closure.facts[i].derivation);
+                }
+            }
+            let inferred = closure.inferred().count();
+            println!(
+                "ℹ {} relation(s) in the closure, {} inferred from {} rule(s)",
+                closure.facts.len(),
+                inferred,
+                rules.rules.len() + rules.relations.len()
+            );
+        }
+        _ => return Err(utils::error::Error::new(&format!("Unsupported format: {}", format))),
+    }
+    Ok(())
+}
+
+pub fn dissonance(store: &[String], rules_path: Option<&str>, keys: &[String], format: &str) -> Result<()> {
+    let rules = match rules_path {
+        Some(path) => inference::RuleSet::from_json5(&fs::read_to_string(path)?)
+            .map_err(|e| utils::error::Error::new(&format!("{}: {}", path, e)))?,
+        None => inference::RuleSet::from_json5(dissonance::DEFAULT_RULES)
+            .map_err(|e| utils::error::Error::new(&e.to_string()))?,
+    };
+    let keyring = trust::Keyring::load(keys).map_err(|e| utils::error::Error::new(&e.to_string()))?;
+    let glyphs = load_glyphs(store)?;
+    let report = dissonance::analyze(&glyphs, &rules, &keyring)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(&report)?),
+        "text" => {
+            if report.is_empty() {
+                println!("✓ No dissonance among {} glyph(s)", report.glyphs);
+            } else {
+                print!("{}", report.to_text());
+            }
+        }
+        _ => return Err(utils::error::Error::new(&format!("Unsupported format: {}", format))),
+    }
+    Ok(())
+}
+
+/// Trust, key and output options of `consensus`
+#[derive(Debug, Default)]
+pub struct ConsensusOptions<'a> {
+    /// JSON5 trust policy; every version weighs the same without one
+    pub trust: Option<&'a str>,
+    /// Public keys to name signers
+    pub keys: &'a [String],
+    /// Write the consensus glyph to this file
+    pub output: Option<&'a str>,
+    pub sign: bool,
+    pub private_key: Option<&'a str>,
+}
+
+/// Weigh competing versions of a concept into one consensus glyph
+pub fn consensus(concept: &str, store: &[String], format: &str, options: &ConsensusOptions) -> Result<()> {
+    let ConsensusOptions { trust: trust_path, keys, output, sign, private_key } = *options;
+    let policy = match trust_path {
+        Some(path) => trust::TrustPolicy::from_json5(&fs::read_to_string(path)?)
+            .map_err(|e| utils::error::Error::new(&format!("{}: {}", path, e)))?,
+        None => trust::TrustPolicy::default(),
+    };
+    let keyring = trust::Keyring::load(keys).map_err(|e| utils::error::Error::new(&e.to_string()))?;
+    let glyphs = load_glyphs(store)?;
+    let mut result = consensus::consensus(&glyphs, concept, &policy, &keyring)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+    if sign {
+        match private_key {
+            Some(key_path) => {
+                let private_key_bytes = fs::read(key_path)?;
+                let signature = glyph_sign::sign_hash(&private_key_bytes, &result.glyph.header.hash)
+                    .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+                result.glyph.header.signature = Some(signature);
+            }
+            None => eprintln!("Warning: --sign specified but no --private-key provided"),
+        }
+    }
+
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(&result)?),
+        "text" => print!("{}", result.to_text()),
+        _ => return Err(utils::error::Error::new(&format!("Unsupported format: {}", format))),
+    }
+
+    if let Some(output) = output {
+        let cbor_data = result.glyph.to_cbor()
+            .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+        fs::write(output, cbor_data)?;
+        // The JSON report owns stdout
+        if format == "text" {
+            println!("✓ Consensus glyph written to: {} ({})", output, result.glyph.header.hash);
+        }
+    }
+    Ok(())
+}
+
+/// Source and bytes of an incoming glyph, with the decoded glyph or why it could not be read
+type Incoming = (String, Vec<u8>, std::result::Result<glyph_decode::Glyph, String>);
+
+fn read_incoming(inputs: &[String]) -> Result<Vec<Incoming>> {
+    let mut incoming = Vec::new();
+
+    let (streams, files): (Vec<String>, Vec<String>) = inputs.iter()
+        .cloned()
+        .partition(|input| input.ends_with(".glyphs"));
+
+    for path in collect_glyph_files(&files)? {
+        let cbor_data = fs::read(&path)?;
+        let glyph = glyph_decode::Glyph::from_cbor(&cbor_data)
+            .map_err(|e| format!("not a glyph: {}", e));
+        incoming.push((path, cbor_data, glyph));
+    }
+
+    for path in streams {
+        let file = std::io::BufReader::new(fs::File::open(&path)?);
+        let reader = glyph_stream::GlyphStreamReader::new(file)
+            .map_err(|e| utils::error::Error::new(&format!("{}: {}", path, e)))?;
+        for (i, record) in reader.enumerate() {
+            let source = format!("{}#{}", path, i);
+            match record.and_then(|glyph| Ok((glyph.to_cbor()?, glyph))) {
+                Ok((cbor_data, glyph)) => incoming.push((source, cbor_data, Ok(glyph))),
+                Err(e) => incoming.push((source, Vec::new(), Err(format!("not a glyph: {}", e)))),
+            }
+        }
+    }
+
+    Ok(incoming)
+}
+
+/// Pass incoming glyphs through the firewall into a store directory;
+/// rejected glyphs go to the store's quarantine
+pub fn ingest(inputs: &[String], store: &str, policy_path: &str, keys: &[String], dry_run: bool) -> Result<()> {
+    let policy = firewall::Policy::from_json5(&fs::read_to_string(policy_path)?)
+        .map_err(|e| utils::error::Error::new(&format!("{}: {}", policy_path, e)))?;
+    let keyring = trust::Keyring::load(keys).map_err(|e| utils::error::Error::new(&e.to_string()))?;
+    let incoming = read_incoming(inputs)?;
+    if !dry_run {
+        fs::create_dir_all(store)?;
+    }
+    let quarantine = quarantine::Quarantine::of_store(store);
+
+    let (mut accepted, mut rejected) = (0, 0);
+    let mut log = Vec::new();
+    for (source, cbor_data, glyph) in incoming {
+        let glyph = match glyph {
+            Ok(glyph) => glyph,
+            Err(reason) => {
+                eprintln!("✗ {}: {}", source, reason);
+                if !dry_run {
+                    let entry = quarantine.add(&source, &cbor_data, None, None, vec![reason.clone()])
+                        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+                    log.push(serde_json::json!({ "source": source, "quarantine": entry.entry, "reasons": [reason] }));
+                }
+                rejected += 1;
+                continue;
+            }
+        };
+
+        let verdict = policy.evaluate(&glyph, cbor_data.len(), &keyring);
+        // The id names the file in the store, so a glyph without a UUID id is
+        // refused whatever the policy says
+        let target = glyph.file_name().map(|name| Path::new(store).join(name));
+        let mut reasons = Vec::new();
+        match &target {
+            Err(e) => reasons.push(e.to_string()),
+            Ok(target) if verdict.allowed() && target.exists() && fs::read(target)? != cbor_data => {
+                reasons.push(format!("a different glyph with id {} is already in the store", glyph.header.id));
+            }
+            Ok(_) => {}
+        }
+        if !verdict.allowed() {
+            reasons.extend(verdict.reasons.iter().cloned());
+        }
+
+        match target {
+            Ok(target) if reasons.is_empty() => {
+                if !dry_run {
+                    fs::write(&target, &cbor_data)?;
+                }
+                println!("✓ {} ({}) -> {}", source, glyph.payload.label, target.display());
+                accepted += 1;
+            }
+            _ => {
+                let rule = verdict.rule.filter(|_| !verdict.allowed()).map(|i| i + 1);
+                let summary = if reasons == verdict.reasons { verdict.describe() } else { reasons.join("; ") };
+                eprintln!("✗ {} ({}): {}", source, glyph.payload.label, summary);
+                if !dry_run {
+                    let entry = quarantine.add(&source, &cbor_data, Some(&glyph), rule, reasons.clone())
+                        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+                    log.push(serde_json::json!({
+                        "source": source,
+                        "quarantine": entry.entry,
+                        "id": glyph.header.id,
+                        "label": glyph.payload.label,
+                        "hash": glyph.header.hash,
+                        "rule": rule,
+                        "reasons": reasons,
+                    }));
+                }
+                rejected += 1;
+            }
+        }
+    }
+
+    // Every rejection is kept with its reasons next to the store
+    if !log.is_empty() {
+        use std::io::Write;
+        let log_path = Path::new(store).join("firewall.jsonl");
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(&log_path)?;
+        let now = chrono::Utc::now().to_rfc3339();
+        for mut entry in log {
+            entry["time"] = serde_json::json!(now);
+            writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        }
+        println!("ℹ Rejected glyphs quarantined in {}; rejections recorded in {}", quarantine.dir().display(), log_path.display());
+    }
+
+    if dry_run {
+        println!("ℹ {} glyph(s) would be ingested, {} rejected (dry run)", accepted, rejected);
+        if rejected > 0 {
+            std::process::exit(1);
+        }
+    } else {
+        println!("ℹ {} glyph(s) ingested, {} quarantined", accepted, rejected);
+    }
+    Ok(())
+}
+
+/// List the quarantine of a store
+pub fn quarantine_list(store: &str, format: &str) -> Result<()> {
+    let entries = quarantine::Quarantine::of_store(store).list()
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(&entries)?),
+        "text" => {
+            for entry in &entries {
+                println!(
+                    "{}  {}  {:<20}  {}",
+                    entry.entry.get(..8).unwrap_or(&entry.entry),
+                    entry.arrived_at.format("%Y-%m-%d %H:%M:%S"),
+                    entry.label.as_deref().unwrap_or("(undecodable)"),
+                    entry.diagnostics.first().map(String::as_str).unwrap_or("")
+                );
+            }
+            println!("ℹ {} quarantined glyph(s)", entries.len());
+        }
+        _ => return Err(utils::error::Error::new(&format!("Unsupported format: {}", format))),
+    }
+    Ok(())
+}
+
+/// Show one quarantine entry with its diagnostics and the glyph it holds
+pub fn quarantine_show(entry: &str, store: &str, format: &str) -> Result<()> {
+    let quarantine = quarantine::Quarantine::of_store(store);
+    let entry = quarantine.find(entry).map_err(|e| utils::error::Error::new(&e.to_string()))?;
+    let glyph = quarantine.glyph(&entry).ok();
+
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(&serde_json::json!({ "entry": entry, "glyph": glyph }))?),
+        "text" => {
+            print!("{}", entry.to_text());
+            if let Some(glyph) = glyph {
+                println!();
+                print!("{}", glyph.to_text().map_err(|e| utils::error::Error::new(&e.to_string()))?);
+            }
+        }
+        _ => return Err(utils::error::Error::new(&format!("Unsupported format: {}", format))),
+    }
+    Ok(())
+}
+
+/// Admit quarantined glyphs into the store after review
+pub fn quarantine_release(entries: &[String], store: &str) -> Result<()> {
+    let quarantine = quarantine::Quarantine::of_store(store);
+    for prefix in entries {
+        let entry = quarantine.find(prefix).map_err(|e| utils::error::Error::new(&e.to_string()))?;
+        let path = quarantine.release(&entry, store)
+            .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+        println!("✓ Released {} to {}", entry.entry, path.display());
+    }
+    Ok(())
+}
+
+/// Delete quarantine entries: the named ones, those older than a number of days, or all
+pub fn quarantine_purge(entries: &[String], store: &str, older_than: Option<i64>, all: bool) -> Result<()> {
+    let quarantine = quarantine::Quarantine::of_store(store);
+    let purged = if !entries.is_empty() {
+        let mut purged = Vec::new();
+        for prefix in entries {
+            let entry = quarantine.find(prefix).map_err(|e| utils::error::Error::new(&e.to_string()))?;
+            quarantine.remove(&entry).map_err(|e| utils::error::Error::new(&e.to_string()))?;
+            purged.push(entry);
+        }
+        purged
+    } else if older_than.is_some() || all {
+        quarantine.purge(older_than.map(chrono::Duration::days))
+            .map_err(|e| utils::error::Error::new(&e.to_string()))?
+    } else {
+        return Err(utils::error::Error::new("Name entries to purge, or use --older-than or --all"));
+    };
+    println!("✓ Purged {} quarantined glyph(s)", purged.len());
+    Ok(())
+}
+
+/// Judge the quarantine again after a policy or trust change, optionally releasing what now passes
+pub fn quarantine_recheck(store: &str, policy_path: &str, keys: &[String], release: bool) -> Result<()> {
+    let policy = firewall::Policy::from_json5(&fs::read_to_string(policy_path)?)
+        .map_err(|e| utils::error::Error::new(&format!("{}: {}", policy_path, e)))?;
+    let keyring = trust::Keyring::load(keys).map_err(|e| utils::error::Error::new(&e.to_string()))?;
+    let quarantine = quarantine::Quarantine::of_store(store);
+    let results = quarantine.recheck(&policy, &keyring)
+        .map_err(|e| utils::error::Error::new(&e.to_string()))?;
+
+    let mut passing = 0;
+    for (entry, verdict) in &results {
+        let name = format!("{} ({})", entry.entry.get(..8).unwrap_or(&entry.entry), entry.label.as_deref().unwrap_or("undecodable"));
+        match verdict {
+            Some(verdict) if verdict.allowed() => {
+                passing += 1;
+                if release {
+                    match quarantine.release(entry, store) {
+                        Ok(path) => println!("✓ {} now passes; released to {}", name, path.display()),
+                        Err(e) => eprintln!("✗ {} now passes but was not released: {}", name, e),
+                    }
+                } else {
+                    println!("✓ {} now passes: {}", name, verdict.describe());
+                }
+            }
+            Some(verdict) => println!("✗ {} still {}", name, verdict.describe()),
+            None => println!("✗ {} still does not decode", name),
+        }
+    }
+    println!("ℹ {} of {} quarantined glyph(s) pass the current policy", passing, results.len());
+    Ok(())
+}
+
+/// Search a store by label, aliases, overlay labels, phonetics and classes
+pub fn search(
+    text: &str,
+    store: &[String],
+    namespace: Option<&str>,
+    truth_mode: Option<&str>,
+    min_confidence: Option<f64>,
+    limit: usize,
+    format: &str,
+) -> Result<()> {
+    let glyphs = load_glyphs(store)?;
+    let index = search::SearchIndex::build(&glyphs);
+    let filters = search::Filters {
+        namespace: namespace.map(str::to_string),
+        truth_mode: truth_mode.map(str::to_string),
+        min_confidence,
+    };
+    let mut hits = index.search(text, &filters);
+    let total = hits.len();
+    hits.truncate(limit);
+
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(&hits)?),
+        "text" => {
+            for hit in &hits {
+                let glyph = &glyphs[hit.glyph];
+                println!(
+                    "{:5.2}  {:<24} {} ({}, {:.2})  {}",
+                    hit.score,
+                    hit.label,
+                    glyph.payload.namespace.provision,
+                    glyph.payload.truth_mode.r#type,
+                    glyph.payload.truth_mode.confidence,
+                    hit.id
+                );
+                println!("       {}", hit.matched.join("; "));
+            }
+            println!("ℹ {} match(es){}", total, if total > hits.len() { format!(", showing {}", hits.len()) } else { String::new() });
+        }
+        _ => return Err(utils::error::Error::new(&format!("Unsupported format: {}", format))),
+    }
+    Ok(())
+}
@@ -0,0 +1,444 @@
+// Consensus over competing versions of one concept.
+//
+// Agents publish their own glyph for a concept: the same label or wikidata id
+// with different relations, classes and confidences. A consensus collects the
+// latest revision of every such version and weighs each by the trust policy:
+//
+//   scalars   label, provision, truth type, wikidata, audio, visual take the
+//             value with the most weight behind it
+//   mean      confidence is the weighted mean
+//   union     aliases, verified_by, conflicts, sensors and existing overlays
+//             are kept from every version
+//   majority  classes and relations are kept when more than half of the
+//             weight stands behind them
+//
+// Nothing is discarded. A version whose label or truth type lost becomes a
+// contextual overlay for its agent, and relations without a majority stay on
+// the glyph scoped to the agents that asserted them (`agent:x/type`), so
+// `decode --perspective` still shows each minority position. Every value,
+// adopted or not, is attributed to its agents with its share of the weight.
+
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use super::dissonance::concept_key;
+use super::glyph_decode::{Glyph, GlyphHeader, GlyphPayload};
+use super::lineage::{self, Lineage};
+use super::perspective;
+use super::trust::{Keyring, Signer, TrustPolicy};
+
+const VOTE_FIELDS: &[&str] = &[
+    "/label",
+    "/namespace/provision",
+    "/truth_mode/type",
+    "/grounding/wikidata",
+    "/audio",
+    "/visual",
+];
+
+const UNION_FIELDS: &[&str] = &[
+    "/aliases",
+    "/truth_mode/verified_by",
+    "/truth_mode/conflicts",
+    "/grounding/sensors",
+    "/namespace/contextual_overlays",
+];
+
+const MAJORITY_FIELDS: &[&str] = &["/classes", "/relations"];
+
+/// Creator recorded on consensus glyphs
+pub const CONSENSUS_CREATOR: &str = "glyphos:consensus";
+
+// ============================================================================
+// DATA STRUCTURES
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Contributor {
+    pub id: String,
+    pub hash: String,
+    pub label: String,
+    /// Known signer, else creator, else the glyph itself
+    pub agent: String,
+    pub signer: Signer,
+    pub weight: f64,
+}
+
+/// One value of a field and who stands behind it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Attribution {
+    pub field: String,
+    pub value: Value,
+    /// Share of the total weight
+    pub support: f64,
+    pub agents: Vec<String>,
+    pub adopted: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Consensus {
+    pub concept: String,
+    pub glyph: Glyph,
+    pub contributors: Vec<Contributor>,
+    pub attribution: Vec<Attribution>,
+}
+
+// ============================================================================
+// CONSENSUS
+// ============================================================================
+
+/// Latest revisions of every version of a concept, oldest first. A version
+/// matches on its label (case-insensitive) or wikidata id, and versions that
+/// share a wikidata id with a match are included too. Earlier consensus
+/// glyphs do not vote.
+pub fn versions(glyphs: &[Glyph], concept: &str) -> Vec<usize> {
+    let mut current = lineage::latest_revisions(glyphs);
+    current.retain(|&i| glyphs[i].payload.origin.as_ref().is_none_or(|origin| origin.creator != CONSENSUS_CREATOR));
+    let wanted = concept.to_lowercase();
+    let direct: Vec<usize> = current.iter().copied()
+        .filter(|&i| {
+            let payload = &glyphs[i].payload;
+            payload.label.to_lowercase() == wanted || payload.grounding.wikidata.as_deref() == Some(concept)
+        })
+        .collect();
+    let keys: Vec<String> = direct.iter().map(|&i| concept_key(&glyphs[i])).collect();
+
+    let mut versions: Vec<usize> = current.into_iter()
+        .filter(|i| direct.contains(i) || keys.contains(&concept_key(&glyphs[*i])))
+        .collect();
+    versions.sort_by_key(|&i| glyphs[i].header.created_at);
+    versions
+}
+
+pub fn consensus(
+    glyphs: &[Glyph],
+    concept: &str,
+    policy: &TrustPolicy,
+    keyring: &Keyring,
+) -> Result<Consensus, Box<dyn std::error::Error>> {
+    let versions = versions(glyphs, concept);
+    if versions.is_empty() {
+        return Err(format!("No version of {} in the store", concept).into());
+    }
+
+    let contributors: Vec<Contributor> = versions.iter()
+        .map(|&i| {
+            let glyph = &glyphs[i];
+            let signer = keyring.signer(glyph);
+            let creator = glyph.payload.origin.as_ref().map(|origin| origin.creator.clone());
+            let agent = signer.agent(creator.as_deref())
+                .unwrap_or_else(|| format!("glyph:{}", glyph.header.id));
+            Contributor {
+                id: glyph.header.id.clone(),
+                hash: glyph.header.hash.clone(),
+                label: glyph.payload.label.clone(),
+                weight: policy.weight(&signer, creator.as_deref()),
+                agent,
+                signer,
+            }
+        })
+        .collect();
+    let total: f64 = contributors.iter().map(|contributor| contributor.weight).sum();
+    if total <= 0.0 {
+        return Err(format!("No version of {} carries any trust under the policy", concept).into());
+    }
+
+    let payloads = versions.iter()
+        .map(|&i| serde_json::to_value(&glyphs[i].payload))
+        .collect::<Result<Vec<Value>, _>>()?;
+    let mut merged = payloads[0].clone();
+    let mut attribution = Vec::new();
+
+    for &pointer in VOTE_FIELDS {
+        let votes = tally(&payloads, &contributors, |payload| vec![payload.pointer(pointer).cloned().unwrap_or(Value::Null)]);
+        let winner = votes.iter()
+            .enumerate()
+            .fold(0, |best, (i, (_, weight, _))| if *weight > votes[best].1 { i } else { best });
+        set(&mut merged, pointer, votes[winner].0.clone());
+        attribute(&mut attribution, pointer, votes, total, |i, _| i == winner);
+    }
+
+    let confidence = payloads.iter().zip(&contributors)
+        .map(|(payload, contributor)| payload["truth_mode"]["confidence"].as_f64().unwrap_or(0.0) * contributor.weight)
+        .sum::<f64>() / total;
+    set(&mut merged, "/truth_mode/confidence", json!(confidence));
+    let votes = tally(&payloads, &contributors, |payload| vec![payload["truth_mode"]["confidence"].clone()]);
+    attribute(&mut attribution, "/truth_mode/confidence", votes, total, |_, _| false);
+    let mut agents: Vec<String> = Vec::new();
+    for contributor in contributors.iter().filter(|contributor| contributor.weight > 0.0) {
+        if !agents.contains(&contributor.agent) {
+            agents.push(contributor.agent.clone());
+        }
+    }
+    attribution.push(Attribution {
+        field: "truth_mode.confidence".to_string(),
+        value: json!(confidence),
+        support: 1.0,
+        agents,
+        adopted: true,
+    });
+
+    // Values only weightless versions hold are left out
+    for &pointer in UNION_FIELDS {
+        let votes = tally(&payloads, &contributors, |payload| items(payload, pointer));
+        let kept = votes.iter()
+            .filter(|(_, weight, _)| *weight > 0.0)
+            .map(|(value, _, _)| value.clone())
+            .collect();
+        set(&mut merged, pointer, Value::Array(kept));
+        attribute(&mut attribution, pointer, votes, total, |_, (_, weight)| weight > 0.0);
+    }
+
+    for &pointer in MAJORITY_FIELDS {
+        let votes = tally(&payloads, &contributors, |payload| items(payload, pointer));
+        let adopted = |value: &Value, weight: f64| {
+            weight > total / 2.0
+                || (weight > 0.0 && value["type"].as_str().and_then(perspective::scope).is_some())
+        };
+        let mut kept: Vec<Value> = votes.iter()
+            .filter(|(value, weight, _)| adopted(value, *weight))
+            .map(|(value, _, _)| value.clone())
+            .collect();
+        // Minority relations stay, scoped to the agents that asserted them
+        if pointer == "/relations" {
+            for (value, weight, agents) in &votes {
+                if adopted(value, *weight) {
+                    continue;
+                }
+                for agent in agents {
+                    let trusted = contributors.iter().any(|c| c.agent == *agent && c.weight > 0.0);
+                    let scoped = json!({
+                        "type": format!("{}/{}", agent, value["type"].as_str().unwrap_or_default()),
+                        "target": value["target"],
+                    });
+                    if trusted && !kept.contains(&scoped) {
+                        kept.push(scoped);
+                    }
+                }
+            }
+        }
+        set(&mut merged, pointer, Value::Array(kept));
+        attribute(&mut attribution, pointer, votes, total, |_, (value, weight)| adopted(value, weight));
+    }
+
+    // Losing labels and truth modes become overlays of their agents
+    let label = merged["label"].clone();
+    let mode = merged["truth_mode"]["type"].clone();
+    for (payload, contributor) in payloads.iter().zip(&contributors) {
+        if contributor.weight <= 0.0 || (payload["label"] == label && payload["truth_mode"]["type"] == mode) {
+            continue;
+        }
+        let overlays = merged["namespace"]["contextual_overlays"].as_array_mut().unwrap();
+        overlays.retain(|overlay| overlay["agent"] != contributor.agent.as_str());
+        overlays.push(json!({
+            "agent": contributor.agent,
+            "label": payload["label"],
+            "truth_mode": payload["truth_mode"]["type"],
+        }));
+    }
+    if let Value::Array(aliases) = &mut merged["aliases"] {
+        aliases.retain(|alias| *alias != label);
+    }
+    set(&mut merged, "/origin", json!({
+        "creator": CONSENSUS_CREATOR,
+        "timestamp": Utc::now(),
+        "signature": null,
+    }));
+
+    let payload: GlyphPayload = serde_json::from_value(merged)?;
+    let first = &glyphs[versions[0]].header;
+    let id = uuid::Uuid::new_v4().to_string();
+    let mut glyph = Glyph {
+        header: GlyphHeader {
+            magic: first.magic.clone(),
+            version: first.version.clone(),
+            id: id.clone(),
+            hash: String::new(),
+            signature: None,
+            created_at: Utc::now(),
+            lineage: Some(Lineage::root(&id)),
+        },
+        payload,
+    };
+    glyph.header.hash = glyph.semantic_hash();
+
+    Ok(Consensus {
+        concept: concept.to_string(),
+        glyph,
+        contributors,
+        attribution,
+    })
+}
+
+type Tally = Vec<(Value, f64, Vec<String>)>;
+
+// Distinct values in order of first appearance, with their weight and agents
+fn tally(payloads: &[Value], contributors: &[Contributor], values: impl Fn(&Value) -> Vec<Value>) -> Tally {
+    let mut tally: Tally = Vec::new();
+    for (payload, contributor) in payloads.iter().zip(contributors) {
+        let mut seen = Vec::new();
+        for value in values(payload) {
+            if seen.contains(&value) {
+                continue;
+            }
+            seen.push(value.clone());
+            match tally.iter_mut().find(|(other, _, _)| *other == value) {
+                Some((_, weight, agents)) => {
+                    *weight += contributor.weight;
+                    if !agents.contains(&contributor.agent) {
+                        agents.push(contributor.agent.clone());
+                    }
+                }
+                None => tally.push((value, contributor.weight, vec![contributor.agent.clone()])),
+            }
+        }
+    }
+    tally
+}
+
+fn attribute(
+    attribution: &mut Vec<Attribution>,
+    pointer: &str,
+    tally: Tally,
+    total: f64,
+    adopted: impl Fn(usize, (&Value, f64)) -> bool,
+) {
+    for (i, (value, weight, agents)) in tally.into_iter().enumerate() {
+        attribution.push(Attribution {
+            field: pointer[1..].replace('/', "."),
+            adopted: adopted(i, (&value, weight)),
+            support: weight / total,
+            value,
+            agents,
+        });
+    }
+}
+
+fn items(payload: &Value, pointer: &str) -> Vec<Value> {
+    payload.pointer(pointer).and_then(Value::as_array).cloned().unwrap_or_default()
+}
+
+fn set(payload: &mut Value, pointer: &str, value: Value) {
+    let (parent, key) = pointer.rsplit_once('/').unwrap();
+    if let Some(Value::Object(object)) = payload.pointer_mut(parent) {
+        object.insert(key.to_string(), value);
+    }
+}
+
+impl Consensus {
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "Consensus for {} from {} version(s): {}\n\nContributors:\n",
+            self.concept,
+            self.contributors.len(),
+            self.glyph.header.hash
+        );
+        for contributor in &self.contributors {
+            text.push_str(&format!(
+                "  {:<24} weight {:.2}  {}  {} ({})\n",
+                contributor.agent, contributor.weight, contributor.signer, contributor.label, contributor.id
+            ));
+        }
+
+        let mut field = "";
+        for attribution in &self.attribution {
+            if attribution.field != field {
+                field = &attribution.field;
+                text.push_str(&format!("\n{}\n", field));
+            }
+            text.push_str(&format!(
+                "  {} {:>3.0}%  {}  {}\n",
+                if attribution.adopted { "*" } else { " " },
+                attribution.support * 100.0,
+                attribution.value,
+                attribution.agents.join(", ")
+            ));
+        }
+        text
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::glyph;
+
+    // Keyring name and signing key seed of each agent
+    const AGENTS: [(&str, u8); 3] = [("NASA", 1), ("ESA", 2), ("priest", 3)];
+
+    fn keyring() -> Keyring {
+        let mut keyring = Keyring::new();
+        for (name, seed) in AGENTS {
+            keyring.add(name, ed25519_dalek::SigningKey::from_bytes(&[seed; 32]).verifying_key().to_bytes().to_vec());
+        }
+        keyring
+    }
+
+    fn store() -> Vec<Glyph> {
+        vec![
+            glyph("sun").creator("agent:NASA").aliases(&["sol"]).relation("orbited_by", "glyph:earth"),
+            glyph("Sun").creator("agent:ESA").confidence(0.7)
+                .relation("orbited_by", "glyph:earth").relation("glyphos:astrology/rules", "glyph:leo"),
+            glyph("Ra").creator("agent:priest").mode("mythic").confidence(0.5).relation("rides", "glyph:barque"),
+        ].into_iter()
+            .zip(AGENTS)
+            .map(|(version, (_, seed))| version.classes(&["astronomy"]).wikidata("Q525").build_signed(&[seed; 32]))
+            .collect()
+    }
+
+    #[test]
+    fn test_weighted_consensus_keeps_minorities() {
+        let glyphs = store();
+        let policy = TrustPolicy::from_json5(r#"{ signers: { NASA: 2.0 }, creators: { "agent:priest": 0.5 } }"#).unwrap();
+        // "Ra" shares the wikidata id, so asking for the label finds all three
+        let result = consensus(&glyphs, "sun", &policy, &keyring()).unwrap();
+        let payload = &result.glyph.payload;
+
+        assert_eq!(result.contributors.len(), 3);
+        assert_eq!(payload.label, "sun");
+        assert_eq!(payload.truth_mode.r#type, "empirical");
+        assert!((payload.truth_mode.confidence - (0.9 * 2.0 + 0.7 + 0.5 * 0.5) / 3.5).abs() < 1e-9);
+
+        let relations: Vec<&str> = payload.relations.iter().map(|r| r.r#type.as_str()).collect();
+        assert_eq!(relations, vec!["orbited_by", "glyphos:astrology/rules", "agent:priest/rides"]);
+        let overlays: Vec<(&str, &str)> = payload.namespace.contextual_overlays.iter()
+            .map(|o| (o.agent.as_str(), o.label.as_str()))
+            .collect();
+        assert_eq!(overlays, vec![("agent:ESA", "Sun"), ("agent:priest", "Ra")]);
+        assert_eq!(result.glyph.header.hash, result.glyph.semantic_hash());
+
+        let label = result.attribution.iter().find(|a| a.field == "label" && a.adopted).unwrap();
+        assert_eq!(label.agents, vec!["agent:NASA"]);
+        assert!((label.support - 2.0 / 3.5).abs() < 1e-9);
+
+        // The minority perspective survives in a view
+        let ra = result.glyph.view("agent:priest").unwrap();
+        assert_eq!(ra.payload.label, "Ra");
+        assert!(ra.payload.relations.iter().any(|r| r.r#type == "rides"));
+    }
+
+    #[test]
+    fn test_trust_decides_and_untrusted_versions_are_ignored() {
+        let glyphs = store();
+        let policy = TrustPolicy::from_json5(r#"{ creators: { "agent:priest": 5.0 }, default: 0.0 }"#).unwrap();
+        let result = consensus(&glyphs, "Q525", &policy, &keyring()).unwrap();
+        assert_eq!(result.glyph.payload.label, "Ra");
+        assert_eq!(result.glyph.payload.truth_mode.r#type, "mythic");
+        // Weightless versions get no overlay, aliases or relations, scoped or not
+        assert!(result.glyph.payload.namespace.contextual_overlays.is_empty());
+        assert!(result.glyph.payload.aliases.is_empty());
+        let relations: Vec<&str> = result.glyph.payload.relations.iter().map(|r| r.r#type.as_str()).collect();
+        assert_eq!(relations, vec!["rides"]);
+
+        // Claimed creators carry no weight without a known signer
+        assert!(consensus(&glyphs, "Q525", &policy, &Keyring::new()).is_err());
+        assert!(consensus(&glyphs, "moon", &policy, &keyring()).is_err());
+        assert!(consensus(&glyphs, "sun", &TrustPolicy::from_json5("{ default: 0 }").unwrap(), &Keyring::new()).is_err());
+        assert!(TrustPolicy::from_json5("{ default: -1 }").is_err());
+    }
+}
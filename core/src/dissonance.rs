@@ -0,0 +1,319 @@
+// Dissonance analysis: contradicting claims across a corpus.
+//
+// The analyzer looks at the latest revision of every concept and reports:
+//
+//   relations           two disjoint relations hold between the same glyphs,
+//                       including relations derived by the inference rules
+//                       (sun opposite_of moon, sun same_as moon)
+//   classes             one concept is placed in classes declared disjoint
+//   truth_modes         versions of one concept (same wikidata id or label)
+//                       carry different truth modes
+//   declared_conflict   a glyph lists another in truth_mode.conflicts
+//   unresolved_conflict a declared conflict names no glyph in the corpus
+//
+// Each dissonance names the glyphs involved with their creator and signer, so
+// a report can be traced back to whoever made the claims.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+use super::glyph_decode::Glyph;
+use super::glyph_ref::Resolver;
+use super::inference::{Closure, Node, RuleSet};
+use super::lineage;
+use super::trust::{Keyring, Signer};
+
+/// Rules used when no rules file is given
+pub const DEFAULT_RULES: &str = r#"{
+    relations: {
+        opposite_of: { symmetric: true },
+        same_as: { symmetric: true, transitive: true },
+    },
+    disjoint_relations: [["opposite_of", "same_as"]],
+}"#;
+
+// ============================================================================
+// DATA STRUCTURES
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DissonanceKind {
+    Relations,
+    Classes,
+    TruthModes,
+    DeclaredConflict,
+    UnresolvedConflict,
+}
+
+/// A glyph taking part in a dissonance
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Party {
+    pub id: String,
+    pub label: String,
+    pub hash: String,
+    pub creator: Option<String>,
+    pub signer: Signer,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Dissonance {
+    pub kind: DissonanceKind,
+    pub description: String,
+    pub glyphs: Vec<Party>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    /// Glyphs analyzed after collapsing revisions
+    pub glyphs: usize,
+    pub dissonances: Vec<Dissonance>,
+}
+
+// ============================================================================
+// ANALYSIS
+// ============================================================================
+
+/// Key under which versions of one concept from different sources meet:
+/// the wikidata id when grounded, the lowercased label otherwise
+pub fn concept_key(glyph: &Glyph) -> String {
+    match &glyph.payload.grounding.wikidata {
+        Some(wikidata) => format!("wikidata:{}", wikidata),
+        None => format!("label:{}", glyph.payload.label.to_lowercase()),
+    }
+}
+
+fn agent(party: &Party) -> String {
+    party.signer.agent(party.creator.as_deref()).unwrap_or_else(|| "unknown agent".to_string())
+}
+
+pub fn analyze(glyphs: &[Glyph], rules: &RuleSet, keyring: &Keyring) -> Result<Report, Box<dyn std::error::Error>> {
+    let current = lineage::latest_revisions(glyphs);
+    let party = |i: usize| {
+        let glyph = &glyphs[i];
+        Party {
+            id: glyph.header.id.clone(),
+            label: glyph.payload.label.clone(),
+            hash: glyph.header.hash.clone(),
+            creator: glyph.payload.origin.as_ref().map(|origin| origin.creator.clone()),
+            signer: keyring.signer(glyph),
+        }
+    };
+    let mut dissonances = Vec::new();
+
+    // Disjoint relations, over written and inferred facts
+    let closure = Closure::compute(glyphs, rules)?;
+    let index: HashMap<(&Node, &str, &Node), usize> = closure.facts.iter().enumerate()
+        .map(|(i, fact)| ((&fact.subject, fact.relation.as_str(), &fact.object), i))
+        .collect();
+    let mut seen = BTreeSet::new();
+    for set in &rules.disjoint_relations {
+        for (a, first) in set.iter().enumerate() {
+            for second in &set[a + 1..] {
+                for (i, fact) in closure.facts.iter().enumerate() {
+                    if fact.relation != *first {
+                        continue;
+                    }
+                    let other = index.get(&(&fact.subject, second.as_str(), &fact.object))
+                        .or_else(|| index.get(&(&fact.object, second.as_str(), &fact.subject)));
+                    let Some(&j) = other else { continue };
+
+                    let mut sources = closure.asserted_by(i);
+                    sources.extend(closure.asserted_by(j));
+                    sources.sort();
+                    sources.dedup();
+                    // Claims of superseded revisions no longer count
+                    if sources.iter().any(|source| !current.contains(source)) {
+                        continue;
+                    }
+                    let pair = if fact.subject <= fact.object {
+                        (fact.subject.clone(), fact.object.clone())
+                    } else {
+                        (fact.object.clone(), fact.subject.clone())
+                    };
+                    if !seen.insert((pair, first.clone(), second.clone())) {
+                        continue;
+                    }
+                    dissonances.push(Dissonance {
+                        kind: DissonanceKind::Relations,
+                        description: format!("{} contradicts {}", closure.describe(i), closure.describe(j)),
+                        glyphs: sources.into_iter().map(party).collect(),
+                    });
+                }
+            }
+        }
+    }
+
+    let mut concepts: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for &i in &current {
+        concepts.entry(concept_key(&glyphs[i])).or_default().push(i);
+    }
+
+    for members in concepts.values() {
+        let label = &glyphs[members[0]].payload.label;
+
+        // Disjoint classes, within one glyph or across versions
+        for set in &rules.disjoint_classes {
+            let holders: Vec<(&String, Vec<usize>)> = set.iter()
+                .map(|class| (class, members.iter().copied().filter(|&i| glyphs[i].payload.classes.contains(class)).collect::<Vec<_>>()))
+                .filter(|(_, holders)| !holders.is_empty())
+                .collect();
+            if holders.len() < 2 {
+                continue;
+            }
+            let classes: Vec<&str> = holders.iter().map(|(class, _)| class.as_str()).collect();
+            let mut involved: Vec<usize> = holders.iter().flat_map(|(_, holders)| holders.iter().copied()).collect();
+            involved.sort();
+            involved.dedup();
+            dissonances.push(Dissonance {
+                kind: DissonanceKind::Classes,
+                description: format!("{} is placed in disjoint classes {}", label, classes.join(", ")),
+                glyphs: involved.into_iter().map(party).collect(),
+            });
+        }
+
+        // Truth modes of versions from different sources
+        let mut modes: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for &i in members {
+            modes.entry(glyphs[i].payload.truth_mode.r#type.as_str()).or_default().push(i);
+        }
+        if modes.len() > 1 {
+            let claims: Vec<String> = modes.iter()
+                .map(|(mode, holders)| {
+                    let mut agents: Vec<String> = holders.iter().map(|&i| agent(&party(i))).collect();
+                    agents.sort();
+                    agents.dedup();
+                    format!("{} ({})", mode, agents.join(", "))
+                })
+                .collect();
+            dissonances.push(Dissonance {
+                kind: DissonanceKind::TruthModes,
+                description: format!("{} has different truth modes: {}", label, claims.join(" vs ")),
+                glyphs: members.iter().copied().map(party).collect(),
+            });
+        }
+    }
+
+    // Conflicts declared in the truth modes themselves
+    let resolver = Resolver::new(glyphs);
+    let positions: HashMap<*const Glyph, usize> = glyphs.iter().enumerate()
+        .map(|(i, glyph)| (glyph as *const Glyph, i))
+        .collect();
+    let mut declared = BTreeSet::new();
+    for &i in &current {
+        for reference in &glyphs[i].payload.truth_mode.conflicts {
+            let full = if reference.contains(':') { reference.clone() } else { format!("glyph:{}", reference) };
+            match resolver.resolve_str(&full) {
+                Ok(other) => {
+                    let j = positions[&(other as *const Glyph)];
+                    if !declared.insert((i.min(j), i.max(j))) {
+                        continue;
+                    }
+                    dissonances.push(Dissonance {
+                        kind: DissonanceKind::DeclaredConflict,
+                        description: format!("{} declares a conflict with {}", glyphs[i].payload.label, other.payload.label),
+                        glyphs: vec![party(i), party(j)],
+                    });
+                }
+                Err(e) => dissonances.push(Dissonance {
+                    kind: DissonanceKind::UnresolvedConflict,
+                    description: format!("{} declares a conflict with {}: {}", glyphs[i].payload.label, reference, e),
+                    glyphs: vec![party(i)],
+                }),
+            }
+        }
+    }
+
+    Ok(Report { glyphs: current.len(), dissonances })
+}
+
+// ============================================================================
+// REPORT
+// ============================================================================
+
+impl Report {
+    pub fn is_empty(&self) -> bool {
+        self.dissonances.is_empty()
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("Dissonance report: {} glyph(s), {} dissonance(s)\n", self.glyphs, self.dissonances.len());
+        for dissonance in &self.dissonances {
+            text.push_str(&format!("\n  [{}] {}\n", dissonance.kind, dissonance.description));
+            for party in &dissonance.glyphs {
+                text.push_str(&format!(
+                    "    - {} {}  {}  creator {}, {}\n",
+                    party.label,
+                    party.id,
+                    party.hash,
+                    party.creator.as_deref().unwrap_or("unknown"),
+                    party.signer
+                ));
+            }
+        }
+        text
+    }
+}
+
+impl fmt::Display for DissonanceKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            DissonanceKind::Relations => "relations",
+            DissonanceKind::Classes => "classes",
+            DissonanceKind::TruthModes => "truth_modes",
+            DissonanceKind::DeclaredConflict => "declared_conflict",
+            DissonanceKind::UnresolvedConflict => "unresolved_conflict",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::glyph;
+
+    fn kinds(report: &Report) -> Vec<DissonanceKind> {
+        report.dissonances.iter().map(|dissonance| dissonance.kind).collect()
+    }
+
+    #[test]
+    fn test_relations_through_inference() {
+        // moon same_as luna, so sun opposite_of moon meets sun same_as luna
+        let glyphs = vec![
+            glyph("sun").relation("opposite_of", "glyph:moon").relation("same_as", "glyph:luna").build(),
+            glyph("moon").relation("same_as", "glyph:luna").build(),
+            glyph("luna").build(),
+        ];
+        let report = analyze(&glyphs, &RuleSet::from_json5(DEFAULT_RULES).unwrap(), &Keyring::new()).unwrap();
+        assert_eq!(kinds(&report), vec![DissonanceKind::Relations]);
+        let labels: Vec<&str> = report.dissonances[0].glyphs.iter().map(|party| party.label.as_str()).collect();
+        assert_eq!(labels, vec!["sun", "moon"]);
+
+        // Without the rules the contradiction stays hidden
+        assert!(analyze(&glyphs, &RuleSet::default(), &Keyring::new()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_classes_modes_and_declared_conflicts() {
+        let rules = RuleSet::from_json5(r#"{ disjoint_classes: [["star", "planet"]] }"#).unwrap();
+        let glyphs = vec![
+            glyph("Sun").classes(&["star"]).conflicts(&["glyph:flat_earth"]).build(),
+            glyph("sun").classes(&["planet"]).mode("mythic").conflicts(&["glyph:Sun"]).build(),
+        ];
+        let report = analyze(&glyphs, &rules, &Keyring::new()).unwrap();
+        assert_eq!(kinds(&report), vec![
+            DissonanceKind::Classes,
+            DissonanceKind::TruthModes,
+            DissonanceKind::UnresolvedConflict,
+            DissonanceKind::DeclaredConflict,
+        ]);
+        assert!(report.dissonances[1].description.contains("empirical (unknown agent) vs mythic (unknown agent)"));
+        assert!(report.dissonances.iter().flat_map(|d| &d.glyphs).all(|party| party.signer == Signer::Unsigned));
+    }
+}
@@ -0,0 +1,311 @@
+// Symbolic firewall: policy-driven filtering of incoming glyphs.
+//
+// A JSON5 policy lists allow/deny rules, checked in order; the first rule
+// whose conditions all hold decides, and `default` applies when none does:
+//
+//   {
+//     default: "allow",
+//     rules: [
+//       { action: "deny", valid: false, reason: "fails validation" },
+//       { action: "allow", signers: ["nasa", "esa"] },
+//       { action: "deny", signers: ["unsigned"] },
+//       { action: "deny", namespaces: ["glyph:spam*"] },
+//       { action: "deny", classes: ["propaganda"] },
+//       { action: "deny", relations: ["*/controls", "same_as glyph:flat_earth"] },
+//       { action: "deny", larger_than: 1048576 },
+//     ],
+//   }
+//
+//   signers      keyring names, `unsigned`, or `unknown` (a signature no key
+//                in the keyring accepts)
+//   namespaces   globs over namespace.provision
+//   classes      globs, matching when any class matches
+//   relations    `<type>` or `<type> <target>` globs, matching any relation
+//   larger_than  encoded size in bytes
+//   valid        whether the glyph passes validation and its header hash
+//                matches its payload
+//
+// Globs support `*` only. A verdict always carries its reasons, so every
+// rejection can be recorded and explained.
+
+use serde::{Deserialize, Serialize};
+
+use super::glyph_decode::Glyph;
+use super::trust::{Keyring, Signer};
+
+// ============================================================================
+// DATA STRUCTURES
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Allow,
+    Deny,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FirewallRule {
+    pub action: Option<Action>,
+    #[serde(default)]
+    pub signers: Vec<String>,
+    #[serde(default)]
+    pub namespaces: Vec<String>,
+    #[serde(default)]
+    pub classes: Vec<String>,
+    #[serde(default)]
+    pub relations: Vec<String>,
+    #[serde(default)]
+    pub larger_than: Option<usize>,
+    #[serde(default)]
+    pub valid: Option<bool>,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    #[serde(default = "default_action")]
+    pub default: Action,
+    #[serde(default)]
+    pub rules: Vec<FirewallRule>,
+}
+
+fn default_action() -> Action {
+    Action::Allow
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Verdict {
+    pub action: Action,
+    /// Index of the deciding rule; none when the default applied
+    pub rule: Option<usize>,
+    pub reasons: Vec<String>,
+}
+
+// ============================================================================
+// POLICY
+// ============================================================================
+
+impl Policy {
+    pub fn from_json5(content: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let policy: Policy = serde_json5::from_str(content)?;
+        for (i, rule) in policy.rules.iter().enumerate() {
+            if rule.action.is_none() {
+                return Err(format!("Rule {} has no action", i + 1).into());
+            }
+            let conditions = !rule.signers.is_empty()
+                || !rule.namespaces.is_empty()
+                || !rule.classes.is_empty()
+                || !rule.relations.is_empty()
+                || rule.larger_than.is_some()
+                || rule.valid.is_some();
+            if !conditions {
+                return Err(format!("Rule {} has no conditions; use `default` instead", i + 1).into());
+            }
+            for pattern in &rule.relations {
+                if pattern.split_whitespace().count() > 2 {
+                    return Err(format!("Rule {}: relation pattern '{}' is not '<type> [<target>]'", i + 1, pattern).into());
+                }
+            }
+        }
+        Ok(policy)
+    }
+
+    /// Judge a glyph of `size` encoded bytes
+    pub fn evaluate(&self, glyph: &Glyph, size: usize, keyring: &Keyring) -> Verdict {
+        let signer = keyring.signer(glyph);
+        let mut problems = glyph.validate().err().unwrap_or_default();
+        if glyph.header.hash != glyph.semantic_hash() {
+            problems.push("header hash does not match the payload".to_string());
+        }
+
+        for (i, rule) in self.rules.iter().enumerate() {
+            if let Some(mut reasons) = rule.matches(glyph, size, &signer, &problems) {
+                if let Some(reason) = &rule.reason {
+                    reasons.insert(0, reason.clone());
+                }
+                return Verdict { action: rule.action.unwrap_or(self.default), rule: Some(i), reasons };
+            }
+        }
+        Verdict {
+            action: self.default,
+            rule: None,
+            reasons: vec![format!("no rule matched; default is {}", self.default)],
+        }
+    }
+}
+
+impl FirewallRule {
+    /// Why every condition of the rule holds, or None if one does not
+    fn matches(&self, glyph: &Glyph, size: usize, signer: &Signer, problems: &[String]) -> Option<Vec<String>> {
+        let mut reasons = Vec::new();
+
+        if !self.signers.is_empty() {
+            let name = match signer {
+                Signer::Unsigned => "unsigned",
+                Signer::Unknown => "unknown",
+                Signer::Known(name) => name.as_str(),
+            };
+            self.signers.iter().find(|pattern| glob(pattern, name))?;
+            reasons.push(format!("signer: {}", signer));
+        }
+
+        if !self.namespaces.is_empty() {
+            let provision = &glyph.payload.namespace.provision;
+            self.namespaces.iter().find(|pattern| glob(pattern, provision))?;
+            reasons.push(format!("namespace: {}", provision));
+        }
+
+        if !self.classes.is_empty() {
+            let class = glyph.payload.classes.iter()
+                .find(|class| self.classes.iter().any(|pattern| glob(pattern, class)))?;
+            reasons.push(format!("class: {}", class));
+        }
+
+        if !self.relations.is_empty() {
+            let relation = glyph.payload.relations.iter()
+                .find(|relation| self.relations.iter().any(|pattern| {
+                    let mut parts = pattern.split_whitespace();
+                    let relation_type = parts.next().unwrap_or("*");
+                    glob(relation_type, &relation.r#type) && parts.next().is_none_or(|target| glob(target, &relation.target))
+                }))?;
+            reasons.push(format!("relation: {} {}", relation.r#type, relation.target));
+        }
+
+        if let Some(limit) = self.larger_than {
+            if size <= limit {
+                return None;
+            }
+            reasons.push(format!("size: {} bytes exceeds {}", size, limit));
+        }
+
+        if let Some(valid) = self.valid {
+            if valid != problems.is_empty() {
+                return None;
+            }
+            if valid {
+                reasons.push("valid".to_string());
+            } else {
+                reasons.extend(problems.iter().map(|problem| format!("invalid: {}", problem)));
+            }
+        }
+
+        Some(reasons)
+    }
+}
+
+/// Match `text` against a pattern where `*` stands for any run of characters
+pub fn glob(pattern: &str, text: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == text;
+    }
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !text.starts_with(first) || text.len() < first.len() + last.len() || !text.ends_with(last) {
+        return false;
+    }
+    let mut rest = &text[first.len()..text.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+impl Verdict {
+    pub fn allowed(&self) -> bool {
+        self.action == Action::Allow
+    }
+
+    pub fn describe(&self) -> String {
+        let rule = match self.rule {
+            Some(i) => format!("rule {}", i + 1),
+            None => "default".to_string(),
+        };
+        format!("{} by {}: {}", self.action, rule, self.reasons.join("; "))
+    }
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Action::Allow => write!(f, "allowed"),
+            Action::Deny => write!(f, "denied"),
+        }
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::glyph;
+
+    const POLICY: &str = r#"{
+        default: "allow",
+        rules: [
+            { action: "deny", valid: false, reason: "fails validation" },
+            { action: "deny", namespaces: ["glyph:spam*"] },
+            { action: "deny", classes: ["propaganda"] },
+            { action: "deny", relations: ["*/controls", "same_as glyph:flat_*"] },
+            { action: "deny", larger_than: 4096 },
+        ],
+    }"#;
+
+    #[test]
+    fn test_first_matching_rule_decides() {
+        let policy = Policy::from_json5(POLICY).unwrap();
+        let keyring = Keyring::new();
+
+        let clean = glyph("sun").classes(&["star"]).relation("same_as", "glyph:sol").build();
+        let verdict = policy.evaluate(&clean, 500, &keyring);
+        assert!(verdict.allowed());
+        assert_eq!(verdict.rule, None);
+
+        let cases = [
+            (glyph("sun").provision("glyph:spam/ads").build(), 1, "namespace: glyph:spam/ads"),
+            (glyph("sun").classes(&["star", "propaganda"]).build(), 2, "class: propaganda"),
+            (glyph("sun").relation("same_as", "glyph:flat_earth").build(), 3, "relation: same_as glyph:flat_earth"),
+            (glyph("sun").relation("agent:x/controls", "glyph:minds").build(), 3, "relation: agent:x/controls glyph:minds"),
+        ];
+        for (glyph, rule, reason) in cases {
+            let verdict = policy.evaluate(&glyph, 500, &keyring);
+            assert!(!verdict.allowed());
+            assert_eq!(verdict.rule, Some(rule));
+            assert_eq!(verdict.reasons, vec![reason.to_string()]);
+        }
+        assert_eq!(policy.evaluate(&clean, 5000, &keyring).rule, Some(4));
+
+        // Tampering trips the validation rule before anything else
+        let mut tampered = glyph("sun").provision("glyph:spam").build();
+        tampered.payload.label = "moon".to_string();
+        let verdict = policy.evaluate(&tampered, 500, &keyring);
+        assert_eq!(verdict.rule, Some(0));
+        assert_eq!(verdict.reasons[0], "fails validation");
+        assert!(verdict.reasons[1].contains("header hash"));
+    }
+
+    #[test]
+    fn test_signers_and_policy_checks() {
+        let policy = Policy::from_json5(r#"{ default: "deny", rules: [{ action: "allow", signers: ["nasa"] }, { action: "deny", signers: ["unsigned"] }] }"#).unwrap();
+        let verdict = policy.evaluate(&glyph("sun").build(), 10, &Keyring::new());
+        assert_eq!((verdict.action, verdict.rule), (Action::Deny, Some(1)));
+        assert_eq!(verdict.reasons, vec!["signer: unsigned".to_string()]);
+
+        assert!(glob("glyph:*", "glyph:core"));
+        assert!(glob("*a*b*", "xaybz"));
+        assert!(!glob("glyph:*core", "glyph:cor"));
+
+        assert!(Policy::from_json5(r#"{ rules: [{ signers: ["nasa"] }] }"#).is_err());
+        assert!(Policy::from_json5(r#"{ rules: [{ action: "deny" }] }"#).is_err());
+        assert!(Policy::from_json5(r#"{ default: "maybe" }"#).is_err());
+    }
+}
@@ -4,15 +4,18 @@ use ciborium::{into_writer, from_reader};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 use blake3::Hasher;
-use ed25519_dalek::{SigningKey, VerifyingKey, Signature, Signer, Verifier};
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 use clap::Parser;
-use hex;
-use bardecoder;
-use image::io::Reader as ImageReader;
-use base64;
+
+use super::glyph_sign;
+use super::glyph_encode;
+use super::qr_scan;
+use super::svg_sanitize;
+use super::media::MediaAttachment;
+use super::lineage::Lineage;
+use super::perspective;
 
 // ============================================================================
 // DATA STRUCTURES (Shared with glyph-encode.rs)
@@ -26,6 +29,8 @@ pub struct GlyphHeader {
     pub hash: String,
     pub signature: Option<String>,
     pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lineage: Option<Lineage>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -60,13 +65,19 @@ pub struct TruthMode {
 pub struct Audio {
     pub file: Option<String>,
     pub phonetic: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media: Option<MediaAttachment>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Visual {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
     pub svg: Option<String>,
     pub sigil: Option<String>,
     pub style: Option<VisualStyle>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media: Option<MediaAttachment>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -162,29 +173,27 @@ struct Args {
 
 impl Glyph {
     pub fn from_qr_code(qr_data: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
-        // Load image from bytes
-        let img = ImageReader::new(std::io::Cursor::new(qr_data))
-            .with_guessed_format()?
-            .decode()?;
-        
-        // Create QR decoder
-        let decoder = bardecoder::default_decoder();
-        
-        // Decode QR code
-        let results = decoder.decode(&img);
-        
-        // Find the first successful result
-        let qr_content = results.into_iter()
-            .filter_map(|r| r.ok())
-            .next()
-            .ok_or("No valid QR code found in image")?;
-        
-        // Decode base64 content back to CBOR bytes
-        let cbor_data = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &qr_content)?;
+        Self::from_qr_codes(&[qr_data])
+    }
+    
+    /// Decode a glyph from one or more QR images. Chunked glyphs may be spread
+    /// over several images or share one image; the pieces are reassembled and
+    /// verified against their content hash. Fails unless the images hold
+    /// exactly one distinct glyph; use `qr_scan::Scanner` for batches.
+    pub fn from_qr_codes(images: &[&[u8]]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut scanner = qr_scan::Scanner::new();
+        for (index, qr_data) in images.iter().enumerate() {
+            scanner.scan_bytes(&format!("image {}", index + 1), qr_data);
+        }
         
-        // Decode CBOR to glyph
-        let glyph = Self::from_cbor(&cbor_data)?;
-        Ok(glyph)
+        let mut result = scanner.finish();
+        match result.glyphs.len() {
+            1 => Ok(result.glyphs.remove(0).glyph),
+            0 => Err(result.first_problem()
+                .unwrap_or_else(|| "No valid QR code found in image".to_string())
+                .into()),
+            n => Err(format!("QR codes contain {} different glyphs", n).into()),
+        }
     }
     
     pub fn from_cbor(cbor_data: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
@@ -192,39 +201,50 @@ impl Glyph {
         Ok(glyph)
     }
     
+    pub fn to_cbor(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut buffer = Vec::new();
+        into_writer(self, &mut buffer)?;
+        Ok(buffer)
+    }
+    
+    /// Recompute the semantic hash from the payload
+    pub fn semantic_hash(&self) -> String {
+        let media: Vec<&str> = [&self.payload.audio.media, &self.payload.visual.media].into_iter()
+            .flatten()
+            .map(|media| media.digest.as_str())
+            .collect();
+        glyph_encode::Glyph::compute_hash(&glyph_encode::Glyph::semantic_content(
+            &self.payload.label,
+            &self.payload.aliases,
+            &self.payload.classes,
+            &self.payload.truth_mode.r#type,
+            self.payload.grounding.wikidata.as_deref(),
+            &media,
+        ))
+    }
+    
+    /// File name of the glyph in a directory, `<id>.glyph`. The id comes from
+    /// the glyph itself, so anything but a UUID is refused instead of becoming
+    /// part of a path.
+    pub fn file_name(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let id = Uuid::parse_str(&self.header.id)
+            .map_err(|_| format!("Glyph id {:?} is not a UUID", self.header.id))?;
+        Ok(format!("{}.glyph", id))
+    }
+    
     pub fn verify_signature(&self, public_key: &[u8]) -> Result<bool, Box<dyn std::error::Error>> {
         if let Some(signature_str) = &self.header.signature {
-            // Parse signature
-            let signature_hex = signature_str.strip_prefix("ed25519:")
-                .ok_or("Invalid signature format")?;
-            let signature_bytes = hex::decode(signature_hex)?;
-            
-            // Convert signature bytes to the expected format
-            if signature_bytes.len() != 64 {
-                return Err("Invalid signature length".into());
-            }
-            let mut sig_array = [0u8; 64];
-            sig_array.copy_from_slice(&signature_bytes);
-            let signature = Signature::from_bytes(&sig_array);
-            
-            // Convert public key to the expected format
-            if public_key.len() != 32 {
-                return Err("Invalid public key length".into());
-            }
-            let mut key_array = [0u8; 32];
-            key_array.copy_from_slice(public_key);
-            let verifying_key = VerifyingKey::from_bytes(&key_array)?;
-            
-            // Verify
-            let message = self.header.hash.as_bytes();
-            let is_valid = verifying_key.verify(message, &signature).is_ok();
-            
-            Ok(is_valid)
+            glyph_sign::verify_hash(public_key, &self.header.hash, signature_str)
         } else {
             Ok(false) // No signature to verify
         }
     }
     
+    /// The glyph as seen from one perspective's contextual overlay
+    pub fn view(&self, perspective: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        perspective::view(self, perspective)
+    }
+    
     pub fn validate(&self) -> Result<(), Vec<String>> {
         let mut errors = Vec::new();
         
@@ -263,6 +283,20 @@ impl Glyph {
             }
         }
         
+        // Inline SVG must survive the sanitizer unchanged
+        if let Some(svg) = &self.payload.visual.svg {
+            errors.extend(svg_sanitize::validation_errors(svg));
+        }
+        
+        // Embedded media must match its recorded digest
+        for media in [&self.payload.audio.media, &self.payload.visual.media].into_iter().flatten() {
+            if media.is_embedded() {
+                if let Err(error) = media.verify(None) {
+                    errors.push(format!("Media attachment invalid: {}", error));
+                }
+            }
+        }
+        
         // Validate timestamp
         if self.header.created_at > Utc::now() {
             errors.push("Created timestamp is in the future".to_string());
@@ -305,6 +339,13 @@ impl Glyph {
             text.push_str(&format!("Signature: {}\n", signature));
         }
         
+        if let Some(lineage) = &self.header.lineage {
+            text.push_str(&format!("Concept: {} (revision {})\n", lineage.concept_id, lineage.revision));
+            if let Some(parent) = &lineage.parent {
+                text.push_str(&format!("Parent: {}\n", parent));
+            }
+        }
+        
         text.push_str(&format!("Classes: {}\n", self.payload.classes.join(", ")));
         text.push_str(&format!("Truth Mode: {} (confidence: {})\n", 
             self.payload.truth_mode.r#type, self.payload.truth_mode.confidence));
@@ -492,6 +533,7 @@ mod tests {
                 hash: "blake3:test123".to_string(),
                 signature: None,
                 created_at: Utc::now(),
+                lineage: None,
             },
             payload: GlyphPayload {
                 label: "test".to_string(),
@@ -511,11 +553,14 @@ mod tests {
                 audio: Audio {
                     file: None,
                     phonetic: None,
+                    media: None,
                 },
                 visual: Visual {
+                    file: None,
                     svg: None,
                     sigil: None,
                     style: None,
+                    media: None,
                 },
                 relations: vec![],
                 grounding: Grounding {
@@ -546,6 +591,7 @@ mod tests {
                 hash: "blake3:test".to_string(),
                 signature: None,
                 created_at: Utc::now(),
+                lineage: None,
             },
             payload: GlyphPayload {
                 label: "test".to_string(),
@@ -565,11 +611,14 @@ mod tests {
                 audio: Audio {
                     file: None,
                     phonetic: None,
+                    media: None,
                 },
                 visual: Visual {
+                    file: None,
                     svg: None,
                     sigil: None,
                     style: None,
+                    media: None,
                 },
                 relations: vec![],
                 grounding: Grounding {
@@ -582,5 +631,14 @@ mod tests {
         assert_eq!(test_glyph.extract_field("payload.label").unwrap(), "test");
         assert_eq!(test_glyph.extract_field("header.magic").unwrap(), "GLYPH");
         assert!(test_glyph.extract_field("unknown.field").is_err());
+
+        // Only a UUID id names a file
+        assert!(test_glyph.file_name().is_err());
+        let mut test_glyph = test_glyph;
+        test_glyph.header.id = "../outside/escaped".to_string();
+        assert!(test_glyph.file_name().is_err());
+        let id = Uuid::new_v4().to_string();
+        test_glyph.header.id = id.clone();
+        assert_eq!(test_glyph.file_name().unwrap(), format!("{}.glyph", id));
     }
 }
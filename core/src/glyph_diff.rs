@@ -0,0 +1,345 @@
+// Semantic diff between two glyphs.
+//
+// Instead of diffing JSON text, fields are compared by meaning: aliases,
+// classes and other lists are compared as sets, relations are matched by type
+// so a changed target reads as a retarget, and confidence changes carry their
+// delta. The diff also says whether the semantic hash changed and what state
+// each side's signature is in.
+//
+//   ~ label: sun → sol
+//   + aliases: star
+//   ~ relations: orbits glyph:earth → glyph:sun (retargeted)
+//   ~ truth_mode.confidence: 0.95 → 0.8 (-0.15)
+
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::glyph_decode::Glyph;
+
+// ============================================================================
+// DATA STRUCTURES
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+    Retargeted,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub kind: ChangeKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureStatus {
+    Unsigned,
+    /// Signed, but no key was given to check it
+    Signed,
+    Valid,
+    Invalid,
+    /// The stored hash does not match the payload, so no signature over it
+    /// speaks for this content
+    HashMismatch,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GlyphDiff {
+    pub hash_a: String,
+    pub hash_b: String,
+    pub semantic_hash_changed: bool,
+    pub signature_a: SignatureStatus,
+    pub signature_b: SignatureStatus,
+    pub changes: Vec<FieldChange>,
+}
+
+// ============================================================================
+// DIFFING
+// ============================================================================
+
+impl GlyphDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+
+        for change in &self.changes {
+            let symbol = match change.kind {
+                ChangeKind::Added => '+',
+                ChangeKind::Removed => '-',
+                ChangeKind::Changed | ChangeKind::Retargeted => '~',
+            };
+            let value = match (&change.from, &change.to) {
+                (Some(from), Some(to)) => format!("{} → {}", from, to),
+                (Some(value), None) | (None, Some(value)) => value.clone(),
+                (None, None) => String::new(),
+            };
+            text.push_str(&format!("{} {}: {}", symbol, change.field, value));
+            if let Some(note) = &change.note {
+                text.push_str(&format!(" ({})", note));
+            }
+            text.push('\n');
+        }
+        if self.changes.is_empty() {
+            text.push_str("No semantic changes\n");
+        }
+
+        text.push('\n');
+        if self.semantic_hash_changed {
+            text.push_str(&format!("Semantic hash: changed ({} → {})\n", self.hash_a, self.hash_b));
+        } else {
+            text.push_str(&format!("Semantic hash: unchanged ({})\n", self.hash_a));
+        }
+        text.push_str(&format!(
+            "Signatures: {} → {}\n",
+            status_text(&self.signature_a),
+            status_text(&self.signature_b)
+        ));
+
+        text
+    }
+}
+
+fn status_text(status: &SignatureStatus) -> &'static str {
+    match status {
+        SignatureStatus::Unsigned => "unsigned",
+        SignatureStatus::Signed => "signed (not checked)",
+        SignatureStatus::Valid => "valid",
+        SignatureStatus::Invalid => "invalid",
+        SignatureStatus::HashMismatch => "hash does not match payload",
+    }
+}
+
+pub fn signature_status(glyph: &Glyph, public_key: Option<&[u8]>) -> SignatureStatus {
+    if glyph.header.hash != glyph.semantic_hash() {
+        return SignatureStatus::HashMismatch;
+    }
+    match (&glyph.header.signature, public_key) {
+        (None, _) => SignatureStatus::Unsigned,
+        (Some(_), None) => SignatureStatus::Signed,
+        (Some(_), Some(key)) => match glyph.verify_signature(key) {
+            Ok(true) => SignatureStatus::Valid,
+            _ => SignatureStatus::Invalid,
+        },
+    }
+}
+
+/// Compare two glyphs field by field. With a public key, both signatures are
+/// checked as well.
+pub fn diff(a: &Glyph, b: &Glyph, public_key: Option<&[u8]>) -> GlyphDiff {
+    let mut changes = Vec::new();
+    // Recomputed, so a stale or forged header hash cannot hide a change
+    let (hash_a, hash_b) = (a.semantic_hash(), b.semantic_hash());
+    let (pa, pb) = (&a.payload, &b.payload);
+
+    scalar(&mut changes, "label", Some(&pa.label), Some(&pb.label));
+    set(&mut changes, "aliases", &pa.aliases, &pb.aliases);
+    set(&mut changes, "classes", &pa.classes, &pb.classes);
+    scalar(&mut changes, "namespace.provision", Some(&pa.namespace.provision), Some(&pb.namespace.provision));
+    set(
+        &mut changes,
+        "namespace.contextual_overlays",
+        &pa.namespace.contextual_overlays.iter().map(|o| format!("{} ({}, {})", o.label, o.agent, o.truth_mode)).collect::<Vec<_>>(),
+        &pb.namespace.contextual_overlays.iter().map(|o| format!("{} ({}, {})", o.label, o.agent, o.truth_mode)).collect::<Vec<_>>(),
+    );
+    scalar(
+        &mut changes,
+        "origin.creator",
+        pa.origin.as_ref().map(|o| &o.creator),
+        pb.origin.as_ref().map(|o| &o.creator),
+    );
+
+    scalar(&mut changes, "truth_mode.type", Some(&pa.truth_mode.r#type), Some(&pb.truth_mode.r#type));
+    let (ca, cb) = (pa.truth_mode.confidence, pb.truth_mode.confidence);
+    if ca != cb {
+        changes.push(FieldChange {
+            field: "truth_mode.confidence".to_string(),
+            kind: ChangeKind::Changed,
+            from: Some(ca.to_string()),
+            to: Some(cb.to_string()),
+            note: Some(format!("{:+.2}", cb - ca)),
+        });
+    }
+    set(&mut changes, "truth_mode.verified_by", &pa.truth_mode.verified_by, &pb.truth_mode.verified_by);
+    set(&mut changes, "truth_mode.conflicts", &pa.truth_mode.conflicts, &pb.truth_mode.conflicts);
+
+    relations(&mut changes, a, b);
+
+    scalar(&mut changes, "grounding.wikidata", pa.grounding.wikidata.as_ref(), pb.grounding.wikidata.as_ref());
+    set(&mut changes, "grounding.sensors", &pa.grounding.sensors, &pb.grounding.sensors);
+
+    scalar(&mut changes, "audio.file", pa.audio.file.as_ref(), pb.audio.file.as_ref());
+    scalar(&mut changes, "audio.phonetic", pa.audio.phonetic.as_ref(), pb.audio.phonetic.as_ref());
+    scalar(
+        &mut changes,
+        "audio.media",
+        pa.audio.media.as_ref().map(|m| &m.digest),
+        pb.audio.media.as_ref().map(|m| &m.digest),
+    );
+    scalar(&mut changes, "visual.file", pa.visual.file.as_ref(), pb.visual.file.as_ref());
+    opaque(&mut changes, "visual.svg", pa.visual.svg.as_ref(), pb.visual.svg.as_ref());
+    opaque(&mut changes, "visual.sigil", pa.visual.sigil.as_ref(), pb.visual.sigil.as_ref());
+    scalar(
+        &mut changes,
+        "visual.media",
+        pa.visual.media.as_ref().map(|m| &m.digest),
+        pb.visual.media.as_ref().map(|m| &m.digest),
+    );
+    let style = |g: &Glyph| g.payload.visual.style.as_ref()
+        .map(|s| format!("color={} stroke={}", s.color.as_deref().unwrap_or("-"), s.stroke.map(|v| v.to_string()).unwrap_or("-".to_string())));
+    scalar(&mut changes, "visual.style", style(a).as_ref(), style(b).as_ref());
+
+    GlyphDiff {
+        semantic_hash_changed: hash_a != hash_b,
+        hash_a,
+        hash_b,
+        signature_a: signature_status(a, public_key),
+        signature_b: signature_status(b, public_key),
+        changes,
+    }
+}
+
+fn scalar(changes: &mut Vec<FieldChange>, field: &str, a: Option<&String>, b: Option<&String>) {
+    let kind = match (a, b) {
+        (a, b) if a == b => return,
+        (None, Some(_)) => ChangeKind::Added,
+        (Some(_), None) => ChangeKind::Removed,
+        _ => ChangeKind::Changed,
+    };
+    changes.push(FieldChange {
+        field: field.to_string(),
+        kind,
+        from: a.cloned(),
+        to: b.cloned(),
+        note: None,
+    });
+}
+
+// Large inline content is reported without its value
+fn opaque(changes: &mut Vec<FieldChange>, field: &str, a: Option<&String>, b: Option<&String>) {
+    let kind = match (a, b) {
+        (a, b) if a == b => return,
+        (None, Some(_)) => ChangeKind::Added,
+        (Some(_), None) => ChangeKind::Removed,
+        _ => ChangeKind::Changed,
+    };
+    let size = |v: Option<&String>| v.map(|v| format!("{} bytes", v.len()));
+    changes.push(FieldChange {
+        field: field.to_string(),
+        kind,
+        from: size(a),
+        to: size(b),
+        note: None,
+    });
+}
+
+fn set(changes: &mut Vec<FieldChange>, field: &str, a: &[String], b: &[String]) {
+    let a: BTreeSet<&String> = a.iter().collect();
+    let b: BTreeSet<&String> = b.iter().collect();
+
+    for removed in a.difference(&b) {
+        changes.push(FieldChange { field: field.to_string(), kind: ChangeKind::Removed, from: Some((*removed).clone()), to: None, note: None });
+    }
+    for added in b.difference(&a) {
+        changes.push(FieldChange { field: field.to_string(), kind: ChangeKind::Added, from: None, to: Some((*added).clone()), note: None });
+    }
+}
+
+// Relations are matched by type: one removed and one added target of the same
+// type is a retarget rather than two unrelated changes
+fn relations(changes: &mut Vec<FieldChange>, a: &Glyph, b: &Glyph) {
+    let pairs = |g: &Glyph| -> BTreeSet<(String, String)> {
+        g.payload.relations.iter().map(|r| (r.r#type.clone(), r.target.clone())).collect()
+    };
+    let (a, b) = (pairs(a), pairs(b));
+
+    let mut by_type: BTreeMap<&str, (Vec<&str>, Vec<&str>)> = BTreeMap::new();
+    for (kind, target) in a.difference(&b) {
+        by_type.entry(kind).or_default().0.push(target);
+    }
+    for (kind, target) in b.difference(&a) {
+        by_type.entry(kind).or_default().1.push(target);
+    }
+
+    for (kind, (removed, added)) in by_type {
+        if let ([from], [to]) = (removed.as_slice(), added.as_slice()) {
+            changes.push(FieldChange {
+                field: "relations".to_string(),
+                kind: ChangeKind::Retargeted,
+                from: Some(format!("{} {}", kind, from)),
+                to: Some(to.to_string()),
+                note: Some("retargeted".to_string()),
+            });
+            continue;
+        }
+        for target in removed {
+            changes.push(FieldChange { field: "relations".to_string(), kind: ChangeKind::Removed, from: Some(format!("{} {}", kind, target)), to: None, note: None });
+        }
+        for target in added {
+            changes.push(FieldChange { field: "relations".to_string(), kind: ChangeKind::Added, from: None, to: Some(format!("{} {}", kind, target)), note: None });
+        }
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::glyph;
+
+    #[test]
+    fn test_semantic_changes() {
+        let a = glyph("sun").aliases(&["star"]).confidence(0.95)
+            .relation("orbits", "glyph:earth").relation("emits", "glyph:light").build();
+        let b = glyph("sun").aliases(&["star", "sol"]).confidence(0.8)
+            .relation("orbits", "glyph:galaxy").relation("emits", "glyph:light").build();
+
+        let result = diff(&a, &b, None);
+        assert!(result.semantic_hash_changed); // aliases are part of the semantic hash
+        assert_eq!(result.signature_a, SignatureStatus::Unsigned);
+
+        assert!(result.changes.contains(&FieldChange {
+            field: "aliases".to_string(), kind: ChangeKind::Added, from: None, to: Some("sol".to_string()), note: None,
+        }));
+        let retarget = result.changes.iter().find(|c| c.field == "relations").unwrap();
+        assert_eq!(retarget.kind, ChangeKind::Retargeted);
+        assert_eq!(retarget.from.as_deref(), Some("orbits glyph:earth"));
+
+        let text = result.to_text();
+        assert!(text.contains("~ truth_mode.confidence: 0.95 → 0.8 (-0.15)"));
+        assert!(text.contains("Semantic hash: changed"));
+    }
+
+    #[test]
+    fn test_identical_glyphs() {
+        let a = glyph("sun").build();
+        let b = glyph("sun").build();
+        let result = diff(&a, &b, None);
+        assert!(result.is_empty());
+        assert!(!result.semantic_hash_changed);
+        assert!(result.to_text().starts_with("No semantic changes"));
+
+        // A copied header hash does not make a changed payload look unchanged
+        let mut c = glyph("moon").build();
+        c.header.hash = a.header.hash.clone();
+        let result = diff(&a, &c, None);
+        assert!(result.semantic_hash_changed);
+        assert_eq!(result.signature_b, SignatureStatus::HashMismatch);
+    }
+}
@@ -14,16 +14,21 @@ use ciborium::{into_writer, from_reader};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 use blake3::Hasher;
-use ed25519_dalek::{SigningKey, VerifyingKey, Signature, Signer};
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 use clap::Parser;
-use hex;
-use qrcode::QrCode;
+use qrcode::{QrCode, EcLevel, Color};
 use image::{Luma, ImageBuffer};
 use base64;
 
+use super::glyph_sign;
+use super::qr_chunk;
+use super::qr_compact;
+use super::svg_sanitize;
+use super::media::MediaAttachment;
+use super::lineage::Lineage;
+
 // ============================================================================
 // DATA STRUCTURES
 // ============================================================================
@@ -36,6 +41,8 @@ pub struct GlyphHeader {
     pub hash: String,
     pub signature: Option<String>,
     pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lineage: Option<Lineage>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -70,13 +77,19 @@ pub struct TruthMode {
 pub struct Audio {
     pub file: Option<String>,
     pub phonetic: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media: Option<MediaAttachment>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Visual {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
     pub svg: Option<String>,
     pub sigil: Option<String>,
     pub style: Option<VisualStyle>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media: Option<MediaAttachment>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -162,53 +175,174 @@ struct Args {
 // MAIN ENCODER LOGIC
 // ============================================================================
 
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum QrEcLevel {
+    /// ~7% of codewords can be restored
+    L,
+    /// ~15% of codewords can be restored
+    M,
+    /// ~25% of codewords can be restored
+    Q,
+    /// ~30% of codewords can be restored
+    H,
+}
+
+impl From<QrEcLevel> for EcLevel {
+    fn from(level: QrEcLevel) -> Self {
+        match level {
+            QrEcLevel::L => EcLevel::L,
+            QrEcLevel::M => EcLevel::M,
+            QrEcLevel::Q => EcLevel::Q,
+            QrEcLevel::H => EcLevel::H,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QrOptions {
+    /// Pixels (PNG) or user units (SVG) per module
+    pub scale: u32,
+    /// Light border around the symbol, in modules; scanners expect 4
+    pub quiet_zone: u32,
+    pub ec_level: QrEcLevel,
+    /// Render SVG instead of PNG
+    pub svg: bool,
+    /// Deflate + Base45 payload in alphanumeric mode instead of base64 bytes
+    pub compact: bool,
+}
+
+impl Default for QrOptions {
+    fn default() -> Self {
+        QrOptions {
+            scale: 8,
+            quiet_zone: 4,
+            ec_level: QrEcLevel::M,
+            svg: false,
+            compact: false,
+        }
+    }
+}
+
+/// Render a QR symbol as PNG or SVG bytes according to `options`
+pub fn render_qr(code: &QrCode, options: &QrOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if options.svg {
+        let size = qr_size(code, options)?;
+        return Ok(render_qr_svg(&code.to_colors(), code.width() as u32, size, options).into_bytes());
+    }
+
+    let image_buffer = render_qr_image(code, options)?;
+
+    // Convert to PNG bytes
+    let mut png_data = Vec::new();
+    image_buffer.write_to(&mut std::io::Cursor::new(&mut png_data), image::ImageFormat::Png)?;
+    Ok(png_data)
+}
+
+/// Rasterize a QR symbol with the module scale and quiet zone of `options`
+pub fn render_qr_image(code: &QrCode, options: &QrOptions) -> Result<ImageBuffer<Luma<u8>, Vec<u8>>, Box<dyn std::error::Error>> {
+    let modules = code.width() as u32;
+    let size = qr_size(code, options)?;
+
+    // Create image buffer, light everywhere so the quiet zone is white
+    let mut image_buffer = ImageBuffer::from_pixel(size, size, Luma([255u8]));
+
+    for (index, color) in code.to_colors().iter().enumerate() {
+        if *color != Color::Dark {
+            continue;
+        }
+        let x0 = (index as u32 % modules + options.quiet_zone) * options.scale;
+        let y0 = (index as u32 / modules + options.quiet_zone) * options.scale;
+        for y in y0..y0 + options.scale {
+            for x in x0..x0 + options.scale {
+                image_buffer.put_pixel(x, y, Luma([0u8]));
+            }
+        }
+    }
+
+    Ok(image_buffer)
+}
+
+/// Side of the rendered symbol, quiet zone included, in pixels or user units
+fn qr_size(code: &QrCode, options: &QrOptions) -> Result<u32, Box<dyn std::error::Error>> {
+    if options.scale == 0 {
+        return Err("QR module scale must be at least 1".into());
+    }
+    options.quiet_zone.checked_mul(2)
+        .and_then(|border| border.checked_add(code.width() as u32))
+        .and_then(|modules| modules.checked_mul(options.scale))
+        .filter(|size| size.checked_mul(*size).is_some())
+        .ok_or_else(|| "QR scale and quiet zone make the image too large".into())
+}
+
+fn render_qr_svg(colors: &[Color], modules: u32, size: u32, options: &QrOptions) -> String {
+    let mut path = String::new();
+    for (index, color) in colors.iter().enumerate() {
+        if *color == Color::Dark {
+            let x = (index as u32 % modules + options.quiet_zone) * options.scale;
+            let y = (index as u32 / modules + options.quiet_zone) * options.scale;
+            path.push_str(&format!("M{x} {y}h{s}v{s}h-{s}z", x = x, y = y, s = options.scale));
+        }
+    }
+
+    format!(
+        concat!(
+            "<?xml version=\"1.0\" standalone=\"yes\"?>",
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" version=\"1.1\" width=\"{size}\" height=\"{size}\" ",
+            "viewBox=\"0 0 {size} {size}\" shape-rendering=\"crispEdges\">",
+            "<rect x=\"0\" y=\"0\" width=\"{size}\" height=\"{size}\" fill=\"#fff\"/>",
+            "<path fill=\"#000\" d=\"{path}\"/></svg>"
+        ),
+        size = size,
+        path = path,
+    )
+}
+
 impl Glyph {
     pub fn to_qr_code(&self, output_path: Option<&str>) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        self.to_qr_code_with(&QrOptions::default(), output_path)
+    }
+
+    pub fn to_qr_code_with(&self, options: &QrOptions, output_path: Option<&str>) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
         // Encode glyph to CBOR
         let cbor_data = self.to_cbor()?;
         
-        // Convert CBOR to base64 for QR code (more reliable than raw bytes)
-        let base64_data = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &cbor_data);
-        
-        // Convert to QR code
-        let code = QrCode::new(base64_data.as_bytes())?;
-
-        // Use a simpler approach - render to string first, then convert to image
-        let qr_string = code.render()
-            .light_color(' ')
-            .dark_color('#')
-            .build();
-        
-        // Parse the string representation to create an image
-        let lines: Vec<&str> = qr_string.lines().collect();
-        let height = lines.len() as u32;
-        let width = if height > 0 { lines[0].len() as u32 } else { 0 };
-        
-        // Create image buffer
-        let mut image_buffer = ImageBuffer::new(width, height);
+        // Convert CBOR to text for the QR code (more reliable than raw bytes)
+        let payload = if options.compact {
+            qr_compact::encode(&cbor_data)?
+        } else {
+            base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &cbor_data)
+        };
         
-        // Fill the image based on the string representation
-        for (y, line) in lines.iter().enumerate() {
-            for (x, ch) in line.chars().enumerate() {
-                let pixel = if ch == '#' {
-                    Luma([0u8]) // Black
-                } else {
-                    Luma([255u8]) // White
-                };
-                image_buffer.put_pixel(x as u32, y as u32, pixel);
-            }
-        }
-
-        // Convert to PNG bytes
-        let mut png_data = Vec::new();
-        image_buffer.write_to(&mut std::io::Cursor::new(&mut png_data), image::ImageFormat::Png)?;
+        // Convert to QR code; compact payloads are all alphanumeric and get
+        // encoded in alphanumeric mode
+        let code = QrCode::with_error_correction_level(payload.as_bytes(), options.ec_level.into())?;
+        let qr_data = render_qr(&code, options)?;
 
         // Save to file if path provided
         if let Some(path) = output_path {
-            fs::write(path, &png_data)?;
+            fs::write(path, &qr_data)?;
         }
 
-        Ok(png_data)
+        Ok(qr_data)
+    }
+
+    /// Encode the glyph across as many QR codes as needed, each carrying at
+    /// most `chunk_size` payload characters. Returns one image per code.
+    pub fn to_qr_codes(&self, options: &QrOptions, chunk_size: usize) -> Result<Vec<Vec<u8>>, Box<dyn std::error::Error>> {
+        let cbor_data = self.to_cbor()?;
+
+        let payloads = if options.compact {
+            qr_chunk::split_compact(&cbor_data, chunk_size)?
+        } else {
+            qr_chunk::split(&cbor_data, chunk_size)?
+        };
+
+        payloads.iter()
+            .map(|payload| {
+                let code = QrCode::with_error_correction_level(payload.as_bytes(), options.ec_level.into())?;
+                render_qr(&code, options)
+            })
+            .collect()
     }
 
     pub fn from_json5(json5_content: &str) -> Result<Self, Box<dyn std::error::Error>> {
@@ -232,45 +366,69 @@ impl Glyph {
         Ok(GlyphHeader {
             magic: "GLYPH".to_string(),
             version: "0.1".to_string(),
-            id,
+            id: id.clone(),
             hash,
             signature: None, // Will be set later if signing
             created_at: Utc::now(),
+            lineage: Some(Lineage::root(&id)),
         })
     }
     
     fn extract_semantic_content(payload: &GlyphPayload) -> String {
-        // Extract core semantic fields for hashing
-        format!(
-            "{}|{}|{}|{}|{}",
-            payload.label,
-            payload.aliases.join(","),
-            payload.classes.join(","),
-            payload.truth_mode.r#type,
-            payload.grounding.wikidata.as_deref().unwrap_or("")
+        let media: Vec<&str> = [&payload.audio.media, &payload.visual.media].into_iter()
+            .flatten()
+            .map(|media| media.digest.as_str())
+            .collect();
+        Self::semantic_content(
+            &payload.label,
+            &payload.aliases,
+            &payload.classes,
+            &payload.truth_mode.r#type,
+            payload.grounding.wikidata.as_deref(),
+            &media,
         )
     }
     
-    fn compute_hash(content: &str) -> String {
+    /// Recompute the semantic hash, e.g. after attaching media
+    pub fn semantic_hash(&self) -> String {
+        Self::compute_hash(&Self::extract_semantic_content(&self.payload))
+    }
+    
+    /// Core semantic fields joined for hashing; shared with decoded glyphs so
+    /// both sides compute the same semantic hash
+    pub(crate) fn semantic_content(
+        label: &str,
+        aliases: &[String],
+        classes: &[String],
+        truth_type: &str,
+        wikidata: Option<&str>,
+        media: &[&str],
+    ) -> String {
+        let mut content = format!(
+            "{}|{}|{}|{}|{}",
+            label,
+            aliases.join(","),
+            classes.join(","),
+            truth_type,
+            wikidata.unwrap_or("")
+        );
+        // Attachment digests are covered so a swapped file and digest change
+        // the hash; glyphs without media hash as they always have
+        if !media.is_empty() {
+            content.push_str(&format!("|{}", media.join(",")));
+        }
+        content
+    }
+    
+    pub(crate) fn compute_hash(content: &str) -> String {
         let mut hasher = Hasher::new();
         hasher.update(content.as_bytes());
         format!("blake3:{}", hasher.finalize().to_hex())
     }
     
     pub fn sign(&mut self, private_key: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
-        // Convert private key to the expected format
-        if private_key.len() != 32 {
-            return Err("Private key must be exactly 32 bytes".into());
-        }
-        
-        let mut key_bytes = [0u8; 32];
-        key_bytes.copy_from_slice(private_key);
-        
-        let signing_key = SigningKey::from_bytes(&key_bytes);
-        let message = self.header.hash.as_bytes();
-        let signature = signing_key.sign(message);
-        
-        self.header.signature = Some(format!("ed25519:{}", hex::encode(signature.to_bytes())));
+        let signature = glyph_sign::sign_hash(private_key, &self.header.hash)?;
+        self.header.signature = Some(signature);
         Ok(())
     }
     
@@ -308,6 +466,20 @@ impl Glyph {
             }
         }
         
+        // Inline SVG must survive the sanitizer unchanged
+        if let Some(svg) = &self.payload.visual.svg {
+            errors.extend(svg_sanitize::validation_errors(svg));
+        }
+        
+        // Embedded media must match its recorded digest
+        for media in [&self.payload.audio.media, &self.payload.visual.media].into_iter().flatten() {
+            if media.is_embedded() {
+                if let Err(error) = media.verify(None) {
+                    errors.push(format!("Media attachment invalid: {}", error));
+                }
+            }
+        }
+        
         if errors.is_empty() {
             Ok(())
         } else {
@@ -441,4 +613,38 @@ mod tests {
         assert_eq!(glyph.header.magic, "GLYPH");
         assert_eq!(glyph.payload.label, "test");
     }
+    
+    #[test]
+    fn test_qr_options() {
+        let json5_content = r#"{
+            "label": "test", "aliases": [], "classes": [],
+            "namespace": { "provision": "glyph:test", "contextual_overlays": [] },
+            "truth_mode": { "type": "empirical", "confidence": 0.95, "verified_by": [], "conflicts": [] },
+            "audio": { "file": null, "phonetic": null },
+            "visual": { "svg": null, "sigil": null, "style": null },
+            "relations": [], "grounding": { "wikidata": null, "sensors": [] }
+        }"#;
+        let glyph = Glyph::from_json5(json5_content).unwrap();
+        
+        // QR output scales with the quiet zone and decodes back to the same glyph
+        let options = QrOptions { scale: 4, quiet_zone: 4, ec_level: QrEcLevel::Q, svg: false, compact: false };
+        let png = glyph.to_qr_code_with(&options, None).unwrap();
+        let image = image::load_from_memory(&png).unwrap();
+        assert_eq!(image.width() % 4, 0);
+        let decoded = crate::glyph_decode::Glyph::from_qr_code(&png).unwrap();
+        assert_eq!(decoded.header.hash, glyph.header.hash);
+        
+        let compact = glyph.to_qr_code_with(&QrOptions { compact: true, ..options.clone() }, None).unwrap();
+        let decoded = crate::glyph_decode::Glyph::from_qr_code(&compact).unwrap();
+        assert_eq!(decoded.header.hash, glyph.header.hash);
+        assert!(image::load_from_memory(&compact).unwrap().width() <= image.width());
+        
+        let svg = glyph.to_qr_code_with(&QrOptions { svg: true, ..options.clone() }, None).unwrap();
+        assert!(String::from_utf8(svg).unwrap().contains("<path fill=\"#000\""));
+        
+        // Sizes that do not fit are refused instead of wrapping around
+        for scale in [0, u32::MAX / 2, 70_000] {
+            assert!(glyph.to_qr_code_with(&QrOptions { scale, svg: true, ..options.clone() }, None).is_err());
+        }
+    }
 }
@@ -0,0 +1,292 @@
+// Three-way merge of glyph edits.
+//
+// Given a common ancestor and two edited versions, set-like fields merge
+// element-wise: an element survives unless one side removed it, and additions
+// from either side are kept. Scalar fields merge by rule: if only one side
+// changed a value that side wins, if both made the same change it is taken,
+// and if both changed it differently the field is contested.
+//
+//   sets:    aliases, classes, relations, verified_by, conflicts,
+//            contextual_overlays, sensors
+//   scalars: label, provision, origin, truth type, confidence, wikidata,
+//            audio, visual
+//
+// Contested scalars are reported with git-style conflict markers. They can be
+// settled in favour of one side; until then the merged glyph is not written.
+// The merged header hash is recomputed, and a signature that no longer covers
+// it is cleared. All three glyphs must be revisions of one concept, and the
+// result is recorded as the next revision after ours.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::str::FromStr;
+
+use chrono::Utc;
+
+use super::glyph_decode::{Glyph, GlyphHeader, GlyphPayload};
+use super::lineage::{self, Lineage};
+
+// ============================================================================
+// DATA STRUCTURES
+// ============================================================================
+
+const SET_FIELDS: &[&str] = &[
+    "/aliases",
+    "/classes",
+    "/relations",
+    "/truth_mode/verified_by",
+    "/truth_mode/conflicts",
+    "/namespace/contextual_overlays",
+    "/grounding/sensors",
+];
+
+const SCALAR_FIELDS: &[&str] = &[
+    "/label",
+    "/namespace/provision",
+    "/origin",
+    "/truth_mode/type",
+    "/truth_mode/confidence",
+    "/grounding/wikidata",
+    "/audio",
+    "/visual",
+];
+
+/// Which side wins contested scalars
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Favor {
+    Ours,
+    Theirs,
+}
+
+impl FromStr for Favor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ours" => Ok(Favor::Ours),
+            "theirs" => Ok(Favor::Theirs),
+            _ => Err(format!("Unsupported side: {} (expected ours or theirs)", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Conflict {
+    pub field: String,
+    pub base: Value,
+    pub ours: Value,
+    pub theirs: Value,
+}
+
+#[derive(Debug)]
+pub struct MergeResult {
+    pub glyph: Glyph,
+    pub conflicts: Vec<Conflict>,
+    /// Whether conflicts were settled by a `Favor`
+    pub resolved: bool,
+    /// Whether a signature was dropped because the hash changed
+    pub signature_cleared: bool,
+}
+
+// ============================================================================
+// MERGING
+// ============================================================================
+
+/// Merge `ours` and `theirs` against their common ancestor `base`.
+///
+/// Without `favor`, contested scalars keep our value and are listed in
+/// `conflicts`; the caller decides whether to accept that.
+pub fn merge(base: &Glyph, ours: &Glyph, theirs: &Glyph, favor: Option<Favor>) -> Result<MergeResult, Box<dyn std::error::Error>> {
+    let concept = lineage::concept_of(ours);
+    for (side, glyph) in [("Base", base), ("Theirs", theirs)] {
+        if lineage::concept_of(glyph) != concept {
+            return Err(format!(
+                "{} ({}) is not a revision of concept {}", side, lineage::concept_of(glyph), concept
+            ).into());
+        }
+    }
+
+    let base_payload = serde_json::to_value(&base.payload)?;
+    let ours_payload = serde_json::to_value(&ours.payload)?;
+    let theirs_payload = serde_json::to_value(&theirs.payload)?;
+
+    let mut merged = ours_payload.clone();
+    let mut conflicts = Vec::new();
+
+    for &pointer in SET_FIELDS {
+        let items = |payload: &Value| payload.pointer(pointer)
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+        let value = Value::Array(merge_set(&items(&base_payload), &items(&ours_payload), &items(&theirs_payload)));
+        set(&mut merged, pointer, value);
+    }
+
+    for &pointer in SCALAR_FIELDS {
+        let field = |payload: &Value| payload.pointer(pointer).cloned().unwrap_or(Value::Null);
+        let (b, o, t) = (field(&base_payload), field(&ours_payload), field(&theirs_payload));
+
+        let value = if o == t || t == b {
+            o
+        } else if o == b {
+            t
+        } else {
+            conflicts.push(Conflict {
+                field: pointer[1..].replace('/', "."),
+                base: b,
+                ours: o.clone(),
+                theirs: t.clone(),
+            });
+            match favor {
+                Some(Favor::Theirs) => t,
+                _ => o,
+            }
+        };
+        set(&mut merged, pointer, value);
+    }
+
+    let payload: GlyphPayload = serde_json::from_value(merged)?;
+    let mut glyph = Glyph {
+        header: GlyphHeader {
+            magic: ours.header.magic.clone(),
+            version: ours.header.version.clone(),
+            id: ours.header.id.clone(),
+            hash: String::new(),
+            signature: None,
+            created_at: Utc::now(),
+            lineage: Some(merged_lineage(ours, theirs)),
+        },
+        payload,
+    };
+    glyph.header.hash = glyph.semantic_hash();
+
+    // Our signature still holds if the merge left the semantic hash alone,
+    // and only if the hash it signs was ours to begin with
+    let signature_holds = glyph.header.hash == ours.header.hash && ours.header.hash == ours.semantic_hash();
+    let signature_cleared = ours.header.signature.is_some() && !signature_holds;
+    if !signature_cleared {
+        glyph.header.signature = ours.header.signature.clone();
+    }
+
+    Ok(MergeResult {
+        glyph,
+        resolved: favor.is_some(),
+        conflicts,
+        signature_cleared,
+    })
+}
+
+// The merge follows our revision and outranks both sides
+fn merged_lineage(ours: &Glyph, theirs: &Glyph) -> Lineage {
+    let mut lineage = Lineage::next(ours);
+    if let Some(their_lineage) = &theirs.header.lineage {
+        lineage.revision = lineage.revision.max(their_lineage.revision + 1);
+    }
+    lineage
+}
+
+// Keep our order, then append their additions
+fn merge_set(base: &[Value], ours: &[Value], theirs: &[Value]) -> Vec<Value> {
+    let mut merged: Vec<Value> = ours.iter()
+        .filter(|item| !base.contains(item) || theirs.contains(item))
+        .cloned()
+        .collect();
+    for item in theirs {
+        if !base.contains(item) && !merged.contains(item) {
+            merged.push(item.clone());
+        }
+    }
+    merged
+}
+
+fn set(payload: &mut Value, pointer: &str, value: Value) {
+    let (parent, key) = pointer.rsplit_once('/').unwrap();
+    if let Some(Value::Object(object)) = payload.pointer_mut(parent) {
+        object.insert(key.to_string(), value);
+    }
+}
+
+impl MergeResult {
+    pub fn has_conflicts(&self) -> bool {
+        !self.conflicts.is_empty()
+    }
+
+    /// Contested scalars as git-style conflict blocks
+    pub fn conflict_markers(&self) -> String {
+        let mut text = String::new();
+        for conflict in &self.conflicts {
+            text.push_str(&format!(
+                "<<<<<<< ours\n{field}: {}\n||||||| base\n{field}: {}\n=======\n{field}: {}\n>>>>>>> theirs\n",
+                conflict.ours,
+                conflict.base,
+                conflict.theirs,
+                field = conflict.field,
+            ));
+        }
+        text
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{glyph, GlyphBuilder};
+
+    // An edit of `base`, recorded as its next revision
+    fn edit(base: &Glyph, edited: GlyphBuilder) -> Glyph {
+        let mut glyph = edited.build();
+        glyph.header.lineage = Some(Lineage::next(base));
+        glyph
+    }
+
+    #[test]
+    fn test_clean_merge() {
+        let base = glyph("sun").aliases(&["star", "sol"]).verified_by(&["nasa"]).build();
+        // Ours drops an alias and raises confidence; theirs adds an alias and a verifier
+        let ours = edit(&base, glyph("sun").aliases(&["star"]).confidence(0.95).verified_by(&["nasa"]));
+        let theirs = edit(&base, glyph("sun").aliases(&["star", "sol", "helios"]).verified_by(&["nasa", "esa"]));
+
+        let result = merge(&base, &ours, &theirs, None).unwrap();
+        assert!(!result.has_conflicts());
+        assert_eq!(result.glyph.payload.aliases, vec!["star", "helios"]);
+        assert_eq!(result.glyph.payload.truth_mode.verified_by, vec!["nasa", "esa"]);
+        assert_eq!(result.glyph.payload.truth_mode.confidence, 0.95);
+        assert_eq!(result.glyph.header.hash, result.glyph.semantic_hash());
+
+        // Glyphs of another concept are not edits of this one
+        let stranger = glyph("sun").build();
+        assert!(merge(&base, &ours, &stranger, None).unwrap_err().to_string().contains("not a revision"));
+    }
+
+    #[test]
+    fn test_contested_scalars() {
+        let base = glyph("sun").build();
+        let mut ours = edit(&base, glyph("sol").confidence(0.8));
+        let theirs = edit(&base, glyph("helios").confidence(0.7));
+        ours.header.signature = Some("signed".to_string());
+
+        let result = merge(&base, &ours, &theirs, None).unwrap();
+        let fields: Vec<&str> = result.conflicts.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(fields, vec!["label", "truth_mode.confidence"]);
+        assert!(result.conflict_markers().contains("<<<<<<< ours\nlabel: \"sol\"\n||||||| base\nlabel: \"sun\"\n=======\nlabel: \"helios\"\n>>>>>>> theirs\n"));
+        // Contested fields keep our value, so our signature still covers the hash
+        assert_eq!(result.glyph.header.hash, ours.header.hash);
+        assert!(result.glyph.header.signature.is_some());
+
+        let result = merge(&base, &ours, &theirs, Some(Favor::Theirs)).unwrap();
+        assert!(result.resolved);
+        assert_eq!(result.glyph.payload.label, "helios");
+        assert!(result.signature_cleared);
+        assert!(result.glyph.header.signature.is_none());
+
+        // A signature over a stored hash that does not match our payload is not carried over
+        ours.header.hash = base.header.hash.clone();
+        let result = merge(&base, &ours, &base, None).unwrap();
+        assert!(result.signature_cleared);
+        assert!(result.glyph.header.signature.is_none());
+    }
+}
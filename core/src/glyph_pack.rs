@@ -0,0 +1,428 @@
+// .glyphpack archives: a corpus of glyphs, their companions, media and public
+// keys in one file, described by a signed manifest.
+//
+//   "GLYPHPACK" | version: u8 | manifest length: u32 BE | manifest (CBOR) | data
+//
+// The manifest lists every file with its archive path, kind, blake3 digest,
+// size and offset into the data region. The manifest hash covers the entry
+// list, so signing it seals the whole pack: a verified pack has exactly the
+// listed files, byte for byte. Referenced (non-embedded) glyph media must be
+// packed as well for the pack to count as complete.
+
+use serde::{Deserialize, Serialize};
+use ciborium::{into_writer, from_reader};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use super::glyph_decode::Glyph;
+use super::glyph_encode;
+use super::glyph_sign;
+use super::media;
+
+pub const PACK_MAGIC: &[u8; 9] = b"GLYPHPACK";
+pub const PACK_VERSION: u8 = 1;
+
+// Upper bound on the manifest, so a crafted header cannot exhaust memory
+const MAX_MANIFEST_LEN: u32 = 16 * 1024 * 1024;
+
+// ============================================================================
+// DATA STRUCTURES
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackHeader {
+    pub magic: String,
+    pub version: String,
+    pub id: String,
+    pub hash: String,
+    pub signature: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackEntry {
+    pub path: String,
+    /// glyph, companion, media or key
+    pub kind: String,
+    pub digest: String,
+    pub size: u64,
+    pub offset: u64,
+    /// Semantic hash of glyph entries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub glyph_hash: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackManifest {
+    pub header: PackHeader,
+    pub entries: Vec<PackEntry>,
+}
+
+#[derive(Debug)]
+pub struct GlyphPack {
+    pub manifest: PackManifest,
+    data: Vec<u8>,
+}
+
+fn entry_kind(path: &str) -> &'static str {
+    if path.ends_with(".glyph") {
+        "glyph"
+    } else if path.ends_with(".glyph.json") {
+        "companion"
+    } else if path.ends_with(".pub") {
+        "key"
+    } else {
+        "media"
+    }
+}
+
+// Archive paths are relative, '/'-separated and never leave the extract root
+fn check_archive_path(path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let valid = !path.is_empty()
+        && !path.contains('\\')
+        && Path::new(path).components().all(|c| matches!(c, Component::Normal(_)));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid archive path: {}", path).into())
+    }
+}
+
+// ============================================================================
+// BUILDING
+// ============================================================================
+
+#[derive(Debug, Default)]
+pub struct PackBuilder {
+    entries: Vec<PackEntry>,
+    data: Vec<u8>,
+    paths: HashSet<String>,
+}
+
+impl PackBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.paths.contains(path)
+    }
+
+    pub fn add(&mut self, path: &str, content: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        check_archive_path(path)?;
+        if !self.paths.insert(path.to_string()) {
+            return Err(format!("Duplicate archive path: {}", path).into());
+        }
+
+        let kind = entry_kind(path);
+        let glyph_hash = if kind == "glyph" {
+            let glyph = Glyph::from_cbor(content).map_err(|e| format!("{}: {}", path, e))?;
+            Some(glyph.header.hash)
+        } else {
+            None
+        };
+
+        self.entries.push(PackEntry {
+            path: path.to_string(),
+            kind: kind.to_string(),
+            digest: media::digest(content),
+            size: content.len() as u64,
+            offset: self.data.len() as u64,
+            glyph_hash,
+        });
+        self.data.extend_from_slice(content);
+        Ok(())
+    }
+
+    /// Referenced media of the packed glyphs that is not packed yet, as
+    /// (media name, archive path of the referencing glyph)
+    pub fn missing_media(&self) -> Vec<(String, String)> {
+        missing_media(&self.entries, |entry| self.entry_bytes(entry))
+    }
+
+    fn entry_bytes(&self, entry: &PackEntry) -> &[u8] {
+        &self.data[entry.offset as usize..(entry.offset + entry.size) as usize]
+    }
+
+    pub fn finish(self, private_key: Option<&[u8]>) -> Result<GlyphPack, Box<dyn std::error::Error>> {
+        let mut header = PackHeader {
+            magic: "GLYPHPACK".to_string(),
+            version: "0.1".to_string(),
+            id: Uuid::new_v4().to_string(),
+            hash: compute_manifest_hash(&self.entries),
+            signature: None,
+            created_at: Utc::now(),
+        };
+        if let Some(private_key) = private_key {
+            header.signature = Some(glyph_sign::sign_hash(private_key, &header.hash)?);
+        }
+
+        Ok(GlyphPack {
+            manifest: PackManifest { header, entries: self.entries },
+            data: self.data,
+        })
+    }
+}
+
+fn compute_manifest_hash(entries: &[PackEntry]) -> String {
+    let lines: Vec<String> = entries.iter()
+        .map(|e| format!("{}|{}|{}|{}|{}", e.path, e.kind, e.digest, e.size, e.offset))
+        .collect();
+    glyph_encode::Glyph::compute_hash(&lines.join("\n"))
+}
+
+fn missing_media<'a, F>(entries: &'a [PackEntry], bytes: F) -> Vec<(String, String)>
+where
+    F: Fn(&'a PackEntry) -> &'a [u8],
+{
+    let packed: HashSet<(&str, &str)> = entries.iter()
+        .filter(|e| e.kind == "media")
+        .map(|e| (Path::new(&e.path).file_name().and_then(|n| n.to_str()).unwrap_or(""), e.digest.as_str()))
+        .collect();
+
+    let mut missing = Vec::new();
+    for entry in entries.iter().filter(|e| e.kind == "glyph") {
+        let Ok(glyph) = Glyph::from_cbor(bytes(entry)) else { continue };
+        for attachment in [&glyph.payload.audio.media, &glyph.payload.visual.media].into_iter().flatten() {
+            if !attachment.is_embedded() && !packed.contains(&(attachment.name.as_str(), attachment.digest.as_str())) {
+                missing.push((attachment.name.clone(), entry.path.clone()));
+            }
+        }
+    }
+    missing
+}
+
+// ============================================================================
+// READING AND WRITING
+// ============================================================================
+
+impl GlyphPack {
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), Box<dyn std::error::Error>> {
+        let mut manifest = Vec::new();
+        into_writer(&self.manifest, &mut manifest)?;
+
+        writer.write_all(PACK_MAGIC)?;
+        writer.write_all(&[PACK_VERSION])?;
+        writer.write_all(&(manifest.len() as u32).to_be_bytes())?;
+        writer.write_all(&manifest)?;
+        writer.write_all(&self.data)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut magic = [0u8; 9];
+        reader.read_exact(&mut magic).map_err(|_| "Not a glyph pack")?;
+        if &magic != PACK_MAGIC {
+            return Err("Not a glyph pack".into());
+        }
+
+        let mut version = [0u8; 1];
+        reader.read_exact(&mut version)?;
+        if version[0] != PACK_VERSION {
+            return Err(format!("Unsupported glyph pack version: {}", version[0]).into());
+        }
+
+        let mut length = [0u8; 4];
+        reader.read_exact(&mut length)?;
+        let length = u32::from_be_bytes(length);
+        if length > MAX_MANIFEST_LEN {
+            return Err("Glyph pack manifest exceeds the size limit".into());
+        }
+
+        let mut manifest = vec![0u8; length as usize];
+        reader.read_exact(&mut manifest)?;
+        let manifest: PackManifest = from_reader(manifest.as_slice())?;
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        Ok(GlyphPack { manifest, data })
+    }
+
+    /// Bytes of an entry, if its range lies inside the data region
+    pub fn entry_data(&self, entry: &PackEntry) -> Option<&[u8]> {
+        let start = usize::try_from(entry.offset).ok()?;
+        let end = start.checked_add(usize::try_from(entry.size).ok()?)?;
+        self.data.get(start..end)
+    }
+
+    pub fn verify_signature(&self, public_key: &[u8]) -> Result<bool, Box<dyn std::error::Error>> {
+        if let Some(signature_str) = &self.manifest.header.signature {
+            glyph_sign::verify_hash(public_key, &self.manifest.header.hash, signature_str)
+        } else {
+            Ok(false) // No signature to verify
+        }
+    }
+
+    // ========================================================================
+    // VERIFICATION
+    // ========================================================================
+
+    /// Check that the manifest is intact, every entry matches its digest, the
+    /// data region holds nothing unlisted and referenced media is packed
+    pub fn verify(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        let header = &self.manifest.header;
+
+        if header.magic != "GLYPHPACK" {
+            errors.push("Invalid magic".to_string());
+        }
+        if compute_manifest_hash(&self.manifest.entries) != header.hash {
+            errors.push("Manifest hash does not match its entries".to_string());
+        }
+
+        let mut paths = HashSet::new();
+        let mut ranges = Vec::new();
+        for entry in &self.manifest.entries {
+            if let Err(e) = check_archive_path(&entry.path) {
+                errors.push(e.to_string());
+            }
+            if !paths.insert(entry.path.as_str()) {
+                errors.push(format!("Duplicate entry: {}", entry.path));
+            }
+
+            let Some(content) = self.entry_data(entry) else {
+                errors.push(format!("{}: missing from the data region", entry.path));
+                continue;
+            };
+            ranges.push((entry.offset, entry.offset + entry.size, entry.path.as_str()));
+
+            if media::digest(content) != entry.digest {
+                errors.push(format!("{}: digest mismatch", entry.path));
+            } else if entry.kind == "glyph" {
+                match Glyph::from_cbor(content) {
+                    Ok(glyph) if Some(&glyph.header.hash) != entry.glyph_hash.as_ref() => {
+                        errors.push(format!("{}: glyph hash does not match the manifest", entry.path));
+                    }
+                    Ok(_) => {}
+                    Err(e) => errors.push(format!("{}: {}", entry.path, e)),
+                }
+            }
+        }
+
+        // Entries must tile the data region: no gaps, no overlaps, nothing after
+        ranges.sort();
+        let mut covered = 0u64;
+        for (start, end, path) in ranges {
+            if start > covered {
+                errors.push(format!("Unlisted bytes {}..{} before {}", covered, start, path));
+            } else if start < covered {
+                errors.push(format!("{}: overlaps the previous entry at offset {}", path, start));
+            }
+            covered = covered.max(end);
+        }
+        if covered != self.data.len() as u64 {
+            errors.push(format!(
+                "Data region holds {} bytes but the manifest lists {}",
+                self.data.len(), covered
+            ));
+        }
+
+        for (name, glyph) in missing_media(&self.manifest.entries, |entry| self.entry_data(entry).unwrap_or(&[])) {
+            errors.push(format!("Referenced media not packed: {} (referenced by {})", name, glyph));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Write every entry under `dir`, keeping archive paths
+    pub fn extract(&self, dir: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+        let mut written = Vec::new();
+        for entry in &self.manifest.entries {
+            check_archive_path(&entry.path)?;
+            let content = self.entry_data(entry)
+                .ok_or_else(|| format!("{}: missing from the data region", entry.path))?;
+
+            let path = dir.join(&entry.path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, content)?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_glyph() -> Vec<u8> {
+        let json5_content = r#"{
+            "label": "sun", "aliases": [], "classes": ["astronomy"],
+            "namespace": { "provision": "glyph:sun", "contextual_overlays": [] },
+            "truth_mode": { "type": "empirical", "confidence": 0.9, "verified_by": [], "conflicts": [] },
+            "audio": { "file": "sun.wav", "phonetic": null },
+            "visual": { "svg": null, "sigil": null, "style": null },
+            "relations": [], "grounding": { "wikidata": null, "sensors": [] }
+        }"#;
+        let mut glyph = glyph_encode::Glyph::from_json5(json5_content).unwrap();
+        glyph.payload.audio.media = Some(media::MediaAttachment::new("sun.wav", b"wave".to_vec(), media::MediaMode::Reference));
+        glyph.to_cbor().unwrap()
+    }
+
+    #[test]
+    fn test_pack_roundtrip_and_signature() {
+        let key = [7u8; 32];
+        let public_key = ed25519_dalek::SigningKey::from_bytes(&key).verifying_key().to_bytes();
+
+        let mut builder = PackBuilder::new();
+        builder.add("glyphs/sun.glyph", &sample_glyph()).unwrap();
+        assert_eq!(builder.missing_media().len(), 1);
+        builder.add("media/sun.wav", b"wave").unwrap();
+        assert!(builder.missing_media().is_empty());
+        assert!(builder.add("media/sun.wav", b"again").is_err());
+        assert!(builder.add("../escape", b"x").is_err());
+
+        let mut bytes = Vec::new();
+        builder.finish(Some(&key)).unwrap().write_to(&mut bytes).unwrap();
+
+        let pack = GlyphPack::read_from(bytes.as_slice()).unwrap();
+        assert!(pack.verify().is_ok());
+        assert!(pack.verify_signature(&public_key).unwrap());
+        assert_eq!(pack.manifest.entries[1].kind, "media");
+    }
+
+    #[test]
+    fn test_pack_detects_tampering() {
+        let mut builder = PackBuilder::new();
+        builder.add("sun.glyph", &sample_glyph()).unwrap();
+        builder.add("sun.wav", b"wave").unwrap();
+        let mut bytes = Vec::new();
+        builder.finish(None).unwrap().write_to(&mut bytes).unwrap();
+
+        // Swap the media bytes
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        let errors = GlyphPack::read_from(bytes.as_slice()).unwrap().verify().unwrap_err();
+        assert!(errors.iter().any(|e| e == "sun.wav: digest mismatch"));
+
+        // Smuggle in unlisted bytes
+        bytes[last] ^= 1;
+        bytes.extend_from_slice(b"extra");
+        let errors = GlyphPack::read_from(bytes.as_slice()).unwrap().verify().unwrap_err();
+        assert!(errors[0].contains("manifest lists"));
+
+        // Overlapping entries whose sizes still add up to the data length
+        bytes.truncate(bytes.len() - 5);
+        let mut pack = GlyphPack::read_from(bytes.as_slice()).unwrap();
+        pack.manifest.entries[1].offset = 0;
+        let errors = pack.verify().unwrap_err();
+        assert!(errors.iter().any(|e| e.ends_with("overlaps the previous entry at offset 0")));
+        assert!(errors.iter().any(|e| e.contains("manifest lists")));
+    }
+}
@@ -0,0 +1,258 @@
+// Glyph references and their resolution against a store.
+//
+// Relations, namespaces, overlays and timevector entries point at other
+// glyphs with `glyph:` URIs:
+//
+//   glyph:moon                          label (or alias) in any namespace
+//   glyph:core/moon                     label in the namespace provided as glyph:core
+//   glyph:6f1c...-...                   header id or lineage concept id
+//   glyph:moon@blake3:<64 hex>          label pinned to a semantic hash
+//
+// Resolving a reference against a set of glyphs collapses the revisions of
+// one concept to the latest, so a reference is only ambiguous when it matches
+// different concepts. Hash-pinned references select the revision with that
+// hash instead.
+
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+use super::glyph_decode::Glyph;
+use super::lineage;
+
+// ============================================================================
+// DATA STRUCTURES
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GlyphRef {
+    Id(String),
+    Label {
+        namespace: Option<String>,
+        label: String,
+        hash: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+pub enum ResolveError {
+    NotFound(String),
+    /// Ids and labels of the competing concepts
+    Ambiguous(Vec<String>),
+    /// The label exists, but no revision has the pinned hash
+    HashMismatch { hash: String, found: Vec<String> },
+}
+
+// ============================================================================
+// PARSING
+// ============================================================================
+
+impl FromStr for GlyphRef {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let body = s.strip_prefix("glyph:")
+            .ok_or_else(|| format!("Not a glyph reference: {}", s))?;
+        if body.is_empty() {
+            return Err(format!("Empty glyph reference: {}", s));
+        }
+
+        if uuid::Uuid::parse_str(body).is_ok() {
+            return Ok(GlyphRef::Id(body.to_lowercase()));
+        }
+
+        let (path, hash) = match body.split_once('@') {
+            Some((path, hash)) => {
+                let hex = hash.strip_prefix("blake3:")
+                    .ok_or_else(|| format!("Unsupported hash in {}: expected blake3:<hex>", s))?;
+                if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(format!("Invalid blake3 hash in {}: expected 64 hex digits", s));
+                }
+                (path, Some(format!("blake3:{}", hex.to_lowercase())))
+            }
+            None => (body, None),
+        };
+
+        let (namespace, label) = match path.rsplit_once('/') {
+            Some((namespace, label)) => (Some(namespace.to_string()), label),
+            None => (None, path),
+        };
+        if label.is_empty() || namespace.as_deref() == Some("") {
+            return Err(format!("Invalid glyph reference: {}", s));
+        }
+
+        Ok(GlyphRef::Label { namespace, label: label.to_string(), hash })
+    }
+}
+
+impl fmt::Display for GlyphRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GlyphRef::Id(id) => write!(f, "glyph:{}", id),
+            GlyphRef::Label { namespace, label, hash } => {
+                write!(f, "glyph:")?;
+                if let Some(namespace) = namespace {
+                    write!(f, "{}/", namespace)?;
+                }
+                write!(f, "{}", label)?;
+                if let Some(hash) = hash {
+                    write!(f, "@{}", hash)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResolveError::NotFound(reference) => write!(f, "no glyph matches {}", reference),
+            ResolveError::Ambiguous(candidates) => write!(f, "ambiguous, matches {}", candidates.join(", ")),
+            ResolveError::HashMismatch { hash, found } => {
+                write!(f, "no revision has {} (found {})", hash, found.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+// ============================================================================
+// RESOLUTION
+// ============================================================================
+
+/// Namespace a glyph is provided under: its provision without the scheme
+fn namespace_of(glyph: &Glyph) -> &str {
+    let provision = &glyph.payload.namespace.provision;
+    provision.strip_prefix("glyph:").unwrap_or(provision)
+}
+
+pub struct Resolver<'a> {
+    glyphs: &'a [Glyph],
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(glyphs: &'a [Glyph]) -> Self {
+        Resolver { glyphs }
+    }
+
+    pub fn resolve_str(&self, reference: &str) -> Result<&'a Glyph, Box<dyn std::error::Error>> {
+        let reference: GlyphRef = reference.parse()?;
+        Ok(self.resolve(&reference)?)
+    }
+
+    pub fn resolve(&self, reference: &GlyphRef) -> Result<&'a Glyph, ResolveError> {
+        let candidates: Vec<&Glyph> = match reference {
+            GlyphRef::Id(id) => self.glyphs.iter()
+                .filter(|glyph| glyph.header.id == *id || lineage::concept_of(glyph) == id)
+                .collect(),
+            GlyphRef::Label { namespace, label, hash } => {
+                let in_namespace = |glyph: &&Glyph| namespace.as_deref().is_none_or(|ns| namespace_of(glyph) == ns);
+                let mut candidates: Vec<&Glyph> = self.glyphs.iter()
+                    .filter(in_namespace)
+                    .filter(|glyph| glyph.payload.label == *label)
+                    .collect();
+                // Aliases only count when no label matches
+                if candidates.is_empty() {
+                    candidates = self.glyphs.iter()
+                        .filter(in_namespace)
+                        .filter(|glyph| glyph.payload.aliases.contains(label))
+                        .collect();
+                }
+
+                // Pins match the recomputed hash, so a forged header cannot claim one
+                if let Some(hash) = hash {
+                    let pinned: Vec<&Glyph> = candidates.iter().copied().filter(|glyph| glyph.semantic_hash() == *hash).collect();
+                    if pinned.is_empty() && !candidates.is_empty() {
+                        let mut found: Vec<String> = candidates.iter().map(|glyph| glyph.semantic_hash()).collect();
+                        found.sort();
+                        found.dedup();
+                        return Err(ResolveError::HashMismatch { hash: hash.clone(), found });
+                    }
+                    candidates = pinned;
+                }
+                candidates
+            }
+        };
+
+        // Latest revision of each concept
+        let mut latest: Vec<&Glyph> = Vec::new();
+        for glyph in candidates {
+            match latest.iter_mut().find(|other| lineage::concept_of(other) == lineage::concept_of(glyph)) {
+                Some(other) => {
+                    if lineage::revision_of(glyph) > lineage::revision_of(other) {
+                        *other = glyph;
+                    }
+                }
+                None => latest.push(glyph),
+            }
+        }
+
+        match latest.as_slice() {
+            [] => Err(ResolveError::NotFound(reference.to_string())),
+            [glyph] => Ok(glyph),
+            _ => Err(ResolveError::Ambiguous(
+                latest.iter()
+                    .map(|glyph| format!("{} ({}, {})", glyph.payload.label, namespace_of(glyph), glyph.header.id))
+                    .collect(),
+            )),
+        }
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::glyph;
+
+    #[test]
+    fn test_parse_forms() {
+        let hash = format!("blake3:{}", "ab".repeat(32));
+        assert_eq!("glyph:moon".parse::<GlyphRef>().unwrap(), GlyphRef::Label { namespace: None, label: "moon".into(), hash: None });
+        assert_eq!(
+            "glyph:core/moon".parse::<GlyphRef>().unwrap(),
+            GlyphRef::Label { namespace: Some("core".into()), label: "moon".into(), hash: None }
+        );
+        let pinned: GlyphRef = format!("glyph:moon@{}", hash).parse().unwrap();
+        assert_eq!(pinned, GlyphRef::Label { namespace: None, label: "moon".into(), hash: Some(hash.clone()) });
+        assert_eq!(pinned.to_string(), format!("glyph:moon@{}", hash));
+        assert!(matches!("glyph:5ae6c41f-13cc-4c4f-930f-f0b14c3fae57".parse(), Ok(GlyphRef::Id(_))));
+
+        assert!("agent:NASA".parse::<GlyphRef>().is_err());
+        assert!("glyph:moon@blake3:xyz".parse::<GlyphRef>().is_err());
+        assert!("glyph:/moon".parse::<GlyphRef>().is_err());
+    }
+
+    #[test]
+    fn test_resolution() {
+        let store = vec![
+            glyph("moon").aliases(&["luna"]).build(),
+            glyph("moon").provision("glyph:myth").build(),
+            glyph("sun").build(),
+        ];
+        let resolver = Resolver::new(&store);
+
+        assert!(matches!(resolver.resolve_str("glyph:moon").unwrap_err().downcast_ref(), Some(ResolveError::Ambiguous(c)) if c.len() == 2));
+        assert_eq!(namespace_of(resolver.resolve_str("glyph:myth/moon").unwrap()), "myth");
+        assert_eq!(resolver.resolve_str("glyph:luna").unwrap().payload.label, "moon");
+        assert_eq!(resolver.resolve_str(&format!("glyph:{}", store[2].header.id)).unwrap().payload.label, "sun");
+        assert_eq!(resolver.resolve_str(&format!("glyph:sun@{}", store[2].header.hash)).unwrap().header.id, store[2].header.id);
+
+        assert!(matches!(resolver.resolve_str("glyph:mars").unwrap_err().downcast_ref(), Some(ResolveError::NotFound(_))));
+        let wrong = format!("glyph:sun@blake3:{}", "00".repeat(32));
+        assert!(matches!(resolver.resolve_str(&wrong).unwrap_err().downcast_ref(), Some(ResolveError::HashMismatch { .. })));
+
+        // A glyph claiming the pinned hash without the matching payload is not the pinned one
+        let mut forged = glyph("sun").aliases(&["helios"]).build();
+        forged.header.hash = store[2].header.hash.clone();
+        let store = vec![forged];
+        let pin = format!("glyph:sun@{}", store[0].header.hash);
+        assert!(matches!(Resolver::new(&store).resolve_str(&pin).unwrap_err().downcast_ref(), Some(ResolveError::HashMismatch { .. })));
+    }
+}
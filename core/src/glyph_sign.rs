@@ -0,0 +1,71 @@
+// Ed25519 signing shared by every signed GlyphOS artifact (glyphs, timevectors).
+//
+// Signatures are always computed over the textual content hash
+// ("blake3:<hex>") and serialized as "ed25519:<hex>".
+
+use ed25519_dalek::{SigningKey, VerifyingKey, Signature, Signer, Verifier};
+use hex;
+
+/// Sign a content hash with a raw 32-byte Ed25519 private key
+pub fn sign_hash(private_key: &[u8], hash: &str) -> Result<String, Box<dyn std::error::Error>> {
+    // Convert private key to the expected format
+    if private_key.len() != 32 {
+        return Err("Private key must be exactly 32 bytes".into());
+    }
+
+    let mut key_bytes = [0u8; 32];
+    key_bytes.copy_from_slice(private_key);
+
+    let signing_key = SigningKey::from_bytes(&key_bytes);
+    let signature = signing_key.sign(hash.as_bytes());
+
+    Ok(format!("ed25519:{}", hex::encode(signature.to_bytes())))
+}
+
+/// Verify an "ed25519:<hex>" signature over a content hash
+pub fn verify_hash(public_key: &[u8], hash: &str, signature: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    // Parse signature
+    let signature_hex = signature.strip_prefix("ed25519:")
+        .ok_or("Invalid signature format")?;
+    let signature_bytes = hex::decode(signature_hex)?;
+
+    // Convert signature bytes to the expected format
+    if signature_bytes.len() != 64 {
+        return Err("Invalid signature length".into());
+    }
+    let mut sig_array = [0u8; 64];
+    sig_array.copy_from_slice(&signature_bytes);
+    let signature = Signature::from_bytes(&sig_array);
+
+    // Convert public key to the expected format
+    if public_key.len() != 32 {
+        return Err("Invalid public key length".into());
+    }
+    let mut key_array = [0u8; 32];
+    key_array.copy_from_slice(public_key);
+    let verifying_key = VerifyingKey::from_bytes(&key_array)?;
+
+    // Verify
+    Ok(verifying_key.verify(hash.as_bytes(), &signature).is_ok())
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify_hash() {
+        let private_key = [7u8; 32];
+        let public_key = SigningKey::from_bytes(&private_key).verifying_key().to_bytes();
+
+        let signature = sign_hash(&private_key, "blake3:abc").unwrap();
+        assert!(signature.starts_with("ed25519:"));
+        assert!(verify_hash(&public_key, "blake3:abc", &signature).unwrap());
+        assert!(!verify_hash(&public_key, "blake3:abd", &signature).unwrap());
+        assert!(sign_hash(&[0u8; 16], "blake3:abc").is_err());
+    }
+}
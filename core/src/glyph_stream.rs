@@ -0,0 +1,317 @@
+// Glyphstream container (.glyphs)
+//
+// A glyphstream is a sequence of glyphs encoded for ingestion. On disk it is
+// laid out as:
+//
+//   "GLYPHS" | version (u8) | flags (u8)
+//   ( record_len (u32 BE) | CBOR glyph ) *
+//   0u32                                   -- end of records
+//   [ CBOR index | index_offset (u64 BE) | "GLYX" ]   -- optional footer
+//
+// Records are framed with a length prefix so readers can walk the stream
+// without holding more than one glyph in memory. The index footer allows
+// random access by seeking, but is never required for sequential reads.
+
+use serde::{Deserialize, Serialize};
+use ciborium::{into_writer, from_reader};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use super::glyph_decode::Glyph;
+
+pub const STREAM_MAGIC: &[u8; 6] = b"GLYPHS";
+pub const STREAM_VERSION: u8 = 1;
+pub const INDEX_TRAILER: &[u8; 4] = b"GLYX";
+
+// Refuse to allocate for records larger than this; a corrupt length prefix
+// should not take the process down.
+const MAX_RECORD_LEN: u32 = 64 * 1024 * 1024;
+
+// ============================================================================
+// DATA STRUCTURES
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub offset: u64,
+    pub length: u32,
+    pub id: String,
+    pub hash: String,
+    pub label: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamIndex {
+    pub entries: Vec<IndexEntry>,
+}
+
+// ============================================================================
+// WRITER
+// ============================================================================
+
+pub struct GlyphStreamWriter<W: Write> {
+    writer: W,
+    offset: u64,
+    entries: Vec<IndexEntry>,
+}
+
+impl<W: Write> GlyphStreamWriter<W> {
+    pub fn new(mut writer: W) -> Result<Self, Box<dyn std::error::Error>> {
+        writer.write_all(STREAM_MAGIC)?;
+        writer.write_all(&[STREAM_VERSION, 0])?;
+
+        Ok(GlyphStreamWriter {
+            writer,
+            offset: (STREAM_MAGIC.len() + 2) as u64,
+            entries: Vec::new(),
+        })
+    }
+
+    pub fn write_glyph(&mut self, glyph: &Glyph) -> Result<(), Box<dyn std::error::Error>> {
+        let cbor_data = glyph.to_cbor()?;
+        self.write_record(&cbor_data, glyph)
+    }
+
+    /// Append an already encoded .glyph file without re-encoding it, so the
+    /// bytes in the stream match the original file exactly.
+    pub fn write_raw(&mut self, cbor_data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let glyph = Glyph::from_cbor(cbor_data)?;
+        self.write_record(cbor_data, &glyph)
+    }
+
+    fn write_record(&mut self, cbor_data: &[u8], glyph: &Glyph) -> Result<(), Box<dyn std::error::Error>> {
+        if cbor_data.is_empty() || cbor_data.len() > MAX_RECORD_LEN as usize {
+            return Err(format!("Glyph record size {} out of range", cbor_data.len()).into());
+        }
+
+        let length = cbor_data.len() as u32;
+        self.writer.write_all(&length.to_be_bytes())?;
+        self.writer.write_all(cbor_data)?;
+
+        self.entries.push(IndexEntry {
+            offset: self.offset,
+            length,
+            id: glyph.header.id.clone(),
+            hash: glyph.header.hash.clone(),
+            label: glyph.payload.label.clone(),
+        });
+        self.offset += 4 + length as u64;
+
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Terminate the record section and optionally append the index footer.
+    pub fn finish(mut self, with_index: bool) -> Result<W, Box<dyn std::error::Error>> {
+        self.writer.write_all(&0u32.to_be_bytes())?;
+
+        if with_index {
+            let index_offset = self.offset + 4;
+            let index = StreamIndex { entries: self.entries };
+            into_writer(&index, &mut self.writer)?;
+            self.writer.write_all(&index_offset.to_be_bytes())?;
+            self.writer.write_all(INDEX_TRAILER)?;
+        }
+
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+// ============================================================================
+// READER
+// ============================================================================
+
+pub struct GlyphStreamReader<R: Read> {
+    reader: R,
+    finished: bool,
+}
+
+impl<R: Read> GlyphStreamReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut preamble = [0u8; 8];
+        reader.read_exact(&mut preamble)
+            .map_err(|_| "Input is too short to be a glyphstream")?;
+
+        if &preamble[..6] != STREAM_MAGIC {
+            return Err("Invalid glyphstream magic bytes".into());
+        }
+        if preamble[6] != STREAM_VERSION {
+            return Err(format!("Unsupported glyphstream version: {}", preamble[6]).into());
+        }
+
+        Ok(GlyphStreamReader { reader, finished: false })
+    }
+
+    /// Read the next record without decoding it. Returns `None` once the end
+    /// marker (or a clean end of input) has been reached.
+    pub fn next_raw(&mut self) -> Option<Result<Vec<u8>, Box<dyn std::error::Error>>> {
+        if self.finished {
+            return None;
+        }
+
+        let mut length_bytes = [0u8; 4];
+        if let Err(e) = self.reader.read_exact(&mut length_bytes) {
+            self.finished = true;
+            // Only the end marker ends a stream; running out of input first
+            // means records were lost
+            return Some(Err(if e.kind() == std::io::ErrorKind::UnexpectedEof {
+                "Glyph stream ends before its end marker".into()
+            } else {
+                e.into()
+            }));
+        }
+
+        let length = u32::from_be_bytes(length_bytes);
+        if length == 0 {
+            self.finished = true;
+            return None;
+        }
+        if length > MAX_RECORD_LEN {
+            self.finished = true;
+            return Some(Err(format!("Glyph record length {} exceeds limit", length).into()));
+        }
+
+        let mut record = vec![0u8; length as usize];
+        if let Err(e) = self.reader.read_exact(&mut record) {
+            self.finished = true;
+            return Some(Err(format!("Truncated glyph record: {}", e).into()));
+        }
+
+        Some(Ok(record))
+    }
+}
+
+impl<R: Read> Iterator for GlyphStreamReader<R> {
+    type Item = Result<Glyph, Box<dyn std::error::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_raw()
+            .map(|record| record.and_then(|data| Glyph::from_cbor(&data)))
+    }
+}
+
+/// Read the index footer of a seekable glyphstream, if it has one.
+pub fn read_index<R: Read + Seek>(reader: &mut R) -> Result<Option<StreamIndex>, Box<dyn std::error::Error>> {
+    let end = reader.seek(SeekFrom::End(0))?;
+    if end < 8 + 4 + 12 {
+        return Ok(None);
+    }
+
+    let mut trailer = [0u8; 12];
+    reader.seek(SeekFrom::End(-12))?;
+    reader.read_exact(&mut trailer)?;
+    if &trailer[8..] != INDEX_TRAILER {
+        return Ok(None);
+    }
+
+    let mut offset_bytes = [0u8; 8];
+    offset_bytes.copy_from_slice(&trailer[..8]);
+    let index_offset = u64::from_be_bytes(offset_bytes);
+    if index_offset >= end - 12 {
+        return Err("Glyphstream index offset out of range".into());
+    }
+
+    reader.seek(SeekFrom::Start(index_offset))?;
+    let index: StreamIndex = from_reader(reader.take(end - 12 - index_offset))?;
+    Ok(Some(index))
+}
+
+/// Read a single record located through the index.
+pub fn read_at<R: Read + Seek>(reader: &mut R, entry: &IndexEntry) -> Result<Glyph, Box<dyn std::error::Error>> {
+    reader.seek(SeekFrom::Start(entry.offset))?;
+
+    let mut length_bytes = [0u8; 4];
+    reader.read_exact(&mut length_bytes)?;
+    if u32::from_be_bytes(length_bytes) != entry.length {
+        return Err("Glyphstream index does not match record".into());
+    }
+
+    let mut record = vec![0u8; entry.length as usize];
+    reader.read_exact(&mut record)?;
+    Glyph::from_cbor(&record)
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glyph_encode;
+    use std::io::Cursor;
+
+    fn test_glyph(label: &str) -> Glyph {
+        let json5_content = format!(r#"
+        {{
+            "label": "{}",
+            "aliases": [],
+            "classes": [],
+            "namespace": {{ "provision": "glyph:test", "contextual_overlays": [] }},
+            "truth_mode": {{ "type": "empirical", "confidence": 0.9, "verified_by": [], "conflicts": [] }},
+            "audio": {{ "file": null, "phonetic": null }},
+            "visual": {{ "svg": null, "sigil": null, "style": null }},
+            "relations": [],
+            "grounding": {{ "wikidata": null, "sensors": [] }}
+        }}
+        "#, label);
+        let encoded = glyph_encode::Glyph::from_json5(&json5_content).unwrap();
+        Glyph::from_cbor(&encoded.to_cbor().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_stream_roundtrip_with_index() {
+        let mut writer = GlyphStreamWriter::new(Vec::new()).unwrap();
+        for label in ["sun", "moon", "tree"] {
+            writer.write_glyph(&test_glyph(label)).unwrap();
+        }
+        let data = writer.finish(true).unwrap();
+
+        let labels: Vec<String> = GlyphStreamReader::new(Cursor::new(&data)).unwrap()
+            .map(|g| g.unwrap().payload.label)
+            .collect();
+        assert_eq!(labels, vec!["sun", "moon", "tree"]);
+
+        let mut cursor = Cursor::new(&data);
+        let index = read_index(&mut cursor).unwrap().unwrap();
+        assert_eq!(index.entries.len(), 3);
+        let glyph = read_at(&mut cursor, &index.entries[1]).unwrap();
+        assert_eq!(glyph.payload.label, "moon");
+    }
+
+    #[test]
+    fn test_stream_without_index() {
+        let mut writer = GlyphStreamWriter::new(Vec::new()).unwrap();
+        writer.write_glyph(&test_glyph("sun")).unwrap();
+        let data = writer.finish(false).unwrap();
+
+        assert!(read_index(&mut Cursor::new(&data)).unwrap().is_none());
+        assert_eq!(GlyphStreamReader::new(Cursor::new(&data)).unwrap().count(), 1);
+        assert!(GlyphStreamReader::new(Cursor::new(b"NOTGLYPH")).is_err());
+    }
+
+    #[test]
+    fn test_truncated_stream_is_an_error() {
+        let mut writer = GlyphStreamWriter::new(Vec::new()).unwrap();
+        writer.write_glyph(&test_glyph("sun")).unwrap();
+        writer.write_glyph(&test_glyph("moon")).unwrap();
+        let data = writer.finish(false).unwrap();
+
+        // Cut after the first record, then inside the second one
+        let first_end = STREAM_MAGIC.len() + 2 + 4
+            + u32::from_be_bytes(data[8..12].try_into().unwrap()) as usize;
+        for cut in [first_end, first_end + 6] {
+            let results: Vec<_> = GlyphStreamReader::new(Cursor::new(&data[..cut])).unwrap().collect();
+            assert_eq!(results.len(), 2);
+            assert_eq!(results[0].as_ref().unwrap().payload.label, "sun");
+            assert!(results[1].is_err());
+        }
+    }
+}
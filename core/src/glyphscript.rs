@@ -0,0 +1,839 @@
+// Glyphscript: a small query and update language over glyph graphs.
+//
+// A script is one or more statements separated by `;`:
+//
+//   match (a)-[powers]->(b) where a.truth_mode.confidence > 0.9 return b.label
+//   match (a:star)-->(b), (b)-[orbits|opposite_of]->(c) return distinct a, c.label as other
+//   match (a) where a.label = "sun" set a.truth_mode.confidence = 0.99
+//   match (a) where a.aliases contains "sol" add "helios" to a.aliases
+//   match (a) remove "sol" from a.aliases return a.aliases limit 10
+//
+// Patterns bind variables to glyphs: `(a:star)` is any glyph with class
+// `star`, `-[powers]->` follows relations of that type (`-->` or `-[]->`
+// follow any relation, quote types such as `-["glyphos:mythology/rides"]->`).
+// Relation targets are resolved with the store resolver, so unresolvable and
+// ambiguous targets simply do not match.
+//
+// Paths read the payload (`a.truth_mode.confidence`) or the header
+// (`a.id`, `a.hash`, `a.header.created_at`); a bare variable is its label.
+// `where` supports = != < <= > >= contains, and, or, not and parentheses.
+//
+// Updates (`set`, `add ... to`, `remove ... from`) change payload fields only.
+// An updated glyph gets its semantic hash recomputed, and a signature that no
+// longer covers it is dropped.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+
+use super::glyph_decode::{Glyph, GlyphPayload};
+use super::glyph_ref::Resolver;
+
+// ============================================================================
+// DATA STRUCTURES
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(f64),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Dash,
+    Arrow,
+    Colon,
+    Comma,
+    Dot,
+    Pipe,
+    Semicolon,
+    Op(CmpOp),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodePattern {
+    pub var: String,
+    pub class: Option<String>,
+}
+
+/// One relation hop; no types means any relation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub types: Vec<String>,
+    pub node: NodePattern,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub start: NodePattern,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    pub var: String,
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    Path(Path),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, CmpOp, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Update {
+    Set(Path, Value),
+    Add(Path, Value),
+    Remove(Path, Value),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReturnItem {
+    pub path: Path,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+    pub patterns: Vec<Pattern>,
+    pub filter: Option<Expr>,
+    pub updates: Vec<Update>,
+    pub distinct: bool,
+    pub returns: Vec<ReturnItem>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Script {
+    pub statements: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+    /// Indices of the glyphs this statement changed
+    pub updated: Vec<usize>,
+}
+
+// ============================================================================
+// LEXER
+// ============================================================================
+
+fn tokenize(script: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = script.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' | '[' | ']' | ':' | ',' | '.' | '|' | ';' => {
+                chars.next();
+                tokens.push(match c {
+                    '(' => Token::LParen,
+                    ')' => Token::RParen,
+                    '[' => Token::LBracket,
+                    ']' => Token::RBracket,
+                    ':' => Token::Colon,
+                    ',' => Token::Comma,
+                    '.' => Token::Dot,
+                    '|' => Token::Pipe,
+                    _ => Token::Semicolon,
+                });
+            }
+            '-' => {
+                chars.next();
+                if chars.next_if_eq(&'>').is_some() {
+                    tokens.push(Token::Arrow);
+                } else {
+                    tokens.push(Token::Dash);
+                }
+            }
+            '=' => {
+                chars.next();
+                chars.next_if_eq(&'=');
+                tokens.push(Token::Op(CmpOp::Eq));
+            }
+            '!' => {
+                chars.next();
+                if chars.next_if_eq(&'=').is_none() {
+                    return Err("Expected '=' after '!'".to_string());
+                }
+                tokens.push(Token::Op(CmpOp::Ne));
+            }
+            '<' | '>' => {
+                chars.next();
+                let equal = chars.next_if_eq(&'=').is_some();
+                tokens.push(Token::Op(match (c, equal) {
+                    ('<', false) => CmpOp::Lt,
+                    ('<', true) => CmpOp::Le,
+                    ('>', false) => CmpOp::Gt,
+                    _ => CmpOp::Ge,
+                }));
+            }
+            '"' | '\'' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => match chars.next() {
+                            Some('n') => text.push('\n'),
+                            Some(escaped) => text.push(escaped),
+                            None => return Err("Unterminated string".to_string()),
+                        },
+                        Some(end) if end == c => break,
+                        Some(other) => text.push(other),
+                        None => return Err("Unterminated string".to_string()),
+                    }
+                }
+                tokens.push(Token::Str(text));
+            }
+            c if c.is_ascii_digit() => {
+                let mut number = String::new();
+                while let Some(digit) = chars.next_if(|d| d.is_ascii_digit() || *d == '.') {
+                    number.push(digit);
+                }
+                tokens.push(Token::Num(number.parse().map_err(|_| format!("Invalid number: {}", number))?));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut ident = String::new();
+                while let Some(part) = chars.next_if(|d| d.is_alphanumeric() || *d == '_') {
+                    ident.push(part);
+                }
+                tokens.push(if ident.eq_ignore_ascii_case("contains") { Token::Op(CmpOp::Contains) } else { Token::Ident(ident) });
+            }
+            other => return Err(format!("Unexpected character: {}", other)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+// ============================================================================
+// PARSER
+// ============================================================================
+
+const KEYWORDS: &[&str] = &[
+    "match", "where", "return", "distinct", "as", "limit", "set", "add", "remove",
+    "to", "from", "and", "or", "not", "true", "false", "null",
+];
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+pub fn parse(script: &str) -> Result<Script, String> {
+    let mut parser = Parser { tokens: tokenize(script)?, pos: 0 };
+    let mut statements = Vec::new();
+
+    loop {
+        while parser.eat(&Token::Semicolon) {}
+        if parser.peek().is_none() {
+            break;
+        }
+        statements.push(parser.statement()?);
+        if parser.peek().is_some() && !parser.eat(&Token::Semicolon) {
+            return Err(format!("Expected ';' or end of script, found {}", parser.describe()));
+        }
+    }
+
+    if statements.is_empty() {
+        return Err("Empty script".to_string());
+    }
+    Ok(Script { statements })
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn describe(&self) -> String {
+        match self.peek() {
+            Some(token) => format!("{:?}", token),
+            None => "end of script".to_string(),
+        }
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), String> {
+        if self.eat(&token) {
+            Ok(())
+        } else {
+            Err(format!("Expected {:?}, found {}", token, self.describe()))
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+        if self.keyword(keyword) {
+            Ok(())
+        } else {
+            Err(format!("Expected '{}', found {}", keyword, self.describe()))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some(Token::Ident(ident)) if !KEYWORDS.contains(&ident.to_lowercase().as_str()) => {
+                let ident = ident.clone();
+                self.pos += 1;
+                Ok(ident)
+            }
+            _ => Err(format!("Expected a name, found {}", self.describe())),
+        }
+    }
+
+    // A bare identifier or a quoted string (class and relation names)
+    fn name(&mut self) -> Result<String, String> {
+        if let Some(Token::Str(text)) = self.peek() {
+            let text = text.clone();
+            self.pos += 1;
+            return Ok(text);
+        }
+        self.ident()
+    }
+
+    fn statement(&mut self) -> Result<Statement, String> {
+        self.expect_keyword("match")?;
+        let mut patterns = vec![self.pattern()?];
+        while self.eat(&Token::Comma) {
+            patterns.push(self.pattern()?);
+        }
+
+        let filter = if self.keyword("where") { Some(self.or_expr()?) } else { None };
+
+        let mut updates = Vec::new();
+        loop {
+            if self.keyword("set") {
+                loop {
+                    let path = self.path()?;
+                    self.expect(Token::Op(CmpOp::Eq))?;
+                    updates.push(Update::Set(path, self.literal()?));
+                    if !self.eat(&Token::Comma) {
+                        break;
+                    }
+                }
+            } else if self.keyword("add") {
+                let value = self.literal()?;
+                self.expect_keyword("to")?;
+                updates.push(Update::Add(self.path()?, value));
+            } else if self.keyword("remove") {
+                let value = self.literal()?;
+                self.expect_keyword("from")?;
+                updates.push(Update::Remove(self.path()?, value));
+            } else {
+                break;
+            }
+        }
+
+        let mut distinct = false;
+        let mut returns = Vec::new();
+        if self.keyword("return") {
+            distinct = self.keyword("distinct");
+            loop {
+                let path = self.path()?;
+                let name = if self.keyword("as") {
+                    self.ident()?
+                } else {
+                    std::iter::once(path.var.as_str()).chain(path.fields.iter().map(String::as_str)).collect::<Vec<_>>().join(".")
+                };
+                returns.push(ReturnItem { path, name });
+                if !self.eat(&Token::Comma) {
+                    break;
+                }
+            }
+        }
+
+        let limit = if self.keyword("limit") {
+            match self.peek() {
+                Some(Token::Num(n)) if n.fract() == 0.0 && *n >= 0.0 => {
+                    let n = *n as usize;
+                    self.pos += 1;
+                    Some(n)
+                }
+                _ => return Err(format!("Expected a row count after 'limit', found {}", self.describe())),
+            }
+        } else {
+            None
+        };
+
+        if updates.is_empty() && returns.is_empty() {
+            return Err("A statement needs 'return' or an update ('set', 'add', 'remove')".to_string());
+        }
+        Ok(Statement { patterns, filter, updates, distinct, returns, limit })
+    }
+
+    fn node(&mut self) -> Result<NodePattern, String> {
+        self.expect(Token::LParen)?;
+        let var = self.ident()?;
+        let class = if self.eat(&Token::Colon) { Some(self.name()?) } else { None };
+        self.expect(Token::RParen)?;
+        Ok(NodePattern { var, class })
+    }
+
+    fn pattern(&mut self) -> Result<Pattern, String> {
+        let start = self.node()?;
+        let mut steps = Vec::new();
+
+        while self.eat(&Token::Dash) {
+            let mut types = Vec::new();
+            if self.eat(&Token::LBracket) && !self.eat(&Token::RBracket) {
+                loop {
+                    types.push(self.name()?);
+                    if !self.eat(&Token::Pipe) {
+                        break;
+                    }
+                }
+                self.expect(Token::RBracket)?;
+            }
+            self.expect(Token::Arrow)?;
+            steps.push(Step { types, node: self.node()? });
+        }
+
+        Ok(Pattern { start, steps })
+    }
+
+    fn path(&mut self) -> Result<Path, String> {
+        let var = self.ident()?;
+        let mut fields = Vec::new();
+        while self.eat(&Token::Dot) {
+            match self.peek() {
+                Some(Token::Ident(field)) => {
+                    fields.push(field.clone());
+                    self.pos += 1;
+                }
+                _ => return Err(format!("Expected a field name, found {}", self.describe())),
+            }
+        }
+        Ok(Path { var, fields })
+    }
+
+    fn literal(&mut self) -> Result<Value, String> {
+        let negative = self.eat(&Token::Dash);
+        let value = match self.peek() {
+            Some(Token::Num(n)) => serde_json::json!(if negative { -n } else { *n }),
+            Some(Token::Str(text)) if !negative => Value::String(text.clone()),
+            Some(Token::Ident(ident)) if !negative && ident.eq_ignore_ascii_case("true") => Value::Bool(true),
+            Some(Token::Ident(ident)) if !negative && ident.eq_ignore_ascii_case("false") => Value::Bool(false),
+            Some(Token::Ident(ident)) if !negative && ident.eq_ignore_ascii_case("null") => Value::Null,
+            _ => return Err(format!("Expected a value, found {}", self.describe())),
+        };
+        self.pos += 1;
+        Ok(value)
+    }
+
+    fn or_expr(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and_expr()?;
+        while self.keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and_expr()?));
+        }
+        Ok(expr)
+    }
+
+    fn and_expr(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.keyword("not") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        let left = self.operand()?;
+        if let Some(Token::Op(op)) = self.peek() {
+            let op = *op;
+            self.pos += 1;
+            return Ok(Expr::Compare(Box::new(left), op, Box::new(self.operand()?)));
+        }
+        Ok(left)
+    }
+
+    fn operand(&mut self) -> Result<Expr, String> {
+        if self.eat(&Token::LParen) {
+            let expr = self.or_expr()?;
+            self.expect(Token::RParen)?;
+            return Ok(expr);
+        }
+        match self.peek() {
+            Some(Token::Ident(ident)) if !["true", "false", "null"].contains(&ident.to_lowercase().as_str()) => {
+                Ok(Expr::Path(self.path()?))
+            }
+            _ => Ok(Expr::Literal(self.literal()?)),
+        }
+    }
+}
+
+// ============================================================================
+// INTERPRETER
+// ============================================================================
+
+type Binding = HashMap<String, usize>;
+
+/// Run a script over `glyphs`, applying its updates in place
+pub fn run(script: &Script, glyphs: &mut [Glyph]) -> Result<Vec<QueryResult>, Box<dyn std::error::Error>> {
+    script.statements.iter().map(|statement| execute(statement, glyphs)).collect()
+}
+
+/// Parse and run a script
+pub fn query(script: &str, glyphs: &mut [Glyph]) -> Result<Vec<QueryResult>, Box<dyn std::error::Error>> {
+    run(&parse(script)?, glyphs)
+}
+
+fn execute(statement: &Statement, glyphs: &mut [Glyph]) -> Result<QueryResult, Box<dyn std::error::Error>> {
+    let mut documents = glyphs.iter().map(serde_json::to_value).collect::<Result<Vec<_>, _>>()?;
+    let edges = edges(glyphs);
+
+    let mut bindings = vec![Binding::new()];
+    for pattern in &statement.patterns {
+        let mut next = Vec::new();
+        for binding in &bindings {
+            for start in 0..glyphs.len() {
+                let mut binding = binding.clone();
+                if bind(&mut binding, &pattern.start, start, glyphs) {
+                    walk(binding, start, &pattern.steps, glyphs, &edges, &mut next);
+                }
+            }
+        }
+        bindings = next;
+    }
+
+    if let Some(filter) = &statement.filter {
+        let mut kept = Vec::new();
+        for binding in bindings {
+            if truthy(&evaluate(filter, &binding, &documents)?) {
+                kept.push(binding);
+            }
+        }
+        bindings = kept;
+    }
+
+    // Updates are applied to every matched binding, then written back once per glyph
+    let mut changed = BTreeSet::new();
+    for binding in &bindings {
+        for update in &statement.updates {
+            let path = match update {
+                Update::Set(path, _) | Update::Add(path, _) | Update::Remove(path, _) => path,
+            };
+            let index = *binding.get(&path.var).ok_or_else(|| format!("Unbound variable: {}", path.var))?;
+            if apply(update, &mut documents[index]["payload"])? {
+                changed.insert(index);
+            }
+        }
+    }
+    for &index in &changed {
+        let payload: GlyphPayload = serde_json::from_value(documents[index]["payload"].clone())
+            .map_err(|e| format!("Update leaves {} invalid: {}", glyphs[index].payload.label, e))?;
+        let glyph = &mut glyphs[index];
+        glyph.payload = payload;
+        let hash = glyph.semantic_hash();
+        if hash != glyph.header.hash {
+            glyph.header.hash = hash;
+            glyph.header.signature = None;
+        }
+        documents[index] = serde_json::to_value(&*glyph)?;
+    }
+
+    let mut rows = Vec::new();
+    if !statement.returns.is_empty() {
+        for binding in &bindings {
+            let row = statement.returns.iter()
+                .map(|item| lookup(&item.path, binding, &documents))
+                .collect::<Result<Vec<_>, _>>()?;
+            if !(statement.distinct && rows.contains(&row)) {
+                rows.push(row);
+            }
+        }
+    }
+    if let Some(limit) = statement.limit {
+        rows.truncate(limit);
+    }
+
+    Ok(QueryResult {
+        columns: statement.returns.iter().map(|item| item.name.clone()).collect(),
+        rows,
+        updated: changed.into_iter().collect(),
+    })
+}
+
+// Resolved relations: (type, target index) for every glyph
+fn edges(glyphs: &[Glyph]) -> Vec<Vec<(String, usize)>> {
+    let resolver = Resolver::new(glyphs);
+    let index: HashMap<*const Glyph, usize> = glyphs.iter().enumerate().map(|(i, glyph)| (glyph as *const Glyph, i)).collect();
+
+    glyphs.iter()
+        .map(|glyph| glyph.payload.relations.iter()
+            .filter_map(|relation| {
+                let target = resolver.resolve_str(&relation.target).ok()?;
+                Some((relation.r#type.clone(), index[&(target as *const Glyph)]))
+            })
+            .collect())
+        .collect()
+}
+
+fn bind(binding: &mut Binding, node: &NodePattern, index: usize, glyphs: &[Glyph]) -> bool {
+    if let Some(class) = &node.class {
+        if !glyphs[index].payload.classes.contains(class) {
+            return false;
+        }
+    }
+    match binding.get(&node.var) {
+        Some(&bound) => bound == index,
+        None => {
+            binding.insert(node.var.clone(), index);
+            true
+        }
+    }
+}
+
+fn walk(binding: Binding, current: usize, steps: &[Step], glyphs: &[Glyph], edges: &[Vec<(String, usize)>], out: &mut Vec<Binding>) {
+    let Some((step, rest)) = steps.split_first() else {
+        out.push(binding);
+        return;
+    };
+    for (relation_type, target) in &edges[current] {
+        if !step.types.is_empty() && !step.types.contains(relation_type) {
+            continue;
+        }
+        let mut next = binding.clone();
+        if bind(&mut next, &step.node, *target, glyphs) {
+            walk(next, *target, rest, glyphs, edges, out);
+        }
+    }
+}
+
+fn lookup(path: &Path, binding: &Binding, documents: &[Value]) -> Result<Value, String> {
+    let index = *binding.get(&path.var).ok_or_else(|| format!("Unbound variable: {}", path.var))?;
+    let document = &documents[index];
+
+    let (mut value, fields) = match path.fields.first().map(String::as_str) {
+        None => return Ok(document["payload"]["label"].clone()),
+        Some("header") => (&document["header"], &path.fields[1..]),
+        Some("id" | "hash" | "signature" | "created_at" | "lineage") => (&document["header"], &path.fields[..]),
+        Some(_) => (&document["payload"], &path.fields[..]),
+    };
+    for field in fields {
+        value = &value[field.as_str()];
+    }
+    Ok(value.clone())
+}
+
+fn evaluate(expr: &Expr, binding: &Binding, documents: &[Value]) -> Result<Value, String> {
+    Ok(match expr {
+        Expr::Literal(value) => value.clone(),
+        Expr::Path(path) => lookup(path, binding, documents)?,
+        Expr::Not(inner) => Value::Bool(!truthy(&evaluate(inner, binding, documents)?)),
+        Expr::And(left, right) => Value::Bool(
+            truthy(&evaluate(left, binding, documents)?) && truthy(&evaluate(right, binding, documents)?),
+        ),
+        Expr::Or(left, right) => Value::Bool(
+            truthy(&evaluate(left, binding, documents)?) || truthy(&evaluate(right, binding, documents)?),
+        ),
+        Expr::Compare(left, op, right) => {
+            let (left, right) = (evaluate(left, binding, documents)?, evaluate(right, binding, documents)?);
+            Value::Bool(compare(&left, *op, &right))
+        }
+    })
+}
+
+fn truthy(value: &Value) -> bool {
+    !matches!(value, Value::Null | Value::Bool(false))
+}
+
+// Numbers compare by value so that 1 = 1.0
+fn equal(left: &Value, right: &Value) -> bool {
+    match (left.as_f64(), right.as_f64()) {
+        (Some(l), Some(r)) => l == r,
+        _ => left == right,
+    }
+}
+
+fn compare(left: &Value, op: CmpOp, right: &Value) -> bool {
+    use std::cmp::Ordering;
+
+    let ordering = match (left, right) {
+        (Value::Number(_), Value::Number(_)) => left.as_f64().unwrap().partial_cmp(&right.as_f64().unwrap()),
+        (Value::String(l), Value::String(r)) => Some(l.cmp(r)),
+        _ => None,
+    };
+    match op {
+        CmpOp::Eq => equal(left, right),
+        CmpOp::Ne => !equal(left, right),
+        CmpOp::Lt => ordering == Some(Ordering::Less),
+        CmpOp::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        CmpOp::Gt => ordering == Some(Ordering::Greater),
+        CmpOp::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        CmpOp::Contains => match (left, right) {
+            (Value::Array(items), _) => items.iter().any(|item| equal(item, right)),
+            (Value::String(text), Value::String(part)) => text.contains(part.as_str()),
+            _ => false,
+        },
+    }
+}
+
+// Apply one update to a payload; returns whether anything changed
+fn apply(update: &Update, payload: &mut Value) -> Result<bool, String> {
+    let (path, value) = match update {
+        Update::Set(path, value) | Update::Add(path, value) | Update::Remove(path, value) => (path, value),
+    };
+    let display = std::iter::once(path.var.as_str()).chain(path.fields.iter().map(String::as_str)).collect::<Vec<_>>().join(".");
+    let Some((last, parents)) = path.fields.split_last() else {
+        return Err(format!("Cannot update {} itself; name a field", path.var));
+    };
+    if matches!(path.fields[0].as_str(), "header" | "id" | "hash" | "signature" | "created_at" | "lineage") {
+        return Err(format!("{} is read-only", display));
+    }
+
+    let mut parent = payload;
+    for field in parents {
+        parent = parent.get_mut(field.as_str())
+            .filter(|value| value.is_object())
+            .ok_or_else(|| format!("{} does not exist", display))?;
+    }
+    let object = parent.as_object_mut().ok_or_else(|| format!("{} does not exist", display))?;
+
+    match update {
+        Update::Set(..) => {
+            let changed = object.get(last.as_str()) != Some(value);
+            object.insert(last.clone(), value.clone());
+            Ok(changed)
+        }
+        Update::Add(..) => {
+            let items = object.get_mut(last.as_str()).and_then(Value::as_array_mut)
+                .ok_or_else(|| format!("{} is not a list", display))?;
+            if items.iter().any(|item| equal(item, value)) {
+                return Ok(false);
+            }
+            items.push(value.clone());
+            Ok(true)
+        }
+        Update::Remove(..) => {
+            let items = object.get_mut(last.as_str()).and_then(Value::as_array_mut)
+                .ok_or_else(|| format!("{} is not a list", display))?;
+            let before = items.len();
+            items.retain(|item| !equal(item, value));
+            Ok(items.len() != before)
+        }
+    }
+}
+
+impl QueryResult {
+    pub fn to_text(&self) -> String {
+        let cell = |value: &Value| match value {
+            Value::String(text) => text.clone(),
+            other => other.to_string(),
+        };
+        let mut text = String::new();
+        if !self.columns.is_empty() {
+            text.push_str(&self.columns.join(" | "));
+            text.push('\n');
+            for row in &self.rows {
+                text.push_str(&row.iter().map(cell).collect::<Vec<_>>().join(" | "));
+                text.push('\n');
+            }
+        }
+        text
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::glyph;
+
+    fn store() -> Vec<Glyph> {
+        vec![
+            glyph("sun").classes(&["star"]).confidence(0.98)
+                .relation("powers", "glyph:plant").relation("opposite_of", "glyph:moon").build(),
+            glyph("lamp").confidence(0.6).relation("powers", "glyph:plant").build(),
+            glyph("plant").relation("feeds", "glyph:deer").relation("needs", "glyph:missing").build(),
+            glyph("moon").confidence(0.95).build(),
+            glyph("deer").build(),
+        ]
+    }
+
+    #[test]
+    fn test_match_where_return() {
+        let mut glyphs = store();
+        let results = query("match (a)-[powers]->(b) where a.truth_mode.confidence > 0.9 return a, b.label", &mut glyphs).unwrap();
+        assert_eq!(results[0].columns, vec!["a", "b.label"]);
+        assert_eq!(results[0].rows, vec![vec![Value::from("sun"), Value::from("plant")]]);
+
+        let results = query(
+            "match (a:star)-->(b)-[feeds]->(c) return distinct c; match (x) where not x.label contains 'a' or x.label = 'lamp' return x limit 2",
+            &mut glyphs,
+        ).unwrap();
+        assert_eq!(results[0].rows, vec![vec![Value::from("deer")]]);
+        assert_eq!(results[1].rows, vec![vec![Value::from("sun")], vec![Value::from("lamp")]]);
+
+        assert!(parse("match (a) where a.label = return a").is_err());
+        assert!(parse("match (a)").is_err());
+    }
+
+    #[test]
+    fn test_updates() {
+        let mut glyphs = store();
+        let old_hash = glyphs[0].header.hash.clone();
+        glyphs[0].header.signature = Some("signed".to_string());
+
+        let results = query(
+            "match (a) where a.label = 'sun' set a.truth_mode.confidence = 0.5 add 'sol' to a.aliases return a.aliases",
+            &mut glyphs,
+        ).unwrap();
+        assert_eq!(results[0].updated, vec![0]);
+        assert_eq!(results[0].rows, vec![vec![serde_json::json!(["sol"])]]);
+        assert_eq!(glyphs[0].payload.truth_mode.confidence, 0.5);
+        // Aliases are semantic, so the hash moves and the signature goes
+        assert_ne!(glyphs[0].header.hash, old_hash);
+        assert_eq!(glyphs[0].header.hash, glyphs[0].semantic_hash());
+        assert!(glyphs[0].header.signature.is_none());
+
+        assert!(query("match (a) set a.hash = 'x'", &mut glyphs).is_err());
+        assert!(query("match (a) set a.truth_mode.confidence = 'high'", &mut glyphs).is_err());
+    }
+}
@@ -0,0 +1,541 @@
+// Rule-based inference over relation types.
+//
+// A JSON5 rules file declares relation properties and Horn-style rules:
+//
+//   {
+//     relations: {
+//       opposite_of: { symmetric: true },
+//       part_of: { transitive: true },
+//       has_part: { inverse_of: "part_of" },
+//     },
+//     rules: [
+//       { name: "sustains", if: ["?a powers ?b", "?b feeds ?c"], then: "?a sustains ?c" },
+//       { name: "luminous", if: ["?a is star", "?a powers ?b"], then: "?b lit_by ?a" },
+//     ],
+//     disjoint_relations: [["opposite_of", "same_as"]],
+//     disjoint_classes: [["star", "planet"]],
+//   }
+//
+// Atoms are `<subject> <relation> <object>`, where `?x` is a variable and
+// anything else is a glyph reference or label; `?x is <class>` tests class
+// membership. The engine starts from the relations written in a corpus and
+// applies properties and rules until nothing new follows. Every inferred edge
+// keeps its derivation, so it can be explained down to asserted relations.
+//
+// Disjoint relations may not hold between the same two glyphs, and a glyph
+// may belong to at most one class of each disjoint set; the dissonance
+// analyzer reports both.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use super::glyph_decode::Glyph;
+use super::glyph_ref::Resolver;
+
+// ============================================================================
+// DATA STRUCTURES
+// ============================================================================
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RelationProperties {
+    #[serde(default)]
+    pub symmetric: bool,
+    #[serde(default)]
+    pub transitive: bool,
+    #[serde(default)]
+    pub inverse_of: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub name: String,
+    #[serde(rename = "if")]
+    pub body: Vec<String>,
+    #[serde(rename = "then")]
+    pub head: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleSet {
+    #[serde(default)]
+    pub relations: BTreeMap<String, RelationProperties>,
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    /// Sets of relation types that contradict each other
+    #[serde(default)]
+    pub disjoint_relations: Vec<Vec<String>>,
+    /// Sets of classes that exclude each other
+    #[serde(default)]
+    pub disjoint_classes: Vec<Vec<String>>,
+}
+
+/// A glyph in the corpus, or a relation target that did not resolve to one
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(untagged)]
+pub enum Node {
+    Glyph(usize),
+    External(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Derivation {
+    /// Written in the relations of this glyph
+    Asserted { glyph: usize },
+    Symmetric { from: usize },
+    Inverse { from: usize },
+    Transitive { first: usize, second: usize },
+    Rule { name: String, premises: Vec<usize> },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Fact {
+    pub subject: Node,
+    pub relation: String,
+    pub object: Node,
+    pub derivation: Derivation,
+}
+
+#[derive(Debug, Clone)]
+enum Term {
+    Var(String),
+    Const(Node),
+}
+
+#[derive(Debug, Clone)]
+enum Atom {
+    Relation(Term, String, Term),
+    Class(Term, String),
+}
+
+#[derive(Debug, Clone)]
+struct CompiledRule {
+    name: String,
+    body: Vec<Atom>,
+    head: (Term, String, Term),
+}
+
+pub struct Closure<'a> {
+    glyphs: &'a [Glyph],
+    pub facts: Vec<Fact>,
+    index: HashMap<(Node, String, Node), usize>,
+}
+
+// ============================================================================
+// RULES
+// ============================================================================
+
+impl RuleSet {
+    pub fn from_json5(content: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let rules: RuleSet = serde_json5::from_str(content)?;
+        rules.check()?;
+        Ok(rules)
+    }
+
+    fn check(&self) -> Result<(), String> {
+        for (relation, properties) in &self.relations {
+            if let Some(inverse) = &properties.inverse_of {
+                if inverse == relation && !properties.symmetric {
+                    return Err(format!("{} is its own inverse; declare it symmetric instead", relation));
+                }
+            }
+        }
+        for set in self.disjoint_relations.iter().chain(&self.disjoint_classes) {
+            if set.len() < 2 {
+                return Err(format!("Disjoint set [{}] needs at least two members", set.join(", ")));
+            }
+        }
+        for rule in &self.rules {
+            if rule.body.is_empty() {
+                return Err(format!("Rule {} has no conditions", rule.name));
+            }
+            let mut bound = Vec::new();
+            for atom in &rule.body {
+                let parts = split_atom(atom).map_err(|e| format!("Rule {}: {}", rule.name, e))?;
+                bound.extend(parts.iter().filter(|part| part.starts_with('?')).map(|part| part.to_string()));
+            }
+            let head = split_atom(&rule.head).map_err(|e| format!("Rule {}: {}", rule.name, e))?;
+            if head[1] == "is" {
+                return Err(format!("Rule {}: the conclusion must be a relation, not a class", rule.name));
+            }
+            for part in [head[0], head[2]] {
+                if part.starts_with('?') && !bound.iter().any(|var| var == part) {
+                    return Err(format!("Rule {}: {} appears in the conclusion but not in the conditions", rule.name, part));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn split_atom(atom: &str) -> Result<Vec<&str>, String> {
+    let parts: Vec<&str> = atom.split_whitespace().collect();
+    if parts.len() != 3 {
+        return Err(format!("expected '<subject> <relation> <object>', found '{}'", atom));
+    }
+    Ok(parts)
+}
+
+// ============================================================================
+// CLOSURE
+// ============================================================================
+
+impl<'a> Closure<'a> {
+    /// Derive every relation implied by the corpus and the rules
+    pub fn compute(glyphs: &'a [Glyph], rules: &RuleSet) -> Result<Self, Box<dyn std::error::Error>> {
+        let resolver = Resolver::new(glyphs);
+        let positions: HashMap<*const Glyph, usize> = glyphs.iter().enumerate()
+            .map(|(i, glyph)| (glyph as *const Glyph, i))
+            .collect();
+        let node = |reference: &str| -> Node {
+            let reference = if reference.contains(':') { reference.to_string() } else { format!("glyph:{}", reference) };
+            match resolver.resolve_str(&reference) {
+                Ok(glyph) => Node::Glyph(positions[&(glyph as *const Glyph)]),
+                Err(_) => Node::External(reference),
+            }
+        };
+
+        let mut closure = Closure { glyphs, facts: Vec::new(), index: HashMap::new() };
+        for (i, glyph) in glyphs.iter().enumerate() {
+            for relation in &glyph.payload.relations {
+                closure.add(Node::Glyph(i), &relation.r#type, node(&relation.target), Derivation::Asserted { glyph: i });
+            }
+        }
+
+        let term = |part: &str| if part.starts_with('?') { Term::Var(part.to_string()) } else { Term::Const(node(part)) };
+        let mut compiled = Vec::new();
+        for rule in &rules.rules {
+            let body = rule.body.iter()
+                .map(|atom| {
+                    let parts = split_atom(atom)?;
+                    Ok(if parts[1] == "is" {
+                        Atom::Class(term(parts[0]), parts[2].to_string())
+                    } else {
+                        Atom::Relation(term(parts[0]), parts[1].to_string(), term(parts[2]))
+                    })
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            let head = split_atom(&rule.head)?;
+            compiled.push(CompiledRule {
+                name: rule.name.clone(),
+                body,
+                head: (term(head[0]), head[1].to_string(), term(head[2])),
+            });
+        }
+
+        // Apply properties and rules until a round adds nothing
+        loop {
+            let before = closure.facts.len();
+
+            for i in 0..closure.facts.len() {
+                let (subject, relation, object) = {
+                    let fact = &closure.facts[i];
+                    (fact.subject.clone(), fact.relation.clone(), fact.object.clone())
+                };
+                if let Some(properties) = rules.relations.get(&relation) {
+                    if properties.symmetric {
+                        closure.add(object.clone(), &relation, subject.clone(), Derivation::Symmetric { from: i });
+                    }
+                    if let Some(inverse) = &properties.inverse_of {
+                        closure.add(object.clone(), inverse, subject.clone(), Derivation::Inverse { from: i });
+                    }
+                }
+                // inverse_of is declared on one side but holds both ways
+                for (other, properties) in &rules.relations {
+                    if properties.inverse_of.as_deref() == Some(relation.as_str()) {
+                        closure.add(object.clone(), other, subject.clone(), Derivation::Inverse { from: i });
+                    }
+                }
+            }
+
+            for (relation, properties) in &rules.relations {
+                if !properties.transitive {
+                    continue;
+                }
+                let edges: Vec<usize> = (0..closure.facts.len()).filter(|&i| closure.facts[i].relation == *relation).collect();
+                for &first in &edges {
+                    for &second in &edges {
+                        if closure.facts[first].object == closure.facts[second].subject {
+                            let subject = closure.facts[first].subject.clone();
+                            let object = closure.facts[second].object.clone();
+                            if subject != object {
+                                closure.add(subject, relation, object, Derivation::Transitive { first, second });
+                            }
+                        }
+                    }
+                }
+            }
+
+            for rule in &compiled {
+                let mut matches = Vec::new();
+                closure.solve(&rule.body, &mut HashMap::new(), &mut Vec::new(), &mut matches);
+                for (bindings, premises) in matches {
+                    let resolve = |term: &Term| match term {
+                        Term::Var(var) => bindings[var].clone(),
+                        Term::Const(node) => node.clone(),
+                    };
+                    let (subject, object) = (resolve(&rule.head.0), resolve(&rule.head.2));
+                    closure.add(subject, &rule.head.1, object, Derivation::Rule { name: rule.name.clone(), premises });
+                }
+            }
+
+            if closure.facts.len() == before {
+                break;
+            }
+        }
+
+        Ok(closure)
+    }
+
+    fn add(&mut self, subject: Node, relation: &str, object: Node, derivation: Derivation) {
+        let key = (subject, relation.to_string(), object);
+        if self.index.contains_key(&key) {
+            return;
+        }
+        self.index.insert(key.clone(), self.facts.len());
+        let (subject, relation, object) = key;
+        self.facts.push(Fact { subject, relation, object, derivation });
+    }
+
+    // Backtracking join of rule conditions over the current facts
+    fn solve(
+        &self,
+        atoms: &[Atom],
+        bindings: &mut HashMap<String, Node>,
+        premises: &mut Vec<usize>,
+        out: &mut Vec<(HashMap<String, Node>, Vec<usize>)>,
+    ) {
+        let Some((atom, rest)) = atoms.split_first() else {
+            out.push((bindings.clone(), premises.clone()));
+            return;
+        };
+
+        match atom {
+            Atom::Class(term, class) => {
+                let candidates: Vec<Node> = match term {
+                    Term::Var(var) if !bindings.contains_key(var) => (0..self.glyphs.len()).map(Node::Glyph).collect(),
+                    Term::Var(var) => vec![bindings[var].clone()],
+                    Term::Const(node) => vec![node.clone()],
+                };
+                for node in candidates {
+                    let Node::Glyph(i) = node else { continue };
+                    if !self.glyphs[i].payload.classes.contains(class) {
+                        continue;
+                    }
+                    let fresh = matches!(term, Term::Var(var) if !bindings.contains_key(var));
+                    if let (true, Term::Var(var)) = (fresh, term) {
+                        bindings.insert(var.clone(), node.clone());
+                    }
+                    self.solve(rest, bindings, premises, out);
+                    if let (true, Term::Var(var)) = (fresh, term) {
+                        bindings.remove(var);
+                    }
+                }
+            }
+            Atom::Relation(subject, relation, object) => {
+                for (i, fact) in self.facts.iter().enumerate() {
+                    if fact.relation != *relation {
+                        continue;
+                    }
+                    let mut added = Vec::new();
+                    let ok = [(subject, &fact.subject), (object, &fact.object)].into_iter().all(|(term, node)| match term {
+                        Term::Const(constant) => constant == node,
+                        Term::Var(var) => match bindings.get(var) {
+                            Some(bound) => bound == node,
+                            None => {
+                                bindings.insert(var.clone(), node.clone());
+                                added.push(var.clone());
+                                true
+                            }
+                        },
+                    });
+                    if ok {
+                        premises.push(i);
+                        self.solve(rest, bindings, premises, out);
+                        premises.pop();
+                    }
+                    for var in added {
+                        bindings.remove(&var);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn name(&self, node: &Node) -> String {
+        match node {
+            Node::Glyph(i) => self.glyphs[*i].payload.label.clone(),
+            Node::External(reference) => reference.clone(),
+        }
+    }
+
+    pub fn describe(&self, fact: usize) -> String {
+        let fact = &self.facts[fact];
+        format!("{} {} {}", self.name(&fact.subject), fact.relation, self.name(&fact.object))
+    }
+
+    /// Facts that follow from the rules but were not written down
+    pub fn inferred(&self) -> impl Iterator<Item = (usize, &Fact)> {
+        self.facts.iter().enumerate().filter(|(_, fact)| !matches!(fact.derivation, Derivation::Asserted { .. }))
+    }
+
+    /// Facts matching `<subject> <relation> <object>`, where `?` parts match anything
+    pub fn ask(&self, question: &str) -> Result<Vec<usize>, Box<dyn std::error::Error>> {
+        let parts = split_atom(question)?;
+        let matches_part = |part: &str, node: &Node| {
+            part.starts_with('?') || match node {
+                Node::Glyph(i) => {
+                    let glyph = &self.glyphs[*i];
+                    let label = part.strip_prefix("glyph:").unwrap_or(part);
+                    glyph.payload.label == label || glyph.header.id == label
+                }
+                Node::External(reference) => reference == part || reference.strip_prefix("glyph:") == Some(part),
+            }
+        };
+        Ok(self.facts.iter().enumerate()
+            .filter(|(_, fact)| (parts[1].starts_with('?') || fact.relation == parts[1])
+                && matches_part(parts[0], &fact.subject)
+                && matches_part(parts[2], &fact.object))
+            .map(|(i, _)| i)
+            .collect())
+    }
+
+    /// Glyphs whose written relations a fact rests on
+    pub fn asserted_by(&self, fact: usize) -> Vec<usize> {
+        let mut glyphs = Vec::new();
+        let mut pending = vec![fact];
+        while let Some(fact) = pending.pop() {
+            match &self.facts[fact].derivation {
+                Derivation::Asserted { glyph } => glyphs.push(*glyph),
+                Derivation::Symmetric { from } | Derivation::Inverse { from } => pending.push(*from),
+                Derivation::Transitive { first, second } => pending.extend([*first, *second]),
+                Derivation::Rule { premises, .. } => pending.extend(premises),
+            }
+        }
+        glyphs.sort();
+        glyphs.dedup();
+        glyphs
+    }
+
+    /// Derivation of a fact as an indented tree down to asserted relations
+    pub fn explain(&self, fact: usize) -> String {
+        let mut text = String::new();
+        self.explain_into(fact, 0, &mut text);
+        text
+    }
+
+    fn explain_into(&self, fact: usize, depth: usize, text: &mut String) {
+        let indent = "  ".repeat(depth);
+        let premises = match &self.facts[fact].derivation {
+            Derivation::Asserted { glyph } => {
+                text.push_str(&format!("{}{}  [asserted by {}]\n", indent, self.describe(fact), self.glyphs[*glyph].header.id));
+                return;
+            }
+            Derivation::Symmetric { from } => {
+                text.push_str(&format!("{}{}  [symmetric]\n", indent, self.describe(fact)));
+                vec![*from]
+            }
+            Derivation::Inverse { from } => {
+                text.push_str(&format!("{}{}  [inverse]\n", indent, self.describe(fact)));
+                vec![*from]
+            }
+            Derivation::Transitive { first, second } => {
+                text.push_str(&format!("{}{}  [transitive]\n", indent, self.describe(fact)));
+                vec![*first, *second]
+            }
+            Derivation::Rule { name, premises } => {
+                text.push_str(&format!("{}{}  [rule {}]\n", indent, self.describe(fact), name));
+                premises.clone()
+            }
+        };
+        for premise in premises {
+            self.explain_into(premise, depth + 1, text);
+        }
+    }
+}
+
+impl fmt::Display for Derivation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Derivation::Asserted { .. } => write!(f, "asserted"),
+            Derivation::Symmetric { .. } => write!(f, "symmetric"),
+            Derivation::Inverse { .. } => write!(f, "inverse"),
+            Derivation::Transitive { .. } => write!(f, "transitive"),
+            Derivation::Rule { name, .. } => write!(f, "rule {}", name),
+        }
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::glyph;
+
+    const RULES: &str = r#"{
+        relations: {
+            opposite_of: { symmetric: true },
+            part_of: { transitive: true },
+            has_part: { inverse_of: "part_of" },
+        },
+        rules: [
+            { name: "sustains", if: ["?a is star", "?a powers ?b", "?b feeds ?c"], then: "?a sustains ?c" },
+        ],
+    }"#;
+
+    fn corpus() -> Vec<Glyph> {
+        vec![
+            glyph("sun").classes(&["star"])
+                .relation("opposite_of", "glyph:moon")
+                .relation("part_of", "glyph:solar_system")
+                .relation("powers", "glyph:plant")
+                .build(),
+            glyph("moon").build(),
+            glyph("solar_system").relation("part_of", "glyph:milky_way").build(),
+            glyph("plant").relation("feeds", "glyph:deer").build(),
+            glyph("deer").build(),
+        ]
+    }
+
+    #[test]
+    fn test_closure() {
+        let glyphs = corpus();
+        let rules = RuleSet::from_json5(RULES).unwrap();
+        let closure = Closure::compute(&glyphs, &rules).unwrap();
+
+        let inferred: Vec<String> = closure.inferred().map(|(i, _)| closure.describe(i)).collect();
+        for expected in [
+            "moon opposite_of sun",
+            "sun part_of glyph:milky_way",
+            "solar_system has_part sun",
+            "glyph:milky_way has_part sun",
+            "sun sustains deer",
+        ] {
+            assert!(inferred.contains(&expected.to_string()), "missing {}: {:?}", expected, inferred);
+        }
+
+        // The milky way is not in the corpus, but the chain still explains itself
+        let fact = closure.ask("glyph:milky_way has_part ?x").unwrap()
+            .into_iter()
+            .find(|&i| closure.name(&closure.facts[i].object) == "sun")
+            .unwrap();
+        let explanation = closure.explain(fact);
+        assert!(explanation.starts_with("glyph:milky_way has_part sun  [inverse]\n  sun part_of glyph:milky_way  [transitive]\n"));
+        assert_eq!(explanation.matches("[asserted").count(), 2);
+    }
+
+    #[test]
+    fn test_rule_checks() {
+        assert!(RuleSet::from_json5(r#"{ rules: [{ name: "bad", if: ["?a powers ?b"], then: "?a sustains ?c" }] }"#).is_err());
+        assert!(RuleSet::from_json5(r#"{ rules: [{ name: "bad", if: ["?a powers"], then: "?a x ?a" }] }"#).is_err());
+        assert!(RuleSet::from_json5(r#"{ relations: { part_of: { transitiv: true } } }"#).is_err());
+        assert!(RuleSet::from_json5(r#"{ disjoint_classes: [["star"]] }"#).is_err());
+    }
+}
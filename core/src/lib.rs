@@ -4,9 +4,37 @@ extern crate log;
 pub mod commands;
 pub mod error;
 pub mod hazard;
+#[cfg(test)]
+mod test_fixtures;
 
 pub mod glyph_encode;
 pub mod glyph_decode;
+pub mod glyph_sign;
+pub mod glyph_stream;
+pub mod timevector;
+pub mod captions;
+pub mod sigil;
+pub mod qr_chunk;
+pub mod qr_compact;
+pub mod qr_fountain;
+pub mod qr_scan;
+pub mod svg_sanitize;
+pub mod media;
+pub mod glyph_pack;
+pub mod merkle;
+pub mod glyph_diff;
+pub mod glyph_merge;
+pub mod lineage;
+pub mod perspective;
+pub mod glyph_ref;
+pub mod glyphscript;
+pub mod inference;
+pub mod trust;
+pub mod dissonance;
+pub mod consensus;
+pub mod firewall;
+pub mod quarantine;
+pub mod search;
 
 use utils::error::Result;
 
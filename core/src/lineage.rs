@@ -0,0 +1,249 @@
+// Glyph lineage: version chains across re-encodes.
+//
+// `header.id` is fresh for every encode, so it names one revision rather than
+// the concept. The header's lineage block ties revisions together:
+//
+//   lineage: { concept_id: "<uuid of the first revision>",
+//              revision: 3,
+//              parent: "blake3:<semantic hash of revision 2>" }
+//
+// A history walks every glyph of a concept in a store, ordered by revision,
+// and checks that each revision points at the semantic hash of its real
+// predecessor.
+//
+// Lineage lives in the header, outside the semantic hash, so a signature does
+// not cover it: anyone can re-parent or renumber a signed glyph without
+// breaking its signature. A valid chain shows the revisions are consistent
+// with each other, not who linked them.
+
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeSet, HashMap};
+
+use super::glyph_decode::Glyph;
+
+// ============================================================================
+// DATA STRUCTURES
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lineage {
+    /// Stable id shared by every revision of the concept
+    pub concept_id: String,
+    /// 1 for the first revision
+    pub revision: u64,
+    /// Semantic hash of the previous revision
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Revision {
+    pub revision: u64,
+    pub id: String,
+    pub label: String,
+    pub hash: String,
+    pub parent: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct History {
+    pub concept_id: String,
+    pub revisions: Vec<Revision>,
+    pub problems: Vec<String>,
+}
+
+// ============================================================================
+// LINEAGE
+// ============================================================================
+
+impl Lineage {
+    /// Lineage of a first revision
+    pub fn root(id: &str) -> Self {
+        Lineage {
+            concept_id: id.to_string(),
+            revision: 1,
+            parent: None,
+        }
+    }
+
+    /// Lineage of the revision that follows `parent`. Glyphs encoded before
+    /// lineage existed count as revision 1 of a concept named by their id.
+    pub fn next(parent: &Glyph) -> Self {
+        let (concept_id, revision) = match &parent.header.lineage {
+            Some(lineage) => (lineage.concept_id.clone(), lineage.revision),
+            None => (parent.header.id.clone(), 1),
+        };
+        Lineage {
+            concept_id,
+            revision: revision + 1,
+            parent: Some(parent.header.hash.clone()),
+        }
+    }
+}
+
+/// Concept id of a glyph; its header id when it has no lineage
+pub fn concept_of(glyph: &Glyph) -> &str {
+    glyph.header.lineage.as_ref()
+        .map(|lineage| lineage.concept_id.as_str())
+        .unwrap_or(&glyph.header.id)
+}
+
+pub fn revision_of(glyph: &Glyph) -> u64 {
+    glyph.header.lineage.as_ref().map(|lineage| lineage.revision).unwrap_or(1)
+}
+
+/// Indices of the latest revision of every concept in a store
+pub fn latest_revisions(glyphs: &[Glyph]) -> BTreeSet<usize> {
+    let mut latest: HashMap<&str, usize> = HashMap::new();
+    for (i, glyph) in glyphs.iter().enumerate() {
+        let concept = concept_of(glyph);
+        match latest.get(concept) {
+            Some(&other) if revision_of(&glyphs[other]) >= revision_of(glyph) => {}
+            _ => {
+                latest.insert(concept, i);
+            }
+        }
+    }
+    latest.into_values().collect()
+}
+
+// ============================================================================
+// HISTORY
+// ============================================================================
+
+/// Collect and check the revisions of a concept. `id` may be the concept id
+/// or the header id of any one revision.
+pub fn history(glyphs: &[Glyph], id: &str) -> Option<History> {
+    let concept_id = glyphs.iter()
+        .find(|glyph| concept_of(glyph) == id || glyph.header.id == id)
+        .map(|glyph| concept_of(glyph).to_string())?;
+
+    let mut members: Vec<&Glyph> = glyphs.iter().filter(|glyph| concept_of(glyph) == concept_id).collect();
+    members.sort_by(|a, b| revision_of(a).cmp(&revision_of(b)).then(a.header.created_at.cmp(&b.header.created_at)));
+    members.dedup_by(|a, b| a.header.id == b.header.id && a.header.hash == b.header.hash);
+
+    let mut problems = Vec::new();
+    let mut expected = 1;
+    for (index, glyph) in members.iter().enumerate() {
+        let revision = revision_of(glyph);
+        let parent = glyph.header.lineage.as_ref().and_then(|lineage| lineage.parent.as_ref());
+
+        if glyph.header.hash != glyph.semantic_hash() {
+            problems.push(format!("revision {} ({}): header hash does not match its payload", revision, glyph.header.id));
+        }
+
+        if revision > expected {
+            problems.push(format!("revisions {} to {} are missing", expected, revision - 1));
+        } else if revision < expected {
+            problems.push(format!("revision {} ({}): competing version of the same revision", revision, glyph.header.id));
+        }
+        expected = revision + 1;
+
+        match parent {
+            None if revision > 1 => {
+                problems.push(format!("revision {} ({}): no parent hash", revision, glyph.header.id));
+            }
+            Some(parent) if revision == 1 => {
+                problems.push(format!("revision 1 ({}): first revision has a parent {}", glyph.header.id, parent));
+            }
+            Some(parent) => {
+                let predecessors: Vec<&&Glyph> = members[..index].iter()
+                    .filter(|candidate| revision_of(candidate) == revision - 1)
+                    .collect();
+                let real = predecessors.iter().any(|candidate| candidate.semantic_hash() == *parent);
+                if !predecessors.is_empty() && !real {
+                    problems.push(format!(
+                        "revision {} ({}): parent {} is not the hash of revision {}",
+                        revision, glyph.header.id, parent, revision - 1
+                    ));
+                }
+            }
+            None => {}
+        }
+    }
+
+    let revisions = members.iter()
+        .map(|glyph| Revision {
+            revision: revision_of(glyph),
+            id: glyph.header.id.clone(),
+            label: glyph.payload.label.clone(),
+            hash: glyph.header.hash.clone(),
+            parent: glyph.header.lineage.as_ref().and_then(|lineage| lineage.parent.clone()),
+            created_at: glyph.header.created_at,
+        })
+        .collect();
+
+    Some(History { concept_id, revisions, problems })
+}
+
+impl History {
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("History of {} ({} revision(s))\n", self.concept_id, self.revisions.len());
+        for revision in &self.revisions {
+            text.push_str(&format!(
+                "  r{:<3} {}  {}  {}  {}\n",
+                revision.revision,
+                revision.created_at.format("%Y-%m-%d %H:%M:%S"),
+                revision.hash,
+                revision.label,
+                revision.id
+            ));
+        }
+        text
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::glyph;
+
+    fn revision(label: &str, parent: Option<&Glyph>) -> Glyph {
+        let mut glyph = glyph(label).encode();
+        if let Some(parent) = parent {
+            glyph.header.lineage = Some(Lineage::next(parent));
+        }
+        Glyph::from_cbor(&glyph.to_cbor().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_history_chain() {
+        let first = revision("sun", None);
+        let second = revision("sol", Some(&first));
+        let third = revision("helios", Some(&second));
+        let concept = first.header.lineage.as_ref().unwrap().concept_id.clone();
+
+        let store = vec![third, first, second];
+        let history = history(&store, &concept).unwrap();
+        assert!(history.is_valid(), "{:?}", history.problems);
+        let labels: Vec<&str> = history.revisions.iter().map(|r| r.label.as_str()).collect();
+        assert_eq!(labels, vec!["sun", "sol", "helios"]);
+
+        // Any revision's header id finds the same concept
+        assert_eq!(super::history(&store, &store[0].header.id).unwrap().concept_id, concept);
+        assert!(super::history(&store, "unknown").is_none());
+    }
+
+    #[test]
+    fn test_history_detects_wrong_parent() {
+        let first = revision("sun", None);
+        let second = revision("sol", Some(&first));
+        let mut forged = revision("helios", Some(&second));
+        forged.header.lineage.as_mut().unwrap().parent = Some(first.header.hash.clone());
+        let concept = first.header.lineage.as_ref().unwrap().concept_id.clone();
+
+        let history = history(&[first, second, forged], &concept).unwrap();
+        assert_eq!(history.problems.len(), 1);
+        assert!(history.problems[0].contains("is not the hash of revision 2"));
+    }
+}
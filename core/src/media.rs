@@ -0,0 +1,293 @@
+// Media attachments for the audio and visual blocks.
+//
+// `audio.file` / `visual.file` only name a file. An attachment pins that file
+// by content so a swapped `sun.wav` is detected:
+//
+//   media: { name: "sun.wav", media_type: "audio/wav",
+//            digest: "blake3:<hex>", size: 48000, data: <bytes>? }
+//
+// Embedded attachments carry the bytes inline (a CBOR byte string in .glyph
+// files, base64 in JSON/YAML); referenced attachments carry only the digest
+// and size, and the file travels next to the glyph. Digests are part of the
+// semantic hash, so the signature covers them: swapping both a file and its
+// recorded digest breaks the hash instead of passing verification.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+// ============================================================================
+// DATA STRUCTURES
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MediaMode {
+    /// Store the bytes inside the glyph
+    Embed,
+    /// Store only the digest and size
+    Reference,
+}
+
+impl FromStr for MediaMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "embed" | "inline" => Ok(MediaMode::Embed),
+            "reference" | "ref" => Ok(MediaMode::Reference),
+            _ => Err(format!("Unsupported media mode: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MediaAttachment {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    pub digest: String,
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "inline_bytes")]
+    pub data: Option<Vec<u8>>,
+}
+
+// ============================================================================
+// ATTACHING
+// ============================================================================
+
+pub fn digest(data: &[u8]) -> String {
+    format!("blake3:{}", blake3::hash(data).to_hex())
+}
+
+impl MediaAttachment {
+    pub fn new(name: &str, data: Vec<u8>, mode: MediaMode) -> Self {
+        MediaAttachment {
+            name: name.to_string(),
+            media_type: guess_media_type(name).map(|t| t.to_string()),
+            digest: digest(&data),
+            size: data.len() as u64,
+            data: (mode == MediaMode::Embed).then_some(data),
+        }
+    }
+
+    /// Attach the file at `path`, recorded under its file name
+    pub fn from_file(path: &Path, mode: MediaMode) -> Result<Self, Box<dyn std::error::Error>> {
+        let name = path.file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| format!("Invalid media path: {}", path.display()))?;
+        let data = fs::read(path).map_err(|e| format!("Cannot read media {}: {}", path.display(), e))?;
+        Ok(Self::new(name, data, mode))
+    }
+
+    pub fn is_embedded(&self) -> bool {
+        self.data.is_some()
+    }
+
+    /// File name safe to join onto an output directory
+    pub fn file_name(&self) -> Result<&str, Box<dyn std::error::Error>> {
+        Path::new(&self.name)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .filter(|n| !n.is_empty())
+            .ok_or_else(|| format!("Invalid media name: {}", self.name).into())
+    }
+
+    fn check(&self, data: &[u8]) -> Result<(), String> {
+        if data.len() as u64 != self.size {
+            return Err(format!("{}: size {} does not match recorded {}", self.name, data.len(), self.size));
+        }
+        let actual = digest(data);
+        if actual != self.digest {
+            return Err(format!("{}: digest {} does not match recorded {}", self.name, actual, self.digest));
+        }
+        Ok(())
+    }
+
+    /// Check embedded bytes, or the referenced file in `media_dir`
+    pub fn verify(&self, media_dir: Option<&Path>) -> Result<(), String> {
+        if let Some(data) = &self.data {
+            return self.check(data);
+        }
+
+        let dir = media_dir.ok_or_else(|| format!("{}: referenced media needs a media directory", self.name))?;
+        let name = self.file_name().map_err(|e| e.to_string())?;
+        let data = fs::read(dir.join(name)).map_err(|e| format!("{}: {}", self.name, e))?;
+        self.check(&data)
+    }
+
+    /// Write embedded bytes into `dir`, verifying them first
+    pub fn extract(&self, dir: &Path) -> Result<std::path::PathBuf, Box<dyn std::error::Error>> {
+        let data = self.data.as_ref()
+            .ok_or_else(|| format!("{} is referenced, not embedded", self.name))?;
+        self.check(data)?;
+
+        fs::create_dir_all(dir)?;
+        let path = dir.join(self.file_name()?);
+        fs::write(&path, data)?;
+        Ok(path)
+    }
+}
+
+fn guess_media_type(name: &str) -> Option<&'static str> {
+    let extension = Path::new(name).extension()?.to_str()?.to_lowercase();
+    Some(match extension.as_str() {
+        "wav" => "audio/wav",
+        "mp3" => "audio/mpeg",
+        "ogg" | "oga" => "audio/ogg",
+        "flac" => "audio/flac",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        _ => return None,
+    })
+}
+
+// Bytes as a CBOR byte string, or base64 text for human-readable formats
+mod inline_bytes {
+    use super::*;
+    use serde::de::{self, Visitor};
+    use std::fmt;
+
+    pub fn serialize<S: Serializer>(data: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match data {
+            None => serializer.serialize_none(),
+            Some(data) if serializer.is_human_readable() => serializer.serialize_some(
+                &base64::Engine::encode(&base64::engine::general_purpose::STANDARD, data),
+            ),
+            Some(data) => serializer.serialize_some(&Bytes(data)),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error> {
+        deserializer.deserialize_option(OptionVisitor)
+    }
+
+    struct Bytes<'a>(&'a [u8]);
+
+    impl Serialize for Bytes<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(self.0)
+        }
+    }
+
+    struct OptionVisitor;
+
+    impl<'de> Visitor<'de> for OptionVisitor {
+        type Value = Option<Vec<u8>>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("optional media bytes")
+        }
+
+        fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+            deserializer.deserialize_any(BytesVisitor).map(Some)
+        }
+    }
+
+    struct BytesVisitor;
+
+    impl<'de> Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a byte string or base64 text")
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+            Ok(v.to_vec())
+        }
+
+        fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+            Ok(v)
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+            base64::Engine::decode(&base64::engine::general_purpose::STANDARD, v).map_err(E::custom)
+        }
+
+        fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(byte) = seq.next_element::<u8>()? {
+                bytes.push(byte);
+            }
+            Ok(bytes)
+        }
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedded_media_roundtrip() {
+        let attachment = MediaAttachment::new("sun.wav", b"RIFF....WAVE".to_vec(), MediaMode::Embed);
+        assert_eq!(attachment.media_type.as_deref(), Some("audio/wav"));
+
+        let mut cbor = Vec::new();
+        ciborium::into_writer(&attachment, &mut cbor).unwrap();
+        // Stored as a CBOR byte string (major type 2), not an array of integers
+        assert!(cbor.windows(13).any(|w| w[0] == 0x4c && &w[1..] == b"RIFF....WAVE"));
+        let decoded: MediaAttachment = ciborium::from_reader(cbor.as_slice()).unwrap();
+        assert_eq!(decoded, attachment);
+
+        let json = serde_json::to_string(&attachment).unwrap();
+        assert!(json.contains("\"data\":\"UklGRi4uLi5XQVZF\""));
+        assert_eq!(serde_json::from_str::<MediaAttachment>(&json).unwrap(), attachment);
+
+        let mut tampered = decoded;
+        tampered.data = Some(b"RIFF....EVIL".to_vec());
+        assert!(tampered.verify(None).unwrap_err().contains("digest"));
+
+        // Replacing the digest along with the bytes changes the semantic hash
+        let mut glyph = crate::glyph_encode::Glyph::from_json5(r#"{
+            "label": "sun", "aliases": [], "classes": [],
+            "namespace": { "provision": "glyph:core", "contextual_overlays": [] },
+            "truth_mode": { "type": "empirical", "confidence": 0.9, "verified_by": [], "conflicts": [] },
+            "audio": { "file": "sun.wav", "phonetic": null },
+            "visual": { "svg": null, "sigil": null, "style": null },
+            "relations": [], "grounding": { "wikidata": null, "sensors": [] }
+        }"#).unwrap();
+        let bare = glyph.header.hash.clone();
+        glyph.payload.audio.media = Some(attachment);
+        glyph.header.hash = glyph.semantic_hash();
+        assert_ne!(glyph.header.hash, bare);
+
+        let mut decoded = crate::glyph_decode::Glyph::from_cbor(&glyph.to_cbor().unwrap()).unwrap();
+        assert_eq!(decoded.semantic_hash(), glyph.header.hash);
+        decoded.payload.audio.media = Some(MediaAttachment::new("sun.wav", b"RIFF....EVIL".to_vec(), MediaMode::Embed));
+        assert!(decoded.payload.audio.media.as_ref().unwrap().verify(None).is_ok());
+        assert_ne!(decoded.semantic_hash(), decoded.header.hash);
+    }
+
+    #[test]
+    fn test_referenced_media_detects_swap() {
+        let dir = std::env::temp_dir().join(format!("glyph-media-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("sun.wav"), b"original").unwrap();
+
+        let attachment = MediaAttachment::from_file(&dir.join("sun.wav"), MediaMode::Reference).unwrap();
+        assert!(!attachment.is_embedded());
+        assert!(attachment.verify(Some(&dir)).is_ok());
+        assert!(attachment.extract(&dir).is_err());
+
+        fs::write(dir.join("sun.wav"), b"swapped!").unwrap();
+        assert!(attachment.verify(Some(&dir)).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,235 @@
+// Merkle commitments over a glyph corpus.
+//
+// The leaves are the corpus' semantic hashes, sorted and deduplicated, so the
+// root depends only on which glyphs are published and not on file order.
+// Hashing reuses the blake3 "blake3:<hex>" form of compute_hash with domain
+// prefixes that keep leaves and inner nodes apart:
+//
+//   leaf = H("leaf:" + semantic hash)
+//   node = H("node:" + left + "|" + right)
+//
+// A node without a sibling is carried up unchanged. An inclusion proof lists
+// the sibling hashes from the leaf to the root, so another agent can check
+// that a glyph belongs to a signed root without the rest of the corpus.
+
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+
+use super::glyph_encode;
+use super::glyph_sign;
+
+// ============================================================================
+// DATA STRUCTURES
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// A sibling hash on the path to the root, and which side it sits on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProofStep {
+    pub side: Side,
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InclusionProof {
+    /// Semantic hash of the glyph being proven
+    pub leaf: String,
+    pub index: usize,
+    pub leaf_count: usize,
+    pub path: Vec<ProofStep>,
+    pub root: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedRoot {
+    pub magic: String,
+    pub version: String,
+    pub root: String,
+    pub leaf_count: usize,
+    pub signature: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MerkleTree {
+    leaves: Vec<String>,
+    // levels[0] holds the leaf hashes, the last level holds the root
+    levels: Vec<Vec<String>>,
+}
+
+fn leaf_hash(semantic_hash: &str) -> String {
+    glyph_encode::Glyph::compute_hash(&format!("leaf:{}", semantic_hash))
+}
+
+fn node_hash(left: &str, right: &str) -> String {
+    glyph_encode::Glyph::compute_hash(&format!("node:{}|{}", left, right))
+}
+
+// ============================================================================
+// TREE
+// ============================================================================
+
+impl MerkleTree {
+    pub fn build<I, S>(semantic_hashes: I) -> Result<Self, Box<dyn std::error::Error>>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut leaves: Vec<String> = semantic_hashes.into_iter().map(Into::into).collect();
+        leaves.sort();
+        leaves.dedup();
+        if leaves.is_empty() {
+            return Err("Cannot build a Merkle tree over an empty corpus".into());
+        }
+
+        let mut levels = vec![leaves.iter().map(|h| leaf_hash(h)).collect::<Vec<_>>()];
+        while levels.last().unwrap().len() > 1 {
+            let next = levels.last().unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(left, right),
+                    [single] => single.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+
+        Ok(MerkleTree { leaves, levels })
+    }
+
+    pub fn root(&self) -> &str {
+        &self.levels.last().unwrap()[0]
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    pub fn proof(&self, semantic_hash: &str) -> Option<InclusionProof> {
+        let index = self.leaves.binary_search_by(|leaf| leaf.as_str().cmp(semantic_hash)).ok()?;
+
+        let mut path = Vec::new();
+        let mut position = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = position ^ 1;
+            if let Some(hash) = level.get(sibling) {
+                let side = if sibling < position { Side::Left } else { Side::Right };
+                path.push(ProofStep { side, hash: hash.clone() });
+            }
+            position /= 2;
+        }
+
+        Some(InclusionProof {
+            leaf: semantic_hash.to_string(),
+            index,
+            leaf_count: self.leaves.len(),
+            path,
+            root: self.root().to_string(),
+        })
+    }
+
+    pub fn signed_root(&self, private_key: Option<&[u8]>) -> Result<SignedRoot, Box<dyn std::error::Error>> {
+        let signature = match private_key {
+            Some(private_key) => Some(glyph_sign::sign_hash(private_key, self.root())?),
+            None => None,
+        };
+
+        Ok(SignedRoot {
+            magic: "MERKLEROOT".to_string(),
+            version: "0.1".to_string(),
+            root: self.root().to_string(),
+            leaf_count: self.leaves.len(),
+            signature,
+            created_at: Utc::now(),
+        })
+    }
+}
+
+// ============================================================================
+// VERIFICATION
+// ============================================================================
+
+impl InclusionProof {
+    /// Recompute the root from the leaf and its path
+    pub fn computed_root(&self) -> String {
+        self.path.iter().fold(leaf_hash(&self.leaf), |hash, step| match step.side {
+            Side::Left => node_hash(&step.hash, &hash),
+            Side::Right => node_hash(&hash, &step.hash),
+        })
+    }
+
+    /// Whether the proof ties its leaf to `root`
+    pub fn verify(&self, root: &str) -> bool {
+        self.root == root && self.computed_root() == root
+    }
+}
+
+impl SignedRoot {
+    pub fn verify_signature(&self, public_key: &[u8]) -> Result<bool, Box<dyn std::error::Error>> {
+        if let Some(signature_str) = &self.signature {
+            glyph_sign::verify_hash(public_key, &self.root, signature_str)
+        } else {
+            Ok(false) // No signature to verify
+        }
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hashes(n: usize) -> Vec<String> {
+        (0..n).map(|i| glyph_encode::Glyph::compute_hash(&format!("glyph {}", i))).collect()
+    }
+
+    #[test]
+    fn test_every_leaf_has_a_valid_proof() {
+        for n in [1, 2, 5, 8, 13] {
+            let tree = MerkleTree::build(hashes(n)).unwrap();
+            for hash in hashes(n) {
+                let proof = tree.proof(&hash).unwrap();
+                assert!(proof.verify(tree.root()), "leaf count {}", n);
+            }
+        }
+
+        // Input order and duplicates do not change the root
+        let mut shuffled = hashes(5);
+        shuffled.reverse();
+        shuffled.push(shuffled[0].clone());
+        assert_eq!(MerkleTree::build(shuffled).unwrap().root(), MerkleTree::build(hashes(5)).unwrap().root());
+    }
+
+    #[test]
+    fn test_forged_proofs_fail() {
+        let tree = MerkleTree::build(hashes(6)).unwrap();
+        let mut proof = tree.proof(&hashes(6)[2]).unwrap();
+        assert!(tree.proof("blake3:unknown").is_none());
+
+        proof.leaf = glyph_encode::Glyph::compute_hash("intruder");
+        assert!(!proof.verify(tree.root()));
+
+        let other = MerkleTree::build(hashes(7)).unwrap();
+        let proof = other.proof(&hashes(7)[0]).unwrap();
+        assert!(!proof.verify(tree.root()));
+
+        let key = [3u8; 32];
+        let public_key = ed25519_dalek::SigningKey::from_bytes(&key).verifying_key().to_bytes();
+        let signed = tree.signed_root(Some(&key)).unwrap();
+        assert!(signed.verify_signature(&public_key).unwrap());
+    }
+}
@@ -0,0 +1,113 @@
+// Perspective views over namespace contextual overlays.
+//
+// An overlay gives one agent's label and truth mode for a glyph:
+//
+//   { agent: "glyphos:mythology", label: "Ra", truth_mode: "mythic" }
+//
+// Relations can be scoped to a perspective by prefixing their type with the
+// agent; unscoped relations are shared by every perspective:
+//
+//   { type: "glyphos:mythology/rides", target: "glyph:solar_barque" }
+//
+// A view takes the overlay label and truth mode, keeps shared relations and
+// the perspective's own scoped relations (prefix stripped), and drops the
+// relations scoped to other perspectives. The canonical label stays available
+// as the first alias. The header is untouched: the hash and signature still
+// describe the canonical glyph.
+
+use super::glyph_decode::{Glyph, Relation};
+
+// ============================================================================
+// PERSPECTIVES
+// ============================================================================
+
+/// Split a scoped relation type into its agent and relation type
+pub fn scope(relation_type: &str) -> Option<(&str, &str)> {
+    relation_type.split_once('/')
+        .filter(|(agent, relation)| agent.contains(':') && !relation.is_empty())
+}
+
+/// Agents with an overlay or scoped relations on this glyph
+pub fn perspectives(glyph: &Glyph) -> Vec<&str> {
+    let mut agents: Vec<&str> = glyph.payload.namespace.contextual_overlays.iter()
+        .map(|overlay| overlay.agent.as_str())
+        .chain(glyph.payload.relations.iter().filter_map(|relation| scope(&relation.r#type).map(|(agent, _)| agent)))
+        .collect();
+    agents.sort();
+    agents.dedup();
+    agents
+}
+
+/// The glyph as seen from `perspective`
+pub fn view(glyph: &Glyph, perspective: &str) -> Result<Glyph, Box<dyn std::error::Error>> {
+    let mut view: Glyph = serde_json::from_value(serde_json::to_value(glyph)?)?;
+
+    let overlay = glyph.payload.namespace.contextual_overlays.iter()
+        .find(|overlay| overlay.agent == perspective);
+    if let Some(overlay) = overlay {
+        if overlay.label != glyph.payload.label {
+            view.payload.aliases.retain(|alias| *alias != overlay.label && *alias != glyph.payload.label);
+            view.payload.aliases.insert(0, glyph.payload.label.clone());
+            view.payload.label = overlay.label.clone();
+        }
+        view.payload.truth_mode.r#type = overlay.truth_mode.clone();
+    }
+
+    view.payload.relations = glyph.payload.relations.iter()
+        .filter_map(|relation| match scope(&relation.r#type) {
+            None => Some(Relation { r#type: relation.r#type.clone(), target: relation.target.clone() }),
+            Some((agent, relation_type)) if agent == perspective => {
+                Some(Relation { r#type: relation_type.to_string(), target: relation.target.clone() })
+            }
+            Some(_) => None,
+        })
+        .collect();
+
+    Ok(view)
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::glyph;
+
+    fn sun() -> Glyph {
+        glyph("sun").aliases(&["sol"]).classes(&["star"]).provision("glyph:sun").confidence(0.98)
+            .overlay("glyphos:mythology", "Ra", "mythic")
+            .relation("opposite_of", "glyph:moon")
+            .relation("glyphos:mythology/rides", "glyph:solar_barque")
+            .relation("glyphos:astrology/rules", "glyph:leo")
+            .wikidata("Q525")
+            .build()
+    }
+
+    #[test]
+    fn test_overlay_view() {
+        let glyph = sun();
+        assert_eq!(perspectives(&glyph), vec!["glyphos:astrology", "glyphos:mythology"]);
+
+        let view = glyph.view("glyphos:mythology").unwrap();
+        assert_eq!(view.payload.label, "Ra");
+        assert_eq!(view.payload.aliases, vec!["sun", "sol"]);
+        assert_eq!(view.payload.truth_mode.r#type, "mythic");
+        let relations: Vec<(&str, &str)> = view.payload.relations.iter()
+            .map(|r| (r.r#type.as_str(), r.target.as_str()))
+            .collect();
+        assert_eq!(relations, vec![("opposite_of", "glyph:moon"), ("rides", "glyph:solar_barque")]);
+        assert_eq!(view.header.hash, glyph.header.hash);
+    }
+
+    #[test]
+    fn test_perspective_without_overlay() {
+        let glyph = sun();
+        let view = glyph.view("agent:nasa").unwrap();
+        assert_eq!(view.payload.label, "sun");
+        assert_eq!(view.payload.truth_mode.r#type, "empirical");
+        // Only shared relations remain
+        assert_eq!(view.payload.relations.len(), 1);
+    }
+}
@@ -0,0 +1,311 @@
+// Multi-code QR transport for glyphs that exceed a single symbol.
+//
+// The base64 text of a glyph's CBOR is split into N pieces and each piece is
+// carried by its own QR code with a small header:
+//
+//   GQ1:<seq>/<total>:<blake3 hex of the full CBOR>:<base64 piece>
+//
+// `seq` is 1-based. The content hash ties the pieces of one glyph together
+// and verifies the reassembled bytes. ':' is not in the base64 alphabet, so
+// chunked payloads are never confused with single-code payloads.
+//
+// Compact chunks split the compact transport body instead (see qr_compact)
+// and spell the hash in upper case so the whole payload stays alphanumeric:
+//
+//   GQ2:<seq>/<total>:<BLAKE3 HEX>:<base45 piece>
+
+use std::collections::{BTreeMap, HashMap};
+
+use super::qr_compact;
+
+pub const CHUNK_PREFIX: &str = "GQ1:";
+pub const COMPACT_CHUNK_PREFIX: &str = "GQ2:";
+
+// Payload characters per code; comfortably scannable at EC level M
+pub const DEFAULT_CHUNK_SIZE: usize = 1200;
+
+// Most codes in one sequence. Codes are scanned one at a time, so a few
+// thousand is already more than anyone will hold up to a camera; larger
+// glyphs need a larger chunk size, and split refuses to go past this
+pub const MAX_CHUNKS: usize = 4096;
+
+// ============================================================================
+// DATA STRUCTURES
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub seq: usize,
+    pub total: usize,
+    pub hash: String,
+    pub data: String,
+    pub compact: bool,
+}
+
+impl Chunk {
+    pub fn to_payload(&self) -> String {
+        if self.compact {
+            format!("{}{}/{}:{}:{}", COMPACT_CHUNK_PREFIX, self.seq, self.total, self.hash.to_uppercase(), self.data)
+        } else {
+            format!("{}{}/{}:{}:{}", CHUNK_PREFIX, self.seq, self.total, self.hash, self.data)
+        }
+    }
+
+    /// Parse a QR payload. Returns `None` when the payload is not a chunk.
+    pub fn parse(payload: &str) -> Option<Result<Self, Box<dyn std::error::Error>>> {
+        if let Some(rest) = payload.strip_prefix(CHUNK_PREFIX) {
+            return Some(Self::parse_fields(rest, false));
+        }
+        let rest = payload.strip_prefix(COMPACT_CHUNK_PREFIX)?;
+        Some(Self::parse_fields(rest, true))
+    }
+
+    fn parse_fields(rest: &str, compact: bool) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut fields = rest.splitn(3, ':');
+        let position = fields.next().ok_or("Missing chunk position")?;
+        let hash = fields.next().ok_or("Missing chunk hash")?;
+        let data = fields.next().ok_or("Missing chunk data")?;
+
+        let (seq, total) = position.split_once('/').ok_or("Invalid chunk position")?;
+        let (seq, total): (usize, usize) = (seq.parse()?, total.parse()?);
+        if total > MAX_CHUNKS {
+            return Err(format!("Chunk total {} exceeds the limit of {}", total, MAX_CHUNKS).into());
+        }
+        if seq == 0 || seq > total {
+            return Err(format!("Chunk {} out of range 1..{}", seq, total).into());
+        }
+        if hash.len() != 64 || hex::decode(hash).is_err() {
+            return Err("Invalid chunk content hash".into());
+        }
+
+        Ok(Chunk { seq, total, hash: hash.to_lowercase(), data: data.to_string(), compact })
+    }
+}
+
+// ============================================================================
+// SPLITTING
+// ============================================================================
+
+pub fn content_hash(cbor_data: &[u8]) -> String {
+    blake3::hash(cbor_data).to_hex().to_string()
+}
+
+/// Split CBOR bytes into chunk payloads of at most `chunk_size` data characters
+pub fn split(cbor_data: &[u8], chunk_size: usize) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let encoded = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, cbor_data);
+    split_encoded(&encoded, &content_hash(cbor_data), chunk_size, false)
+}
+
+/// Like `split`, but over the compact (deflate + Base45) transport body
+pub fn split_compact(cbor_data: &[u8], chunk_size: usize) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let encoded = qr_compact::encode_body(cbor_data)?;
+    split_encoded(&encoded, &content_hash(cbor_data), chunk_size, true)
+}
+
+fn split_encoded(encoded: &str, hash: &str, chunk_size: usize, compact: bool) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    if chunk_size == 0 {
+        return Err("Chunk size must be at least 1".into());
+    }
+
+    // Both transports are pure ASCII, so byte chunks are character chunks
+    let pieces: Vec<&[u8]> = encoded.as_bytes().chunks(chunk_size).collect();
+    let total = pieces.len();
+    if total > MAX_CHUNKS {
+        return Err(format!(
+            "Glyph needs {} QR codes at {} characters each, more than the limit of {}; use a larger chunk size",
+            total, chunk_size, MAX_CHUNKS
+        ).into());
+    }
+
+    Ok(pieces.iter()
+        .enumerate()
+        .map(|(index, piece)| Chunk {
+            seq: index + 1,
+            total,
+            hash: hash.to_string(),
+            data: String::from_utf8_lossy(piece).to_string(),
+            compact,
+        }.to_payload())
+        .collect())
+}
+
+// ============================================================================
+// REASSEMBLY
+// ============================================================================
+
+#[derive(Debug, Default)]
+pub struct Reassembler {
+    // content hash -> (total, compact, seq -> data)
+    groups: HashMap<String, (usize, bool, BTreeMap<usize, String>)>,
+}
+
+impl Reassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a chunk; duplicates (the same code seen twice) are ignored
+    pub fn add(&mut self, chunk: Chunk) -> Result<(), Box<dyn std::error::Error>> {
+        let (total, compact, pieces) = self.groups
+            .entry(chunk.hash.clone())
+            .or_insert_with(|| (chunk.total, chunk.compact, BTreeMap::new()));
+
+        if *compact != chunk.compact {
+            return Err(format!("Chunk {} mixes transport encodings", chunk.seq).into());
+        }
+        if *total != chunk.total {
+            return Err(format!("Chunk {} disagrees on the total count ({} vs {})", chunk.seq, chunk.total, total).into());
+        }
+        if let Some(existing) = pieces.get(&chunk.seq) {
+            if *existing != chunk.data {
+                return Err(format!("Conflicting data for chunk {}/{}", chunk.seq, chunk.total).into());
+            }
+        }
+        pieces.insert(chunk.seq, chunk.data);
+
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Ranges of sequence numbers (inclusive) still missing for each glyph
+    /// seen so far
+    pub fn missing(&self) -> Vec<(String, Vec<(usize, usize)>)> {
+        self.groups.iter()
+            .map(|(hash, (total, _, pieces))| {
+                // Walk the gaps between received pieces rather than every
+                // sequence number, so a claimed total costs nothing
+                let mut missing = Vec::new();
+                let mut next = 1;
+                for &seq in pieces.keys().chain(std::iter::once(&(total + 1))) {
+                    if seq > next {
+                        missing.push((next, seq - 1));
+                    }
+                    next = seq + 1;
+                }
+                (hash.clone(), missing)
+            })
+            .filter(|(_, missing)| !missing.is_empty())
+            .collect()
+    }
+
+    /// Reassemble and verify every complete glyph, returning its CBOR bytes
+    pub fn complete(&self) -> Vec<Result<Vec<u8>, Box<dyn std::error::Error>>> {
+        self.groups.iter()
+            .filter(|(_, (total, _, pieces))| pieces.len() == *total)
+            .map(|(hash, (_, compact, pieces))| Self::assemble(hash, *compact, pieces))
+            .collect()
+    }
+
+    fn assemble(hash: &str, compact: bool, pieces: &BTreeMap<usize, String>) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let encoded: String = pieces.values().map(|piece| piece.as_str()).collect();
+        let cbor_data = if compact {
+            qr_compact::decode_body(&encoded)?
+        } else {
+            base64::Engine::decode(&base64::engine::general_purpose::STANDARD, encoded)?
+        };
+
+        if content_hash(&cbor_data) != hash {
+            return Err(format!("Reassembled content does not match hash {}", hash).into());
+        }
+
+        Ok(cbor_data)
+    }
+
+    /// Reassemble exactly one glyph, failing with the missing chunks otherwise
+    pub fn finish(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut complete = self.complete();
+        match complete.len() {
+            1 => complete.remove(0),
+            0 => {
+                let missing: Vec<String> = self.missing().iter()
+                    .map(|(hash, ranges)| format!("{}: missing {}", &hash[..12], format_ranges(ranges)))
+                    .collect();
+                Err(format!("Incomplete chunked QR glyph ({})", missing.join("; ")).into())
+            }
+            n => Err(format!("QR codes contain {} different glyphs", n).into()),
+        }
+    }
+}
+
+/// Render inclusive ranges as "1-3,7"
+pub fn format_ranges(ranges: &[(usize, usize)]) -> String {
+    ranges.iter()
+        .map(|&(first, last)| if first == last { first.to_string() } else { format!("{}-{}", first, last) })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_and_reassemble_out_of_order() {
+        let data: Vec<u8> = (0..=255u8).cycle().take(3000).collect();
+        let payloads = split(&data, 500).unwrap();
+        assert!(payloads.len() > 1);
+        assert!(payloads[0].starts_with("GQ1:1/"));
+
+        let mut reassembler = Reassembler::new();
+        for payload in payloads.iter().rev().chain(payloads.iter().take(1)) {
+            reassembler.add(Chunk::parse(payload).unwrap().unwrap()).unwrap();
+        }
+        assert_eq!(reassembler.finish().unwrap(), data);
+    }
+
+    #[test]
+    fn test_missing_and_tampered_chunks() {
+        let data = vec![42u8; 2000];
+        let payloads = split(&data, 400).unwrap();
+
+        let mut reassembler = Reassembler::new();
+        for payload in payloads.iter().skip(1) {
+            reassembler.add(Chunk::parse(payload).unwrap().unwrap()).unwrap();
+        }
+        assert!(reassembler.finish().unwrap_err().to_string().contains("missing 1"));
+
+        let mut tampered = Chunk::parse(&payloads[0]).unwrap().unwrap();
+        tampered.data = tampered.data.replace('q', "r");
+        reassembler.add(tampered).unwrap();
+        assert!(reassembler.finish().unwrap_err().to_string().contains("does not match"));
+
+        assert!(Chunk::parse("not-a-chunk").is_none());
+        assert!(Chunk::parse("GQ1:3/2:abc:data").unwrap().is_err());
+        let hash = content_hash(&data);
+        assert!(Chunk::parse(&format!("GQ1:1/18446744073709551615:{}:data", hash)).unwrap().is_err());
+        assert!(Chunk::parse(&format!("GQ1:1/{}:{}:data", MAX_CHUNKS + 1, hash)).unwrap().is_err());
+        assert!(split(&vec![0u8; MAX_CHUNKS], 1).is_err());
+
+        // A claimed total is reported as ranges, not enumerated
+        let mut reassembler = Reassembler::new();
+        for seq in [2, 3, 7] {
+            let payload = format!("GQ1:{}/{}:{}:x", seq, MAX_CHUNKS, hash);
+            reassembler.add(Chunk::parse(&payload).unwrap().unwrap()).unwrap();
+        }
+        let missing = reassembler.missing();
+        assert_eq!(missing[0].1, vec![(1, 1), (4, 6), (8, MAX_CHUNKS)]);
+        assert_eq!(format_ranges(&missing[0].1), format!("1,4-6,8-{}", MAX_CHUNKS));
+    }
+
+    #[test]
+    fn test_compact_chunks_are_alphanumeric() {
+        let data: Vec<u8> = b"glyph ".iter().cycle().take(4000).copied().collect();
+        let payloads = split_compact(&data, 20).unwrap();
+        assert!(payloads.len() > 1);
+        assert!(payloads.iter().all(|p| p.starts_with(COMPACT_CHUNK_PREFIX)
+            && p.bytes().all(|c| c.is_ascii_digit() || c.is_ascii_uppercase() || b" $%*+-./:".contains(&c))));
+
+        let mut reassembler = Reassembler::new();
+        for payload in &payloads {
+            reassembler.add(Chunk::parse(payload).unwrap().unwrap()).unwrap();
+        }
+        assert_eq!(reassembler.finish().unwrap(), data);
+    }
+}
@@ -0,0 +1,157 @@
+// Compact QR transport encoding.
+//
+// Standard QR payloads are base64 text in byte mode, which spends 8 bits per
+// character on 6 bits of data. The compact transport deflates the CBOR and
+// encodes it with Base45 (RFC 9285), whose alphabet is exactly the QR
+// alphanumeric set, so the symbol uses 5.5 bits per character instead:
+//
+//   GC1:<base45(deflate(cbor))>
+//
+// The prefix is itself alphanumeric and lets decoders tell compact payloads
+// apart from legacy base64 ones.
+
+use flate2::read::{DeflateDecoder, DeflateEncoder};
+use flate2::Compression;
+use std::io::Read;
+
+pub const COMPACT_PREFIX: &str = "GC1:";
+
+const BASE45_ALPHABET: &[u8; 45] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+// Upper bound on inflated glyph size, so a crafted code cannot exhaust memory
+pub const MAX_INFLATED_LEN: u64 = 16 * 1024 * 1024;
+
+// ============================================================================
+// COMPRESSION
+// ============================================================================
+
+pub fn compress(data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut compressed = Vec::new();
+    DeflateEncoder::new(data, Compression::best()).read_to_end(&mut compressed)?;
+    Ok(compressed)
+}
+
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut inflated = Vec::new();
+    DeflateDecoder::new(data)
+        .take(MAX_INFLATED_LEN + 1)
+        .read_to_end(&mut inflated)?;
+
+    if inflated.len() as u64 > MAX_INFLATED_LEN {
+        return Err("Compact QR payload inflates beyond the size limit".into());
+    }
+    Ok(inflated)
+}
+
+// ============================================================================
+// BASE45
+// ============================================================================
+
+pub fn base45_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len() * 3 / 2 + 2);
+
+    for pair in data.chunks(2) {
+        let (mut n, digits) = match pair {
+            [a, b] => ((*a as u32) * 256 + *b as u32, 3),
+            [a] => (*a as u32, 2),
+            _ => unreachable!(),
+        };
+        for _ in 0..digits {
+            out.push(BASE45_ALPHABET[(n % 45) as usize] as char);
+            n /= 45;
+        }
+    }
+
+    out
+}
+
+pub fn base45_decode(text: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let values = text.bytes()
+        .map(|c| BASE45_ALPHABET.iter()
+            .position(|a| *a == c)
+            .map(|v| v as u32)
+            .ok_or_else(|| format!("Invalid Base45 character: {:?}", c as char)))
+        .collect::<Result<Vec<u32>, String>>()?;
+
+    let mut out = Vec::with_capacity(values.len() * 2 / 3);
+    for group in values.chunks(3) {
+        match group {
+            [c, d, e] => {
+                let n = c + d * 45 + e * 45 * 45;
+                if n > 0xFFFF {
+                    return Err("Invalid Base45 group".into());
+                }
+                out.push((n >> 8) as u8);
+                out.push((n & 0xFF) as u8);
+            }
+            [c, d] => {
+                let n = c + d * 45;
+                if n > 0xFF {
+                    return Err("Invalid Base45 group".into());
+                }
+                out.push(n as u8);
+            }
+            _ => return Err("Truncated Base45 data".into()),
+        }
+    }
+
+    Ok(out)
+}
+
+// ============================================================================
+// TRANSPORT
+// ============================================================================
+
+/// Deflate and Base45-encode CBOR bytes, without the version prefix
+pub fn encode_body(cbor_data: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(base45_encode(&compress(cbor_data)?))
+}
+
+pub fn decode_body(body: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    decompress(&base45_decode(body)?)
+}
+
+/// Encode CBOR bytes as a single compact QR payload
+pub fn encode(cbor_data: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(format!("{}{}", COMPACT_PREFIX, encode_body(cbor_data)?))
+}
+
+/// Decode a compact QR payload. Returns `None` when the payload uses another
+/// transport encoding.
+pub fn decode(payload: &str) -> Option<Result<Vec<u8>, Box<dyn std::error::Error>>> {
+    payload.strip_prefix(COMPACT_PREFIX).map(decode_body)
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base45_rfc_vectors() {
+        // Test vectors from RFC 9285
+        assert_eq!(base45_encode(b"AB"), "BB8");
+        assert_eq!(base45_encode(b"Hello!!"), "%69 VD92EX0");
+        assert_eq!(base45_encode(b"base-45"), "UJCLQE7W581");
+        assert_eq!(base45_decode("QED8WEX0").unwrap(), b"ietf!");
+        assert!(base45_decode("GGW").is_err());
+        assert!(base45_decode("abc").is_err());
+    }
+
+    #[test]
+    fn test_compact_roundtrip() {
+        let data = br#"{"label":"sun","aliases":["star","solar core"],"classes":["astronomy"]}"#.repeat(20);
+        let payload = encode(&data).unwrap();
+        assert!(payload.starts_with(COMPACT_PREFIX));
+        assert!(payload.bytes().all(|c| BASE45_ALPHABET.contains(&c)));
+
+        let base64_len = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &data).len();
+        assert!(payload.len() < base64_len);
+
+        assert_eq!(decode(&payload).unwrap().unwrap(), data);
+        assert!(decode("AAAA").is_none());
+    }
+}
@@ -0,0 +1,562 @@
+// Fountain-coded QR sequences.
+//
+// A glyph shown in video frames cannot rely on every frame being seen, so the
+// CBOR bytes are LT-coded (a Luby transform fountain code): they are cut into
+// K source blocks and the encoder emits an endless stream of droplets, each
+// the XOR of a pseudo-random subset of blocks. Any sufficient subset of
+// droplets, in any order, rebuilds the glyph. The first K droplets are
+// systematic (droplet i carries block i alone), so a clean capture decodes
+// with exactly K frames.
+//
+// Every droplet is one QR frame with an alphanumeric payload:
+//
+//   GF1:<K>/<length>/<block size>:<BLAKE3 HEX>:<seed>:<base45 block>
+//
+// The seed is all a decoder needs to re-derive which blocks were combined.
+
+use image::{AnimationDecoder, DynamicImage, Frame, GrayImage, ImageBuffer, Luma};
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use qrcode::QrCode;
+use std::collections::{BTreeMap, HashSet};
+use std::io::{Read, Write};
+
+use super::glyph_encode::{self, QrOptions};
+use super::qr_chunk;
+use super::qr_compact;
+
+pub const FOUNTAIN_PREFIX: &str = "GF1:";
+
+pub const DEFAULT_BLOCK_SIZE: usize = 256;
+
+// Most source blocks in one stream; each needs at least one frame, so the
+// same bound as a chunked QR sequence applies
+pub const MAX_BLOCKS: usize = qr_chunk::MAX_CHUNKS;
+
+// Robust soliton parameters
+const SOLITON_C: f64 = 0.1;
+const SOLITON_DELTA: f64 = 0.05;
+
+// ============================================================================
+// DATA STRUCTURES
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Droplet {
+    pub k: usize,
+    pub length: usize,
+    pub block_size: usize,
+    pub hash: String,
+    pub seed: u32,
+    pub data: Vec<u8>,
+}
+
+impl Droplet {
+    pub fn to_payload(&self) -> String {
+        format!(
+            "{}{}/{}/{}:{}:{}:{}",
+            FOUNTAIN_PREFIX,
+            self.k,
+            self.length,
+            self.block_size,
+            self.hash.to_uppercase(),
+            self.seed,
+            qr_compact::base45_encode(&self.data)
+        )
+    }
+
+    /// Parse a QR payload. Returns `None` when the payload is not a droplet.
+    pub fn parse(payload: &str) -> Option<Result<Self, Box<dyn std::error::Error>>> {
+        let rest = payload.strip_prefix(FOUNTAIN_PREFIX)?;
+        Some(Self::parse_fields(rest))
+    }
+
+    fn parse_fields(rest: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut fields = rest.splitn(4, ':');
+        let params = fields.next().ok_or("Missing droplet parameters")?;
+        let hash = fields.next().ok_or("Missing droplet hash")?;
+        let seed = fields.next().ok_or("Missing droplet seed")?;
+        let data = fields.next().ok_or("Missing droplet data")?;
+
+        let params: Vec<usize> = params.split('/')
+            .map(|p| p.parse::<usize>())
+            .collect::<Result<_, _>>()?;
+        let [k, length, block_size] = params[..] else {
+            return Err("Invalid droplet parameters".into());
+        };
+        if k == 0 || block_size == 0 || k != length.div_ceil(block_size) {
+            return Err("Inconsistent droplet parameters".into());
+        }
+        if length as u64 > qr_compact::MAX_INFLATED_LEN {
+            return Err(format!("Droplet length {} exceeds the limit of {}", length, qr_compact::MAX_INFLATED_LEN).into());
+        }
+        // Decoders allocate per block, so a tiny block size over a large
+        // length must not pass
+        if k > MAX_BLOCKS {
+            return Err(format!("Droplet block count {} exceeds the limit of {}", k, MAX_BLOCKS).into());
+        }
+        if hash.len() != 64 || hex::decode(hash).is_err() {
+            return Err("Invalid droplet content hash".into());
+        }
+
+        let data = qr_compact::base45_decode(data)?;
+        if data.len() != block_size {
+            return Err("Droplet data does not match the block size".into());
+        }
+
+        Ok(Droplet {
+            k,
+            length,
+            block_size,
+            hash: hash.to_lowercase(),
+            seed: seed.parse()?,
+            data,
+        })
+    }
+}
+
+// SplitMix64; small, fast and identical on every platform
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Cumulative robust soliton distribution over degrees 1..=k
+fn soliton_cdf(k: usize) -> Vec<f64> {
+    let kf = k as f64;
+    let r = SOLITON_C * (kf / SOLITON_DELTA).ln() * kf.sqrt();
+    let spike = if r > 0.0 { (kf / r).floor() as usize } else { 0 };
+
+    let mut weights: Vec<f64> = (1..=k)
+        .map(|d| {
+            let ideal = if d == 1 { 1.0 / kf } else { 1.0 / (d as f64 * (d as f64 - 1.0)) };
+            let robust = if spike == 0 || d > spike {
+                0.0
+            } else if d < spike {
+                r / (d as f64 * kf)
+            } else {
+                r * (r / SOLITON_DELTA).ln() / kf
+            };
+            ideal + robust.max(0.0)
+        })
+        .collect();
+
+    let total: f64 = weights.iter().sum();
+    let mut acc = 0.0;
+    for weight in weights.iter_mut() {
+        acc += *weight / total;
+        *weight = acc;
+    }
+    weights
+}
+
+/// Source block indices combined into the droplet with this seed
+fn block_indices(seed: u32, k: usize, cdf: &[f64]) -> Vec<usize> {
+    if (seed as usize) < k {
+        return vec![seed as usize];
+    }
+
+    let mut rng = Rng(((k as u64) << 32) | seed as u64);
+    let sample = rng.next_f64();
+    let degree = cdf.iter().position(|p| sample <= *p).unwrap_or(k - 1) + 1;
+
+    let mut chosen = HashSet::with_capacity(degree);
+    let mut indices = Vec::with_capacity(degree);
+    while indices.len() < degree {
+        let index = (rng.next_u64() % k as u64) as usize;
+        if chosen.insert(index) {
+            indices.push(index);
+        }
+    }
+    indices
+}
+
+// ============================================================================
+// ENCODER
+// ============================================================================
+
+pub struct FountainEncoder {
+    blocks: Vec<Vec<u8>>,
+    length: usize,
+    block_size: usize,
+    hash: String,
+    cdf: Vec<f64>,
+    next_seed: u32,
+}
+
+impl FountainEncoder {
+    pub fn new(data: &[u8], block_size: usize) -> Result<Self, Box<dyn std::error::Error>> {
+        if data.is_empty() {
+            return Err("Cannot fountain-code empty data".into());
+        }
+        if block_size == 0 {
+            return Err("Block size must be at least 1".into());
+        }
+
+        let blocks: Vec<Vec<u8>> = data.chunks(block_size)
+            .map(|chunk| {
+                let mut block = chunk.to_vec();
+                block.resize(block_size, 0);
+                block
+            })
+            .collect();
+        if blocks.len() > MAX_BLOCKS {
+            return Err(format!(
+                "Data needs {} blocks of {} bytes, more than the limit of {}; use a larger block size",
+                blocks.len(), block_size, MAX_BLOCKS
+            ).into());
+        }
+
+        Ok(FountainEncoder {
+            cdf: soliton_cdf(blocks.len()),
+            blocks,
+            length: data.len(),
+            block_size,
+            hash: qr_chunk::content_hash(data),
+            next_seed: 0,
+        })
+    }
+
+    /// Number of source blocks; at least this many droplets are needed
+    pub fn k(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn droplet(&self, seed: u32) -> Droplet {
+        let mut data = vec![0u8; self.block_size];
+        for index in block_indices(seed, self.k(), &self.cdf) {
+            for (byte, source) in data.iter_mut().zip(&self.blocks[index]) {
+                *byte ^= source;
+            }
+        }
+
+        Droplet {
+            k: self.k(),
+            length: self.length,
+            block_size: self.block_size,
+            hash: self.hash.clone(),
+            seed,
+            data,
+        }
+    }
+}
+
+impl Iterator for FountainEncoder {
+    type Item = Droplet;
+
+    // Endless: there is always another droplet
+    fn next(&mut self) -> Option<Droplet> {
+        let droplet = self.droplet(self.next_seed);
+        self.next_seed = self.next_seed.wrapping_add(1);
+        Some(droplet)
+    }
+}
+
+// ============================================================================
+// DECODER
+// ============================================================================
+
+pub struct FountainDecoder {
+    k: usize,
+    length: usize,
+    block_size: usize,
+    hash: String,
+    cdf: Vec<f64>,
+    blocks: Vec<Option<Vec<u8>>>,
+    recovered: usize,
+    pending: Vec<(Vec<usize>, Vec<u8>)>,
+    seen: HashSet<u32>,
+}
+
+impl FountainDecoder {
+    pub fn new(first: &Droplet) -> Self {
+        FountainDecoder {
+            k: first.k,
+            length: first.length,
+            block_size: first.block_size,
+            hash: first.hash.clone(),
+            cdf: soliton_cdf(first.k),
+            blocks: vec![None; first.k],
+            recovered: 0,
+            pending: Vec::new(),
+            seen: HashSet::new(),
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.recovered == self.k
+    }
+
+    /// Blocks recovered so far, out of K
+    pub fn progress(&self) -> (usize, usize) {
+        (self.recovered, self.k)
+    }
+
+    /// Feed a droplet; returns whether the data is now complete
+    pub fn add(&mut self, droplet: &Droplet) -> Result<bool, Box<dyn std::error::Error>> {
+        if droplet.hash != self.hash
+            || droplet.k != self.k
+            || droplet.length != self.length
+            || droplet.block_size != self.block_size
+        {
+            return Err("Droplet belongs to a different fountain stream".into());
+        }
+
+        if self.is_complete() || !self.seen.insert(droplet.seed) {
+            return Ok(self.is_complete());
+        }
+
+        let indices = block_indices(droplet.seed, self.k, &self.cdf);
+        self.pending.push((indices, droplet.data.clone()));
+        self.peel();
+
+        Ok(self.is_complete())
+    }
+
+    // Belief propagation: strip known blocks from pending droplets and
+    // release every droplet that is left with a single unknown block
+    fn peel(&mut self) {
+        loop {
+            let mut progressed = false;
+
+            for (indices, data) in self.pending.iter_mut() {
+                indices.retain(|index| match &self.blocks[*index] {
+                    Some(block) => {
+                        for (byte, known) in data.iter_mut().zip(block) {
+                            *byte ^= known;
+                        }
+                        false
+                    }
+                    None => true,
+                });
+
+                if let [index] = indices[..] {
+                    self.blocks[index] = Some(std::mem::take(data));
+                    self.recovered += 1;
+                    indices.clear();
+                    progressed = true;
+                }
+            }
+
+            self.pending.retain(|(indices, _)| !indices.is_empty());
+            if !progressed {
+                break;
+            }
+        }
+    }
+
+    /// Concatenate the recovered blocks and verify them against the hash
+    pub fn finish(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if !self.is_complete() {
+            return Err(format!(
+                "Not enough fountain frames: recovered {} of {} blocks",
+                self.recovered, self.k
+            ).into());
+        }
+
+        let mut data: Vec<u8> = self.blocks.iter().flatten().flatten().copied().collect();
+        data.truncate(self.length);
+
+        if qr_chunk::content_hash(&data) != self.hash {
+            return Err("Fountain-decoded content does not match its hash".into());
+        }
+        Ok(data)
+    }
+}
+
+// ============================================================================
+// FRAMES
+// ============================================================================
+
+/// Render `count` droplets as equally sized QR frames
+pub fn render_frames(
+    encoder: &mut FountainEncoder,
+    count: usize,
+    options: &QrOptions,
+) -> Result<Vec<GrayImage>, Box<dyn std::error::Error>> {
+    let images = encoder.take(count)
+        .map(|droplet| {
+            let code = QrCode::with_error_correction_level(droplet.to_payload().as_bytes(), options.ec_level.into())?;
+            glyph_encode::render_qr_image(&code, options)
+        })
+        .collect::<Result<Vec<GrayImage>, Box<dyn std::error::Error>>>()?;
+
+    // Seed digits can bump the QR version, so center every frame on a canvas
+    // of the largest size to keep the animation steady
+    let size = images.iter().map(|image| image.width()).max().unwrap_or(0);
+    Ok(images.into_iter()
+        .map(|image| {
+            let mut canvas = ImageBuffer::from_pixel(size, size, Luma([255u8]));
+            let offset = ((size - image.width()) / 2) as i64;
+            image::imageops::overlay(&mut canvas, &image, offset, offset);
+            canvas
+        })
+        .collect())
+}
+
+pub fn write_gif<W: Write>(frames: &[GrayImage], frame_delay_ms: u32, writer: W) -> Result<(), Box<dyn std::error::Error>> {
+    let mut encoder = GifEncoder::new_with_speed(writer, 30);
+    encoder.set_repeat(Repeat::Infinite)?;
+
+    encoder.encode_frames(frames.iter().map(|frame| {
+        let rgba = DynamicImage::ImageLuma8(frame.clone()).to_rgba8();
+        Frame::from_parts(rgba, 0, 0, image::Delay::from_numer_denom_ms(frame_delay_ms, 1))
+    }))?;
+    Ok(())
+}
+
+/// Decode every frame of an image file: all frames of a GIF, or the single
+/// frame of any other format
+pub fn load_frames<R: Read + std::io::BufRead + std::io::Seek>(reader: R) -> Result<Vec<DynamicImage>, Box<dyn std::error::Error>> {
+    let reader = image::io::Reader::new(reader).with_guessed_format()?;
+
+    if reader.format() == Some(image::ImageFormat::Gif) {
+        let decoder = GifDecoder::new(reader.into_inner())?;
+        return Ok(decoder.into_frames()
+            .collect_frames()?
+            .into_iter()
+            .map(|frame| DynamicImage::ImageRgba8(frame.into_buffer()))
+            .collect());
+    }
+
+    Ok(vec![reader.decode()?])
+}
+
+/// Feed every fountain droplet found in `frames` to a decoder for its stream,
+/// stopping as soon as one stream is complete. Droplets that do not parse or
+/// do not fit their stream are skipped, and why is added to `diagnostics`.
+pub fn decode_frames<I>(frames: I, diagnostics: &mut Vec<String>) -> Result<Vec<u8>, Box<dyn std::error::Error>>
+where
+    I: IntoIterator<Item = DynamicImage>,
+{
+    let decoder = bardecoder::default_decoder();
+    let mut fountains: BTreeMap<String, FountainDecoder> = BTreeMap::new();
+
+    for (index, frame) in frames.into_iter().enumerate() {
+        for payload in decoder.decode(&frame).into_iter().filter_map(|r| r.ok()) {
+            let Some(droplet) = Droplet::parse(&payload) else {
+                continue;
+            };
+            let added = droplet.and_then(|droplet| {
+                let complete = fountains.entry(droplet.hash.clone())
+                    .or_insert_with(|| FountainDecoder::new(&droplet))
+                    .add(&droplet)?;
+                Ok((complete, droplet.hash))
+            });
+            match added {
+                Ok((true, hash)) => match fountains[&hash].finish() {
+                    Ok(data) => return Ok(data),
+                    // Corrupt blocks; start this stream over
+                    Err(e) => {
+                        diagnostics.push(format!("frame {}: {}", index + 1, e));
+                        fountains.remove(&hash);
+                    }
+                },
+                Ok((false, _)) => {}
+                Err(e) => diagnostics.push(format!("frame {}: {}", index + 1, e)),
+            }
+        }
+    }
+
+    // Nothing completed; report on the stream that got furthest
+    match fountains.values().max_by_key(|state| state.progress().0) {
+        Some(state) => state.finish(),
+        None => Err("No fountain-coded QR frames found".into()),
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_data() -> Vec<u8> {
+        (0..3000u32).map(|n| (n * 7 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_decode_from_any_sufficient_subset() {
+        let data = test_data();
+        let mut encoder = FountainEncoder::new(&data, 200).unwrap();
+        assert_eq!(encoder.k(), 15);
+
+        // Drop every third frame, including systematic ones
+        let droplets: Vec<Droplet> = encoder.by_ref()
+            .take(200)
+            .enumerate()
+            .filter(|(n, _)| n % 3 != 0)
+            .map(|(_, d)| Droplet::parse(&d.to_payload()).unwrap().unwrap())
+            .collect();
+
+        let mut decoder = FountainDecoder::new(&droplets[0]);
+        let mut used = 0;
+        for droplet in &droplets {
+            used += 1;
+            if decoder.add(droplet).unwrap() {
+                break;
+            }
+        }
+        assert!(used < droplets.len());
+        assert_eq!(decoder.finish().unwrap(), data);
+    }
+
+    #[test]
+    fn test_incomplete_and_foreign_droplets() {
+        let data = test_data();
+        let encoder = FountainEncoder::new(&data, 200).unwrap();
+
+        let mut decoder = FountainDecoder::new(&encoder.droplet(0));
+        decoder.add(&encoder.droplet(0)).unwrap();
+        assert_eq!(decoder.progress(), (1, 15));
+        assert!(decoder.finish().is_err());
+
+        let other = FountainEncoder::new(&data[..100], 200).unwrap();
+        assert!(decoder.add(&other.droplet(0)).is_err());
+        assert!(Droplet::parse("GQ1:1/1:x:y").is_none());
+
+        // Sizes beyond any glyph are refused before anything is allocated
+        let hash = qr_chunk::content_hash(&data).to_uppercase();
+        assert!(Droplet::parse(&format!("GF1:1000000000000/1000000000000/1:{}:0:00", hash)).unwrap().is_err());
+        assert!(Droplet::parse(&format!("GF1:16777216/16777216/1:{}:0:00", hash)).unwrap().is_err());
+        assert!(Droplet::parse(&format!("GF1:{0}/{0}/1:{1}:0:00", MAX_BLOCKS, hash)).unwrap().is_ok());
+        assert!(FountainEncoder::new(&vec![0u8; MAX_BLOCKS + 1], 1).is_err());
+    }
+
+    #[test]
+    fn test_decode_frames_skips_bad_droplets() {
+        let data = test_data();
+        let encoder = FountainEncoder::new(&data[..120], 60).unwrap();
+        let foreign = FountainEncoder::new(&data[..90], 30).unwrap();
+        let frame = |payload: String| {
+            let code = QrCode::new(payload.as_bytes()).unwrap();
+            DynamicImage::ImageLuma8(glyph_encode::render_qr_image(&code, &QrOptions::default()).unwrap())
+        };
+
+        let mut corrupt = encoder.droplet(0).to_payload();
+        corrupt.replace_range(4..5, "9");
+        let frames = vec![
+            frame(foreign.droplet(0).to_payload()),
+            frame(corrupt),
+            frame(encoder.droplet(0).to_payload()),
+            frame(encoder.droplet(1).to_payload()),
+        ];
+
+        let mut diagnostics = Vec::new();
+        assert_eq!(decode_frames(frames, &mut diagnostics).unwrap(), data[..120]);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].starts_with("frame 2:"));
+    }
+}
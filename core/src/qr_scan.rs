@@ -0,0 +1,414 @@
+// Batch QR scanning.
+//
+// Photos and video frames may show several glyph codes at once, show the same
+// glyph in many frames, or show nothing useful at all. The scanner decodes
+// every code in every image, reassembles chunked and fountain-coded glyphs
+// across images, drops
+// duplicate glyphs (identical CBOR) while remembering where each was seen,
+// and keeps a per-image report explaining anything it could not use:
+//
+//   photo_01.jpg  2 codes  sun, moon
+//   photo_02.jpg  no QR code found
+//   photo_03.jpg  1 code   CBOR error: unexpected end of input
+
+use image::DynamicImage;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use super::glyph_decode::Glyph;
+use super::qr_chunk;
+use super::qr_compact;
+use super::qr_fountain;
+
+// ============================================================================
+// DATA STRUCTURES
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+pub enum Diagnostic {
+    /// The file could not be read or is not an image
+    Unreadable(String),
+    /// No QR code was detected in the image
+    NoCode,
+    /// A code was detected but could not be decoded
+    UnreadableCode(String),
+    Base64(String),
+    Compact(String),
+    Chunk(String),
+    Cbor(String),
+    /// The header hash does not match the hash recomputed from the payload
+    HashMismatch { expected: String, actual: String },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnostic::Unreadable(e) => write!(f, "unreadable image: {}", e),
+            Diagnostic::NoCode => write!(f, "no QR code found"),
+            Diagnostic::UnreadableCode(e) => write!(f, "undecodable QR code: {}", e),
+            Diagnostic::Base64(e) => write!(f, "base64 error: {}", e),
+            Diagnostic::Compact(e) => write!(f, "compact payload error: {}", e),
+            Diagnostic::Chunk(e) => write!(f, "chunk error: {}", e),
+            Diagnostic::Cbor(e) => write!(f, "CBOR error: {}", e),
+            Diagnostic::HashMismatch { expected, actual } => {
+                write!(f, "hash mismatch: header {} but payload hashes to {}", expected, actual)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageReport {
+    pub source: String,
+    pub codes: usize,
+    /// Labels of the glyphs decoded from single codes in this image
+    pub glyphs: Vec<String>,
+    /// Chunks and fountain droplets contributed to glyphs split across codes
+    pub chunks: usize,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Debug)]
+pub struct ScannedGlyph {
+    pub glyph: Glyph,
+    pub cbor: Vec<u8>,
+    /// Images the glyph (or one of its chunks) was seen in
+    pub sources: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct ScanResult {
+    /// Distinct glyphs in the order they were first seen
+    pub glyphs: Vec<ScannedGlyph>,
+    pub reports: Vec<ImageReport>,
+    /// Problems that belong to no single image, such as incomplete chunk sets
+    pub errors: Vec<String>,
+}
+
+impl ScanResult {
+    /// The first thing that went wrong, for callers that expect one glyph
+    pub fn first_problem(&self) -> Option<String> {
+        self.errors.first().cloned().or_else(|| {
+            self.reports.iter()
+                .flat_map(|report| report.diagnostics.iter().map(move |d| format!("{}: {}", report.source, d)))
+                .next()
+        })
+    }
+}
+
+// ============================================================================
+// SCANNING
+// ============================================================================
+
+pub struct Scanner {
+    decoder: bardecoder::Decoder<DynamicImage, image::GrayImage, String>,
+    reassembler: qr_chunk::Reassembler,
+    // content hash -> fountain stream
+    fountains: HashMap<String, qr_fountain::FountainDecoder>,
+    // chunk or fountain content hash -> images its pieces were seen in
+    chunk_sources: HashMap<String, Vec<String>>,
+    glyphs: Vec<ScannedGlyph>,
+    // CBOR content hash -> index into `glyphs`
+    seen: HashMap<String, usize>,
+    reports: Vec<ImageReport>,
+    errors: Vec<String>,
+}
+
+impl Default for Scanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scanner {
+    pub fn new() -> Self {
+        Scanner {
+            decoder: bardecoder::default_decoder(),
+            reassembler: qr_chunk::Reassembler::new(),
+            fountains: HashMap::new(),
+            chunk_sources: HashMap::new(),
+            glyphs: Vec::new(),
+            seen: HashMap::new(),
+            reports: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Scan a file, or every file in a directory (sorted by name). Every frame
+    /// of an animated GIF is scanned as `<file>#<frame>`.
+    pub fn scan_path(&mut self, path: &Path) {
+        if path.is_dir() {
+            let mut entries: Vec<_> = match fs::read_dir(path) {
+                Ok(entries) => entries.filter_map(|e| e.ok().map(|e| e.path())).filter(|p| p.is_file()).collect(),
+                Err(e) => {
+                    self.unreadable(&path.display().to_string(), e.to_string());
+                    return;
+                }
+            };
+            entries.sort();
+            for entry in entries {
+                self.scan_path(&entry);
+            }
+            return;
+        }
+
+        match fs::read(path) {
+            Ok(data) => self.scan_bytes(&path.display().to_string(), &data),
+            Err(e) => self.unreadable(&path.display().to_string(), e.to_string()),
+        }
+    }
+
+    /// Scan an encoded image (PNG, JPEG, GIF, ...) held in memory
+    pub fn scan_bytes(&mut self, source: &str, data: &[u8]) {
+        match qr_fountain::load_frames(std::io::Cursor::new(data)) {
+            Ok(frames) if frames.len() == 1 => self.scan_image(source, &frames[0]),
+            Ok(frames) => {
+                for (index, frame) in frames.iter().enumerate() {
+                    self.scan_image(&format!("{}#{}", source, index), frame);
+                }
+            }
+            Err(e) => self.unreadable(source, e.to_string()),
+        }
+    }
+
+    pub fn scan_image(&mut self, source: &str, image: &DynamicImage) {
+        let mut report = ImageReport {
+            source: source.to_string(),
+            codes: 0,
+            glyphs: Vec::new(),
+            chunks: 0,
+            diagnostics: Vec::new(),
+        };
+
+        // With several codes in view the detector also proposes finder pattern
+        // triples spanning different codes; those fail to decode, so detection
+        // failures only matter when nothing in the image decoded at all
+        let (payloads, failures): (Vec<_>, Vec<_>) = self.decoder.decode(image)
+            .into_iter()
+            .partition(|result| result.is_ok());
+
+        if payloads.is_empty() {
+            report.diagnostics.push(match failures.into_iter().next() {
+                Some(Err(e)) => Diagnostic::UnreadableCode(e.to_string()),
+                _ => Diagnostic::NoCode,
+            });
+        }
+
+        // Triples spanning several codes can also decode, to nothing, and one
+        // code can be found through more than one triple
+        let mut seen = Vec::new();
+        for payload in payloads.into_iter().flatten() {
+            if payload.is_empty() || seen.contains(&payload) {
+                continue;
+            }
+            seen.push(payload.clone());
+            report.codes += 1;
+            self.scan_payload(&payload, &mut report);
+        }
+
+        self.reports.push(report);
+    }
+
+    fn scan_payload(&mut self, payload: &str, report: &mut ImageReport) {
+        if let Some(chunk) = qr_chunk::Chunk::parse(payload) {
+            let added = chunk.and_then(|chunk| {
+                let hash = chunk.hash.clone();
+                self.reassembler.add(chunk)?;
+                Ok(hash)
+            });
+            match added {
+                Ok(hash) => self.piece_seen(hash, report),
+                Err(e) => report.diagnostics.push(Diagnostic::Chunk(e.to_string())),
+            }
+            return;
+        }
+
+        if let Some(droplet) = qr_fountain::Droplet::parse(payload) {
+            let added = droplet.and_then(|droplet| {
+                self.fountains
+                    .entry(droplet.hash.clone())
+                    .or_insert_with(|| qr_fountain::FountainDecoder::new(&droplet))
+                    .add(&droplet)?;
+                Ok(droplet.hash)
+            });
+            match added {
+                Ok(hash) => self.piece_seen(hash, report),
+                Err(e) => report.diagnostics.push(Diagnostic::Chunk(e.to_string())),
+            }
+            return;
+        }
+
+        // Compact payloads announce themselves with a prefix, anything else
+        // is legacy base64
+        let cbor_data = match qr_compact::decode(payload) {
+            Some(decoded) => decoded.map_err(|e| Diagnostic::Compact(e.to_string())),
+            None => base64::Engine::decode(&base64::engine::general_purpose::STANDARD, payload)
+                .map_err(|e| Diagnostic::Base64(e.to_string())),
+        };
+
+        match cbor_data.and_then(|cbor_data| self.accept(cbor_data, std::slice::from_ref(&report.source))) {
+            Ok(label) => report.glyphs.push(label),
+            Err(diagnostic) => report.diagnostics.push(diagnostic),
+        }
+    }
+
+    fn piece_seen(&mut self, hash: String, report: &mut ImageReport) {
+        let sources = self.chunk_sources.entry(hash).or_default();
+        if !sources.contains(&report.source) {
+            sources.push(report.source.clone());
+        }
+        report.chunks += 1;
+    }
+
+    // Decode and check a glyph, merging it with an identical one seen before
+    fn accept(&mut self, cbor_data: Vec<u8>, sources: &[String]) -> Result<String, Diagnostic> {
+        let key = qr_chunk::content_hash(&cbor_data);
+        if let Some(&index) = self.seen.get(&key) {
+            let existing = &mut self.glyphs[index];
+            for source in sources {
+                if !existing.sources.contains(source) {
+                    existing.sources.push(source.clone());
+                }
+            }
+            return Ok(existing.glyph.payload.label.clone());
+        }
+
+        let glyph = Glyph::from_cbor(&cbor_data).map_err(|e| Diagnostic::Cbor(e.to_string()))?;
+        let actual = glyph.semantic_hash();
+        if actual != glyph.header.hash {
+            return Err(Diagnostic::HashMismatch { expected: glyph.header.hash.clone(), actual });
+        }
+
+        let label = glyph.payload.label.clone();
+        self.seen.insert(key, self.glyphs.len());
+        self.glyphs.push(ScannedGlyph { glyph, cbor: cbor_data, sources: sources.to_vec() });
+        Ok(label)
+    }
+
+    fn unreadable(&mut self, source: &str, error: String) {
+        self.reports.push(ImageReport {
+            source: source.to_string(),
+            codes: 0,
+            glyphs: Vec::new(),
+            chunks: 0,
+            diagnostics: vec![Diagnostic::Unreadable(error)],
+        });
+    }
+
+    /// Reassemble chunked and fountain-coded glyphs and return everything found
+    pub fn finish(mut self) -> ScanResult {
+        let reassembler = std::mem::take(&mut self.reassembler);
+        let fountains = std::mem::take(&mut self.fountains);
+
+        let fountain_data = fountains.values()
+            .filter(|fountain| fountain.is_complete())
+            .map(|fountain| fountain.finish());
+
+        for cbor_data in reassembler.complete().into_iter().chain(fountain_data) {
+            let result = cbor_data
+                .map_err(|e| Diagnostic::Chunk(e.to_string()))
+                .and_then(|cbor_data| {
+                    let sources = self.chunk_sources.get(&qr_chunk::content_hash(&cbor_data)).cloned().unwrap_or_default();
+                    self.accept(cbor_data, &sources)
+                });
+            if let Err(diagnostic) = result {
+                self.errors.push(format!("split glyph: {}", diagnostic));
+            }
+        }
+
+        for (hash, missing) in reassembler.missing() {
+            self.errors.push(format!(
+                "incomplete chunked glyph {}: missing {}",
+                &hash[..12],
+                qr_chunk::format_ranges(&missing)
+            ));
+        }
+
+        for (hash, fountain) in &fountains {
+            let (recovered, total) = fountain.progress();
+            if recovered < total {
+                self.errors.push(format!(
+                    "incomplete fountain-coded glyph {}: recovered {} of {} blocks",
+                    &hash[..12], recovered, total
+                ));
+            }
+        }
+
+        ScanResult { glyphs: self.glyphs, reports: self.reports, errors: self.errors }
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glyph_encode::{self, QrOptions};
+    use image::{GrayImage, ImageBuffer, Luma};
+    use qrcode::QrCode;
+
+    const SAMPLE: &str = r#"{
+        "label": "sun",
+        "aliases": [],
+        "classes": ["astronomy"],
+        "namespace": { "provision": "glyph:sun", "contextual_overlays": [] },
+        "truth_mode": { "type": "empirical", "confidence": 0.9, "verified_by": [], "conflicts": [] },
+        "audio": { "file": null, "phonetic": null },
+        "visual": { "svg": null, "sigil": null, "style": null },
+        "relations": [],
+        "grounding": { "wikidata": null, "sensors": [] }
+    }"#;
+
+    fn qr_image(payload: &str) -> GrayImage {
+        let code = QrCode::new(payload.as_bytes()).unwrap();
+        let options = QrOptions { scale: 4, ..QrOptions::default() };
+        glyph_encode::render_qr_image(&code, &options).unwrap()
+    }
+
+    #[test]
+    fn test_scan_dedupes_and_reports() {
+        let glyph = glyph_encode::Glyph::from_json5(SAMPLE).unwrap();
+        let payload = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, glyph.to_cbor().unwrap());
+
+        // Two codes side by side in one image: the glyph and a non-glyph
+        let first = qr_image(&payload);
+        let second = qr_image("aGVsbG8=");
+        let mut both: GrayImage = ImageBuffer::from_pixel(first.width() + second.width(), first.height().max(second.height()), Luma([255u8]));
+        image::imageops::overlay(&mut both, &first, 0, 0);
+        image::imageops::overlay(&mut both, &second, first.width() as i64, 0);
+
+        let mut scanner = Scanner::new();
+        scanner.scan_image("both.png", &DynamicImage::ImageLuma8(both));
+        scanner.scan_image("again.png", &DynamicImage::ImageLuma8(first));
+        scanner.scan_image("blank.png", &DynamicImage::ImageLuma8(ImageBuffer::from_pixel(64, 64, Luma([255u8]))));
+        let result = scanner.finish();
+
+        assert_eq!(result.glyphs.len(), 1);
+        assert_eq!(result.glyphs[0].sources, vec!["both.png", "again.png"]);
+        assert_eq!(result.reports[0].codes, 2);
+        assert_eq!(result.reports[0].glyphs, vec!["sun"]);
+        assert!(matches!(result.reports[0].diagnostics[..], [Diagnostic::Cbor(_)]));
+        assert_eq!(result.reports[2].diagnostics, vec![Diagnostic::NoCode]);
+    }
+
+    #[test]
+    fn test_scan_detects_hash_mismatch() {
+        let mut glyph = glyph_encode::Glyph::from_json5(SAMPLE).unwrap();
+        glyph.payload.label = "moon".to_string();
+
+        let mut scanner = Scanner::new();
+        let mut report = ImageReport { source: "x".into(), codes: 1, glyphs: vec![], chunks: 0, diagnostics: vec![] };
+        scanner.scan_payload(&qr_compact::encode(&glyph.to_cbor().unwrap()).unwrap(), &mut report);
+        scanner.scan_payload("!!not base64!!", &mut report);
+
+        assert!(matches!(report.diagnostics[0], Diagnostic::HashMismatch { .. }));
+        assert!(matches!(report.diagnostics[1], Diagnostic::Base64(_)));
+        assert!(scanner.finish().glyphs.is_empty());
+    }
+}
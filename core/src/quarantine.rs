@@ -0,0 +1,266 @@
+// Epistemic quarantine: rejected glyphs kept for review.
+//
+// Glyphs the firewall or validation turns away are not dropped. They are kept
+// under the store's `quarantine/` directory, which store loaders do not read:
+//
+//   quarantine/<entry>.glyph    the bytes exactly as they arrived
+//   quarantine/<entry>.json     source, arrival date, deciding rule and
+//                               diagnostics
+//
+// Entries are named by a fresh uuid, so the same glyph arriving twice is kept
+// twice; commands accept any unique prefix of the name. A quarantined glyph
+// leaves by release into the store, after review or because a recheck
+// against a changed policy or keyring now admits it, or by purge.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::firewall::{Policy, Verdict};
+use super::glyph_decode::Glyph;
+use super::trust::Keyring;
+
+/// Directory of the quarantine inside a store
+pub const QUARANTINE_DIR: &str = "quarantine";
+
+// ============================================================================
+// DATA STRUCTURES
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub entry: String,
+    pub source: String,
+    pub arrived_at: DateTime<Utc>,
+    /// Header fields, when the bytes decode as a glyph
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub hash: Option<String>,
+    /// 1-based firewall rule that rejected the glyph
+    #[serde(default)]
+    pub rule: Option<usize>,
+    pub diagnostics: Vec<String>,
+}
+
+/// An entry with its new verdict; none when it still does not decode
+pub type Rechecked = (Entry, Option<Verdict>);
+
+pub struct Quarantine {
+    dir: PathBuf,
+}
+
+// ============================================================================
+// QUARANTINE
+// ============================================================================
+
+impl Quarantine {
+    /// The quarantine of a store; created on first use
+    pub fn of_store(store: &str) -> Self {
+        Quarantine { dir: Path::new(store).join(QUARANTINE_DIR) }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn add(
+        &self,
+        source: &str,
+        bytes: &[u8],
+        glyph: Option<&Glyph>,
+        rule: Option<usize>,
+        diagnostics: Vec<String>,
+    ) -> Result<Entry, Box<dyn std::error::Error>> {
+        fs::create_dir_all(&self.dir)?;
+        let entry = Entry {
+            entry: uuid::Uuid::new_v4().to_string(),
+            source: source.to_string(),
+            arrived_at: Utc::now(),
+            id: glyph.map(|glyph| glyph.header.id.clone()),
+            label: glyph.map(|glyph| glyph.payload.label.clone()),
+            hash: glyph.map(|glyph| glyph.header.hash.clone()),
+            rule,
+            diagnostics,
+        };
+        fs::write(self.dir.join(format!("{}.glyph", entry.entry)), bytes)?;
+        fs::write(self.dir.join(format!("{}.json", entry.entry)), serde_json::to_string_pretty(&entry)?)?;
+        Ok(entry)
+    }
+
+    /// Every entry, oldest arrival first
+    pub fn list(&self) -> Result<Vec<Entry>, Box<dyn std::error::Error>> {
+        let mut entries = Vec::new();
+        if !self.dir.is_dir() {
+            return Ok(entries);
+        }
+        for file in fs::read_dir(&self.dir)? {
+            let path = file?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                let entry: Entry = serde_json::from_str(&fs::read_to_string(&path)?)
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                entries.push(entry);
+            }
+        }
+        entries.sort_by(|a, b| a.arrived_at.cmp(&b.arrived_at).then(a.entry.cmp(&b.entry)));
+        Ok(entries)
+    }
+
+    /// The entry whose name starts with `prefix`
+    pub fn find(&self, prefix: &str) -> Result<Entry, Box<dyn std::error::Error>> {
+        let matches: Vec<Entry> = self.list()?.into_iter().filter(|entry| entry.entry.starts_with(prefix)).collect();
+        match matches.len() {
+            0 => Err(format!("No quarantine entry matches {}", prefix).into()),
+            1 => Ok(matches.into_iter().next().unwrap()),
+            n => Err(format!("{} quarantine entries match {}; use a longer prefix", n, prefix).into()),
+        }
+    }
+
+    pub fn bytes(&self, entry: &Entry) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        Ok(fs::read(self.dir.join(format!("{}.glyph", entry.entry)))?)
+    }
+
+    pub fn glyph(&self, entry: &Entry) -> Result<Glyph, Box<dyn std::error::Error>> {
+        Glyph::from_cbor(&self.bytes(entry)?)
+    }
+
+    pub fn remove(&self, entry: &Entry) -> Result<(), Box<dyn std::error::Error>> {
+        fs::remove_file(self.dir.join(format!("{}.glyph", entry.entry)))?;
+        fs::remove_file(self.dir.join(format!("{}.json", entry.entry)))?;
+        Ok(())
+    }
+
+    /// Move an entry into the store as `<id>.glyph`, returning its new path.
+    /// A different glyph already stored under the same id blocks the release.
+    pub fn release(&self, entry: &Entry, store: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let bytes = self.bytes(entry)?;
+        let glyph = Glyph::from_cbor(&bytes).map_err(|e| format!("Entry {} is not a glyph: {}", entry.entry, e))?;
+        let target = Path::new(store).join(glyph.file_name()?);
+        if target.exists() && fs::read(&target)? != bytes {
+            return Err(format!("A different glyph with id {} is already in the store", glyph.header.id).into());
+        }
+        fs::write(&target, &bytes)?;
+        self.remove(entry)?;
+        Ok(target)
+    }
+
+    /// Remove entries that arrived more than `older_than` ago, or all of them
+    pub fn purge(&self, older_than: Option<Duration>) -> Result<Vec<Entry>, Box<dyn std::error::Error>> {
+        let cutoff = older_than.map(|age| Utc::now() - age);
+        let mut purged = Vec::new();
+        for entry in self.list()? {
+            if cutoff.is_none_or(|cutoff| entry.arrived_at < cutoff) {
+                self.remove(&entry)?;
+                purged.push(entry);
+            }
+        }
+        Ok(purged)
+    }
+
+    /// Judge every entry again under the current policy and keyring
+    pub fn recheck(&self, policy: &Policy, keyring: &Keyring) -> Result<Vec<Rechecked>, Box<dyn std::error::Error>> {
+        let mut results = Vec::new();
+        for entry in self.list()? {
+            let bytes = self.bytes(&entry)?;
+            let verdict = Glyph::from_cbor(&bytes).ok().map(|glyph| policy.evaluate(&glyph, bytes.len(), keyring));
+            results.push((entry, verdict));
+        }
+        Ok(results)
+    }
+}
+
+impl Entry {
+    pub fn to_text(&self) -> String {
+        let mut text = format!("Entry:       {}\n", self.entry);
+        text.push_str(&format!("Arrived:     {}\n", self.arrived_at.format("%Y-%m-%d %H:%M:%S")));
+        text.push_str(&format!("Source:      {}\n", self.source));
+        if let (Some(id), Some(label), Some(hash)) = (&self.id, &self.label, &self.hash) {
+            text.push_str(&format!("Glyph:       {} ({})\n", label, id));
+            text.push_str(&format!("Hash:        {}\n", hash));
+        }
+        if let Some(rule) = self.rule {
+            text.push_str(&format!("Rule:        {}\n", rule));
+        }
+        text.push_str("Diagnostics:\n");
+        for diagnostic in &self.diagnostics {
+            text.push_str(&format!("  - {}\n", diagnostic));
+        }
+        text
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::glyph;
+
+    fn propaganda(label: &str) -> (Glyph, Vec<u8>) {
+        let cbor = glyph(label).classes(&["propaganda"]).cbor();
+        (Glyph::from_cbor(&cbor).unwrap(), cbor)
+    }
+
+    fn scratch(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("glyphos-quarantine-{}-{}", name, uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_recheck_and_release() {
+        let store = scratch("release");
+        let quarantine = Quarantine::of_store(&store);
+        let (sun, bytes) = propaganda("sun");
+        let entry = quarantine.add("incoming/sun.glyph", &bytes, Some(&sun), Some(1), vec!["class: propaganda".into()]).unwrap();
+        quarantine.add("junk.glyph", b"junk", None, None, vec!["not a glyph".into()]).unwrap();
+        assert_eq!(quarantine.list().unwrap().len(), 2);
+        assert_eq!(quarantine.find(&entry.entry[..8]).unwrap(), entry);
+
+        let strict = Policy::from_json5(r#"{ rules: [{ action: "deny", classes: ["propaganda"] }] }"#).unwrap();
+        let relaxed = Policy::from_json5(r#"{ rules: [{ action: "deny", classes: ["spam"] }] }"#).unwrap();
+        let allowed = |policy: &Policy| -> Vec<String> {
+            quarantine.recheck(policy, &Keyring::new()).unwrap().into_iter()
+                .filter(|(_, verdict)| verdict.as_ref().is_some_and(Verdict::allowed))
+                .map(|(entry, _)| entry.source)
+                .collect()
+        };
+        assert!(allowed(&strict).is_empty());
+        assert_eq!(allowed(&relaxed), vec!["incoming/sun.glyph"]);
+
+        let path = quarantine.release(&entry, &store).unwrap();
+        assert_eq!(Glyph::from_cbor(&fs::read(path).unwrap()).unwrap().header.id, sun.header.id);
+        assert_eq!(quarantine.list().unwrap().len(), 1);
+        fs::remove_dir_all(&store).unwrap();
+    }
+
+    #[test]
+    fn test_purge_and_release_refusals() {
+        let store = scratch("purge");
+        let quarantine = Quarantine::of_store(&store);
+        let junk = quarantine.add("junk.glyph", b"junk", None, None, vec![]).unwrap();
+        assert!(quarantine.release(&junk, &store).is_err());
+
+        // Another glyph already holds the id
+        let (sun, bytes) = propaganda("sun");
+        fs::write(Path::new(&store).join(format!("{}.glyph", sun.header.id)), b"other").unwrap();
+        let entry = quarantine.add("sun.glyph", &bytes, Some(&sun), None, vec![]).unwrap();
+        assert!(quarantine.release(&entry, &store).is_err());
+
+        // The id would name a file outside the store
+        let mut escape = glyph("moon").encode();
+        escape.header.id = "../moon".to_string();
+        let entry = quarantine.add("moon.glyph", &escape.to_cbor().unwrap(), None, None, vec![]).unwrap();
+        assert!(quarantine.release(&entry, &store).unwrap_err().to_string().contains("not a UUID"));
+
+        assert!(quarantine.purge(Some(Duration::days(1))).unwrap().is_empty());
+        assert_eq!(quarantine.purge(None).unwrap().len(), 3);
+        assert!(quarantine.find("").is_err());
+        fs::remove_dir_all(&store).unwrap();
+    }
+}
@@ -0,0 +1,299 @@
+// Full-text and fuzzy search over a store.
+//
+// The index covers the latest revision of every concept and these fields,
+// weighted by how strongly they name the glyph:
+//
+//   label 1.0   aliases 0.8   overlay labels 0.7   phonetic 0.6   classes 0.5
+//
+// Text is normalized before indexing and querying: compatibility-decomposed
+// (NFKD), stripped of combining marks, lowercased, and split on anything that
+// is not a letter or digit, so "Sól", "sol" and "SOL" are the same term.
+//
+// Every query term must match a term of the glyph, exactly, as a prefix, or
+// within a small edit distance (one edit from 4 characters, two from 8).
+// A glyph scores the mean of the best weighted match of each query term, plus
+// a bonus when a whole field equals the query; hits are ranked by score.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use super::glyph_decode::Glyph;
+use super::lineage;
+
+// ============================================================================
+// DATA STRUCTURES
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    Label,
+    Alias,
+    Overlay,
+    Phonetic,
+    Class,
+}
+
+#[derive(Debug, Clone)]
+struct Posting {
+    glyph: usize,
+    field: Field,
+    /// Position of the field value within the glyph
+    value: usize,
+}
+
+/// Restrictions on which glyphs may be returned
+#[derive(Debug, Clone, Default)]
+pub struct Filters {
+    /// Namespace provision, with or without the `glyph:` prefix
+    pub namespace: Option<String>,
+    pub truth_mode: Option<String>,
+    pub min_confidence: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Hit {
+    pub glyph: usize,
+    pub id: String,
+    pub label: String,
+    pub score: f64,
+    /// Field values that matched, as `field: value`
+    pub matched: Vec<String>,
+}
+
+pub struct SearchIndex<'a> {
+    glyphs: &'a [Glyph],
+    terms: BTreeMap<String, Vec<Posting>>,
+    /// Normalized field values by glyph, for whole-field matches
+    values: BTreeMap<usize, Vec<(Field, String, String)>>,
+}
+
+// ============================================================================
+// NORMALIZATION
+// ============================================================================
+
+/// Fold text to lowercase letters and digits separated by single spaces
+pub fn normalize(text: &str) -> String {
+    let folded: String = text.nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect();
+    folded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Optimal string alignment distance between two terms
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    rows[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            rows[i][j] = (rows[i - 1][j] + 1).min(rows[i][j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                rows[i][j] = rows[i][j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+fn allowed_edits(term: &str) -> usize {
+    match term.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+// ============================================================================
+// INDEX
+// ============================================================================
+
+impl Field {
+    pub fn weight(&self) -> f64 {
+        match self {
+            Field::Label => 1.0,
+            Field::Alias => 0.8,
+            Field::Overlay => 0.7,
+            Field::Phonetic => 0.6,
+            Field::Class => 0.5,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Field::Label => "label",
+            Field::Alias => "alias",
+            Field::Overlay => "overlay",
+            Field::Phonetic => "phonetic",
+            Field::Class => "class",
+        }
+    }
+}
+
+impl<'a> SearchIndex<'a> {
+    pub fn build(glyphs: &'a [Glyph]) -> Self {
+        let mut index = SearchIndex { glyphs, terms: BTreeMap::new(), values: BTreeMap::new() };
+        for i in lineage::latest_revisions(glyphs) {
+            let payload = &glyphs[i].payload;
+            let fields = std::iter::once((Field::Label, &payload.label))
+                .chain(payload.aliases.iter().map(|alias| (Field::Alias, alias)))
+                .chain(payload.namespace.contextual_overlays.iter().map(|overlay| (Field::Overlay, &overlay.label)))
+                .chain(payload.audio.phonetic.iter().map(|phonetic| (Field::Phonetic, phonetic)))
+                .chain(payload.classes.iter().map(|class| (Field::Class, class)));
+
+            let mut values = Vec::new();
+            for (field, text) in fields {
+                let normalized = normalize(text);
+                for term in normalized.split(' ').filter(|term| !term.is_empty()) {
+                    index.terms.entry(term.to_string()).or_default().push(Posting { glyph: i, field, value: values.len() });
+                }
+                values.push((field, text.clone(), normalized));
+            }
+            index.values.insert(i, values);
+        }
+        index
+    }
+
+    /// Ranked hits for `query` that pass `filters`
+    pub fn search(&self, query: &str, filters: &Filters) -> Vec<Hit> {
+        let query = normalize(query);
+        let query_terms: Vec<&str> = query.split(' ').filter(|term| !term.is_empty()).collect();
+        if query_terms.is_empty() {
+            return Vec::new();
+        }
+
+        // Best score of each query term per glyph, and the values it matched
+        let mut scores: BTreeMap<usize, (Vec<f64>, Vec<usize>)> = BTreeMap::new();
+        for (q, query_term) in query_terms.iter().enumerate() {
+            let edits = allowed_edits(query_term);
+            for (term, postings) in &self.terms {
+                let quality = if term == query_term {
+                    1.0
+                } else if term.starts_with(query_term) {
+                    0.8
+                } else if edits > 0 && term.chars().count().abs_diff(query_term.chars().count()) <= edits {
+                    match edit_distance(term, query_term) {
+                        d if d <= edits => 0.6 - 0.1 * (d - 1) as f64,
+                        _ => continue,
+                    }
+                } else {
+                    continue;
+                };
+                for posting in postings {
+                    let (best, matched) = scores.entry(posting.glyph)
+                        .or_insert_with(|| (vec![0.0; query_terms.len()], Vec::new()));
+                    let score = quality * posting.field.weight();
+                    if score > best[q] {
+                        best[q] = score;
+                    }
+                    if !matched.contains(&posting.value) {
+                        matched.push(posting.value);
+                    }
+                }
+            }
+        }
+
+        let mut hits: Vec<Hit> = scores.into_iter()
+            .filter(|(_, (best, _))| best.iter().all(|score| *score > 0.0))
+            .filter(|(i, _)| filters.admit(&self.glyphs[*i]))
+            .map(|(i, (best, mut matched))| {
+                let values = &self.values[&i];
+                let whole = values.iter()
+                    .filter(|(_, _, normalized)| *normalized == query)
+                    .map(|(field, _, _)| field.weight())
+                    .fold(0.0, f64::max);
+                matched.sort();
+                Hit {
+                    glyph: i,
+                    id: self.glyphs[i].header.id.clone(),
+                    label: self.glyphs[i].payload.label.clone(),
+                    score: best.iter().sum::<f64>() / best.len() as f64 + whole,
+                    matched: matched.into_iter()
+                        .map(|value| format!("{}: {}", values[value].0.name(), values[value].1))
+                        .collect(),
+                }
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.label.cmp(&b.label)));
+        hits
+    }
+}
+
+impl Filters {
+    fn admit(&self, glyph: &Glyph) -> bool {
+        let payload = &glyph.payload;
+        let provision = &payload.namespace.provision;
+        self.namespace.as_deref().is_none_or(|namespace| {
+            provision == namespace || provision.strip_prefix("glyph:") == Some(namespace)
+        })
+            && self.truth_mode.as_deref().is_none_or(|mode| payload.truth_mode.r#type.eq_ignore_ascii_case(mode))
+            && self.min_confidence.is_none_or(|min| payload.truth_mode.confidence >= min)
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::glyph;
+
+    fn store() -> Vec<Glyph> {
+        vec![
+            glyph("Sun").aliases(&["Sól", "solar core"]).confidence(0.98).phonetic("sʌn"),
+            glyph("Solstice").phonetic("ˈsɒlstɪs"),
+            glyph("Sunflower").aliases(&["helianthus"]).provision("glyph:botany").confidence(0.6),
+        ].into_iter()
+            .map(|glyph| glyph.classes(&["astronomy"]).overlay("glyphos:mythology", "Hélios", "mythic").build())
+            .collect()
+    }
+
+    #[test]
+    fn test_normalized_prefix_and_fuzzy_matches() {
+        assert_eq!(normalize("  Sól—Invictus! "), "sol invictus");
+        assert_eq!(normalize("ＳＵＮ"), "sun");
+        assert_eq!(edit_distance("sunflwoer", "sunflower"), 1);
+
+        let glyphs = store();
+        let index = SearchIndex::build(&glyphs);
+        let labels = |query: &str| -> Vec<String> {
+            index.search(query, &Filters::default()).into_iter().map(|hit| hit.label).collect()
+        };
+
+        // An exact label outranks prefixes of longer labels
+        assert_eq!(labels("sun"), vec!["Sun", "Sunflower"]);
+        assert_eq!(labels("SOL"), vec!["Sun", "Solstice"]);
+        assert_eq!(labels("sunflwoer"), vec!["Sunflower"]);
+        assert_eq!(labels("solar cor"), vec!["Sun"]);
+        assert_eq!(labels("helios"), vec!["Solstice", "Sun", "Sunflower"]);
+        assert_eq!(labels("sʌn"), vec!["Sun"]);
+        assert!(labels("moon").is_empty());
+        assert!(labels("   ").is_empty());
+
+        let hit = &index.search("solar", &Filters::default())[0];
+        assert_eq!(hit.matched, vec!["alias: solar core"]);
+    }
+
+    #[test]
+    fn test_filters() {
+        let glyphs = store();
+        let index = SearchIndex::build(&glyphs);
+        let count = |filters: Filters| index.search("sun", &filters).len();
+
+        assert_eq!(count(Filters { namespace: Some("botany".into()), ..Filters::default() }), 1);
+        assert_eq!(count(Filters { namespace: Some("glyph:core".into()), ..Filters::default() }), 1);
+        assert_eq!(count(Filters { truth_mode: Some("Mythic".into()), ..Filters::default() }), 0);
+        assert_eq!(count(Filters { min_confidence: Some(0.9), ..Filters::default() }), 1);
+    }
+}
@@ -0,0 +1,248 @@
+// Deterministic sigil generation.
+//
+// A sigil is a stylized, QR-like visual marker derived from a glyph's
+// semantic hash. The hash bits fill one quadrant of a square grid which is
+// then mirrored on both axes, so every sigil is symmetric and the same hash
+// always produces the same mark. The mark is tinted by `visual.style.color`
+// and its frame and cell outlines are scaled by `visual.style.stroke`.
+
+use image::{ImageBuffer, Rgba};
+
+// Grid size; odd so the mirrored pattern has a center row and column
+pub const GRID: usize = 9;
+const HALF: usize = GRID / 2 + 1;
+
+const VIEWBOX: f64 = 100.0;
+const MARGIN: f64 = 14.0;
+
+// Largest PNG side; every pixel is tested against the grid, and a larger
+// mark adds nothing a scaled SVG cannot
+pub const MAX_PNG_SIZE: u32 = 4096;
+
+// ============================================================================
+// DATA STRUCTURES
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sigil {
+    pub cells: [[bool; GRID]; GRID],
+    pub color: [u8; 3],
+    pub stroke: f64,
+}
+
+// ============================================================================
+// GENERATION
+// ============================================================================
+
+impl Sigil {
+    /// Derive a sigil from a semantic hash ("blake3:<hex>"). Hashes that are
+    /// not valid hex are hashed once more so any string yields a stable mark.
+    pub fn generate(hash: &str, color: Option<&str>, stroke: Option<f64>) -> Result<Self, Box<dyn std::error::Error>> {
+        let hex_part = hash.strip_prefix("blake3:").unwrap_or(hash);
+        let bytes = match hex::decode(hex_part) {
+            Ok(bytes) if bytes.len() >= 4 => bytes,
+            _ => blake3::hash(hash.as_bytes()).as_bytes().to_vec(),
+        };
+
+        let bit = |n: usize| (bytes[(n / 8) % bytes.len()] >> (n % 8)) & 1 == 1;
+
+        let mut cells = [[false; GRID]; GRID];
+        for y in 0..HALF {
+            for x in 0..HALF {
+                let on = bit(y * HALF + x);
+                cells[y][x] = on;
+                cells[y][GRID - 1 - x] = on;
+                cells[GRID - 1 - y][x] = on;
+                cells[GRID - 1 - y][GRID - 1 - x] = on;
+            }
+        }
+        // Anchor the center so no sigil is ever blank
+        cells[GRID / 2][GRID / 2] = true;
+
+        let color = match color {
+            Some(color) => parse_color(color)?,
+            None => [0, 0, 0],
+        };
+
+        let stroke = stroke.unwrap_or(1.0);
+        if !stroke.is_finite() || stroke <= 0.0 {
+            return Err("Sigil stroke must be a positive number".into());
+        }
+
+        Ok(Sigil { cells, color, stroke })
+    }
+
+    fn color_hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.color[0], self.color[1], self.color[2])
+    }
+
+    fn cell_size() -> f64 {
+        (VIEWBOX - 2.0 * MARGIN) / GRID as f64
+    }
+
+    // ========================================================================
+    // RENDERING
+    // ========================================================================
+
+    pub fn to_svg(&self) -> String {
+        let color = self.color_hex();
+        let cell = Self::cell_size();
+        let frame_width = 2.0 * self.stroke;
+        let inset = MARGIN / 2.0;
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {v} {v}\" width=\"{v}\" height=\"{v}\">",
+            v = VIEWBOX
+        );
+        svg.push_str(&format!(
+            "<rect x=\"{i}\" y=\"{i}\" width=\"{s}\" height=\"{s}\" fill=\"none\" stroke=\"{c}\" stroke-width=\"{w:.2}\"/>",
+            i = inset,
+            s = VIEWBOX - 2.0 * inset,
+            c = color,
+            w = frame_width
+        ));
+
+        svg.push_str(&format!("<g fill=\"{}\" stroke=\"{}\" stroke-width=\"{:.2}\">", color, color, self.stroke / 2.0));
+        for (y, row) in self.cells.iter().enumerate() {
+            for (x, on) in row.iter().enumerate() {
+                if *on {
+                    svg.push_str(&format!(
+                        "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\"/>",
+                        MARGIN + x as f64 * cell,
+                        MARGIN + y as f64 * cell,
+                        cell,
+                        cell
+                    ));
+                }
+            }
+        }
+        svg.push_str("</g></svg>");
+
+        svg
+    }
+
+    /// Inline form suitable for `visual.sigil`
+    pub fn to_data_uri(&self) -> String {
+        let encoded = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, self.to_svg());
+        format!("data:image/svg+xml;base64,{}", encoded)
+    }
+
+    pub fn to_png(&self, size: u32) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if size < GRID as u32 * 4 {
+            return Err(format!("Sigil size must be at least {} pixels", GRID * 4).into());
+        }
+        if size > MAX_PNG_SIZE {
+            return Err(format!("Sigil size must be at most {} pixels", MAX_PNG_SIZE).into());
+        }
+
+        let scale = size as f64 / VIEWBOX;
+        let ink = Rgba([self.color[0], self.color[1], self.color[2], 255]);
+        let mut image_buffer = ImageBuffer::from_pixel(size, size, Rgba([255u8, 255, 255, 0]));
+
+        // Work in viewbox units so the raster matches the SVG geometry
+        let (near, far) = (MARGIN / 2.0, VIEWBOX - MARGIN / 2.0);
+        let half_frame = self.stroke;
+        let pad = self.stroke / 4.0;
+
+        for py in 0..size {
+            for px in 0..size {
+                let x = (px as f64 + 0.5) / scale;
+                let y = (py as f64 + 0.5) / scale;
+
+                let within = |lo: f64, hi: f64| x > lo && x < hi && y > lo && y < hi;
+                let on_frame = within(near - half_frame, far + half_frame)
+                    && !within(near + half_frame, far - half_frame);
+
+                if on_frame || self.covers(x, y, pad) {
+                    image_buffer.put_pixel(px, py, ink);
+                }
+            }
+        }
+
+        let mut png_data = Vec::new();
+        image_buffer.write_to(&mut std::io::Cursor::new(&mut png_data), image::ImageFormat::Png)?;
+        Ok(png_data)
+    }
+
+    // Whether a point (in viewbox units) falls on a dark cell, including its outline
+    fn covers(&self, x: f64, y: f64, pad: f64) -> bool {
+        let cell = Self::cell_size();
+        let cx = ((x - MARGIN) / cell).floor() as i64;
+        let cy = ((y - MARGIN) / cell).floor() as i64;
+
+        for gy in cy - 1..=cy + 1 {
+            for gx in cx - 1..=cx + 1 {
+                if gx < 0 || gy < 0 || gx >= GRID as i64 || gy >= GRID as i64 {
+                    continue;
+                }
+                if !self.cells[gy as usize][gx as usize] {
+                    continue;
+                }
+                let x0 = MARGIN + gx as f64 * cell;
+                let y0 = MARGIN + gy as f64 * cell;
+                if x >= x0 - pad && x < x0 + cell + pad && y >= y0 - pad && y < y0 + cell + pad {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+}
+
+/// Parse "#RRGGBB" or "#RGB"
+fn parse_color(color: &str) -> Result<[u8; 3], Box<dyn std::error::Error>> {
+    let hex_part = color.strip_prefix('#').unwrap_or(color);
+    let expanded = match hex_part.len() {
+        3 => hex_part.chars().flat_map(|c| [c, c]).collect::<String>(),
+        6 => hex_part.to_string(),
+        _ => return Err(format!("Invalid sigil color: {}", color).into()),
+    };
+
+    let bytes = hex::decode(&expanded).map_err(|_| format!("Invalid sigil color: {}", color))?;
+    Ok([bytes[0], bytes[1], bytes[2]])
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "blake3:b251c467b461098b6a8f8a5eae43bd9ddcfe8b84d51eff6cd409d7e20b33c7f9";
+
+    #[test]
+    fn test_sigil_is_deterministic_and_symmetric() {
+        let a = Sigil::generate(HASH, Some("#FFD700"), Some(1.2)).unwrap();
+        let b = Sigil::generate(HASH, Some("#FFD700"), Some(1.2)).unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a.cells, Sigil::generate("blake3:test", None, None).unwrap().cells);
+
+        for y in 0..GRID {
+            for x in 0..GRID {
+                assert_eq!(a.cells[y][x], a.cells[y][GRID - 1 - x]);
+                assert_eq!(a.cells[y][x], a.cells[GRID - 1 - y][x]);
+            }
+        }
+
+        let svg = a.to_svg();
+        assert!(svg.contains("#ffd700"));
+        assert!(svg.contains("stroke-width=\"2.40\""));
+        assert!(a.to_data_uri().starts_with("data:image/svg+xml;base64,"));
+    }
+
+    #[test]
+    fn test_sigil_png_and_invalid_style() {
+        let sigil = Sigil::generate(HASH, Some("#abc"), None).unwrap();
+        assert_eq!(sigil.color, [0xaa, 0xbb, 0xcc]);
+
+        let png = sigil.to_png(128).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+        assert!(sigil.to_png(MAX_PNG_SIZE + 1).is_err());
+
+        assert!(Sigil::generate(HASH, Some("gold"), None).is_err());
+        assert!(Sigil::generate(HASH, None, Some(0.0)).is_err());
+    }
+}
@@ -0,0 +1,381 @@
+// Glyph sanitizer for `visual.svg`.
+//
+// SVG is a document format, not a picture: it can run scripts, load remote
+// resources, embed HTML through foreignObject and smuggle arbitrary data in
+// comments or oversized data URIs. Inline SVG is therefore rebuilt from an
+// allowlist of drawing elements and presentation attributes:
+//
+// - disallowed elements (script, style, foreignObject, animation, ...) are
+//   dropped together with their content
+// - disallowed attributes, including every on* event handler, are dropped
+// - href / xlink:href may only point inside the document ("#id") or hold a
+//   small raster data URI; url(...) references must be local as well, once
+//   CSS escapes and comments in the value are resolved
+// - comments, processing instructions and CDATA are dropped
+//
+// Documents that exceed the size or complexity limits, declare a DOCTYPE
+// (entity expansion) or do not parse are rejected outright.
+
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+
+const ALLOWED_ELEMENTS: &[&str] = &[
+    "svg", "g", "defs", "symbol", "use", "title", "desc",
+    "path", "rect", "circle", "ellipse", "line", "polyline", "polygon",
+    "text", "tspan", "textPath",
+    "linearGradient", "radialGradient", "stop", "pattern",
+    "clipPath", "mask", "marker", "image",
+];
+
+const ALLOWED_ATTRIBUTES: &[&str] = &[
+    "xmlns", "xmlns:xlink", "version", "id", "class", "style",
+    "viewBox", "preserveAspectRatio", "width", "height", "transform",
+    "x", "y", "x1", "y1", "x2", "y2", "cx", "cy", "r", "rx", "ry", "fx", "fy",
+    "d", "points", "pathLength", "dx", "dy", "rotate", "textLength", "lengthAdjust", "startOffset",
+    "href", "xlink:href",
+    "fill", "fill-opacity", "fill-rule", "stroke", "stroke-width", "stroke-linecap",
+    "stroke-linejoin", "stroke-miterlimit", "stroke-dasharray", "stroke-dashoffset",
+    "stroke-opacity", "opacity", "color", "visibility", "display", "vector-effect",
+    "clip-path", "clip-rule", "clipPathUnits", "mask", "maskUnits", "maskContentUnits",
+    "offset", "stop-color", "stop-opacity", "gradientUnits", "gradientTransform", "spreadMethod",
+    "patternUnits", "patternContentUnits", "patternTransform",
+    "marker-start", "marker-mid", "marker-end", "markerWidth", "markerHeight",
+    "markerUnits", "refX", "refY", "orient",
+    "font-family", "font-size", "font-weight", "font-style", "text-anchor",
+    "dominant-baseline", "letter-spacing", "word-spacing",
+];
+
+// Raster formats an <image> may embed; never SVG, which could nest scripts
+const ALLOWED_DATA_URIS: &[&str] = &[
+    "data:image/png;base64,",
+    "data:image/jpeg;base64,",
+    "data:image/gif;base64,",
+    "data:image/webp;base64,",
+];
+
+// ============================================================================
+// DATA STRUCTURES
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SanitizeLimits {
+    /// Maximum size of the SVG source in bytes
+    pub max_bytes: usize,
+    /// Maximum number of elements kept
+    pub max_elements: usize,
+    /// Maximum element nesting depth
+    pub max_depth: usize,
+    /// Maximum length of a single attribute value (path data, data URIs)
+    pub max_attribute_len: usize,
+}
+
+impl Default for SanitizeLimits {
+    fn default() -> Self {
+        SanitizeLimits {
+            max_bytes: 64 * 1024,
+            max_elements: 2000,
+            max_depth: 32,
+            max_attribute_len: 16 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sanitized {
+    pub svg: String,
+    /// Human-readable description of everything that was stripped
+    pub removed: Vec<String>,
+}
+
+// ============================================================================
+// SANITIZING
+// ============================================================================
+
+pub fn sanitize(svg: &str) -> Result<Sanitized, Box<dyn std::error::Error>> {
+    sanitize_with(svg, &SanitizeLimits::default())
+}
+
+pub fn sanitize_with(svg: &str, limits: &SanitizeLimits) -> Result<Sanitized, Box<dyn std::error::Error>> {
+    if svg.len() > limits.max_bytes {
+        return Err(format!("SVG is {} bytes, the limit is {}", svg.len(), limits.max_bytes).into());
+    }
+
+    let mut reader = Reader::from_str(svg);
+    reader.config_mut().check_end_names = true;
+    let mut writer = Writer::new(Vec::new());
+
+    let mut removed = Vec::new();
+    // Names of the open elements that are kept
+    let mut stack: Vec<String> = Vec::new();
+    // Depth of the disallowed element whose content is being skipped
+    let mut skipping: Option<usize> = None;
+    let mut depth = 0usize;
+    let mut elements = 0usize;
+    let mut seen_root = false;
+
+    loop {
+        let (start, is_empty) = match reader.read_event()? {
+            Event::Eof => break,
+            Event::Start(start) => (start, false),
+            Event::Empty(start) => (start, true),
+            Event::End(_) => {
+                if skipping == Some(depth) {
+                    skipping = None;
+                } else if skipping.is_none() {
+                    let name = stack.pop().ok_or("Unbalanced SVG end tag")?;
+                    writer.write_event(Event::End(BytesEnd::new(name)))?;
+                }
+                depth = depth.saturating_sub(1);
+                continue;
+            }
+            Event::Text(text) => {
+                if skipping.is_none() && !stack.is_empty() {
+                    let text = text.unescape()?;
+                    writer.write_event(Event::Text(BytesText::new(&text)))?;
+                }
+                continue;
+            }
+            Event::CData(_) => {
+                if skipping.is_none() {
+                    removed.push("CDATA section".to_string());
+                }
+                continue;
+            }
+            Event::Comment(_) => {
+                removed.push("comment".to_string());
+                continue;
+            }
+            Event::PI(_) => {
+                removed.push("processing instruction".to_string());
+                continue;
+            }
+            Event::DocType(_) => return Err("SVG must not declare a DOCTYPE".into()),
+            Event::Decl(_) => continue,
+        };
+
+        if !is_empty {
+            depth += 1;
+            if depth > limits.max_depth {
+                return Err(format!("SVG nesting exceeds {} levels", limits.max_depth).into());
+            }
+        }
+        if skipping.is_some() {
+            continue;
+        }
+
+        let name = String::from_utf8(start.name().as_ref().to_vec())?;
+        if seen_root && stack.is_empty() {
+            return Err("SVG has more than one root element".into());
+        }
+        if !seen_root {
+            if name != "svg" {
+                return Err(format!("Root element must be <svg>, found <{}>", name).into());
+            }
+            seen_root = true;
+        }
+
+        if !ALLOWED_ELEMENTS.contains(&name.as_str()) {
+            removed.push(format!("<{}> element", name));
+            if !is_empty {
+                skipping = Some(depth);
+            }
+            continue;
+        }
+
+        elements += 1;
+        if elements > limits.max_elements {
+            return Err(format!("SVG has more than {} elements", limits.max_elements).into());
+        }
+
+        let kept = sanitize_attributes(&start, &name, limits, &mut removed)?;
+        if is_empty {
+            writer.write_event(Event::Empty(kept))?;
+        } else {
+            writer.write_event(Event::Start(kept))?;
+            stack.push(name);
+        }
+    }
+
+    if !seen_root {
+        return Err("SVG has no <svg> root element".into());
+    }
+
+    Ok(Sanitized {
+        svg: String::from_utf8(writer.into_inner())?,
+        removed,
+    })
+}
+
+/// Validation messages for `visual.svg`: everything the sanitizer would
+/// remove, or why it rejects the document
+pub fn validation_errors(svg: &str) -> Vec<String> {
+    match sanitize(svg) {
+        Ok(result) => result.removed.iter()
+            .map(|removed| format!("visual.svg contains disallowed content: {}", removed))
+            .collect(),
+        Err(e) => vec![format!("visual.svg rejected by sanitizer: {}", e)],
+    }
+}
+
+fn sanitize_attributes<'a>(
+    start: &BytesStart<'_>,
+    element: &str,
+    limits: &SanitizeLimits,
+    removed: &mut Vec<String>,
+) -> Result<BytesStart<'a>, Box<dyn std::error::Error>> {
+    let mut kept = BytesStart::new(element.to_string());
+
+    for attribute in start.attributes() {
+        let attribute = attribute?;
+        let key = String::from_utf8(attribute.key.as_ref().to_vec())?;
+        let value = attribute.unescape_value()?;
+
+        let reason = if key.to_ascii_lowercase().starts_with("on") {
+            Some("event handler")
+        } else if !ALLOWED_ATTRIBUTES.contains(&key.as_str()) {
+            Some("not allowed")
+        } else if value.len() > limits.max_attribute_len {
+            Some("oversized")
+        } else if (key == "href" || key == "xlink:href") && !is_local_href(&value) {
+            Some("external reference")
+        } else if has_external_url(&value) {
+            Some("external url")
+        } else {
+            None
+        };
+
+        match reason {
+            Some(reason) => removed.push(format!("{} on <{}> ({})", key, element, reason)),
+            None => kept.push_attribute((key.as_str(), value.as_ref())),
+        }
+    }
+
+    Ok(kept)
+}
+
+fn is_local_href(value: &str) -> bool {
+    let value = value.trim();
+    value.starts_with('#')
+        || ALLOWED_DATA_URIS.iter().any(|prefix| value.to_ascii_lowercase().starts_with(prefix))
+}
+
+// url(...) references in presentation attributes and inline style, plus
+// script URLs in any position
+fn has_external_url(value: &str) -> bool {
+    let lowered = css_normalize(value).to_ascii_lowercase();
+    if lowered.contains("javascript:") || lowered.contains("expression(") || lowered.contains("@import") {
+        return true;
+    }
+
+    lowered.split("url(").skip(1).any(|rest| {
+        let target = rest.trim_start().trim_start_matches(['"', '\'']);
+        !target.starts_with('#')
+    })
+}
+
+// The value as CSS reads it: comments dropped and escapes resolved, so
+// `\75 rl(` or `u/**/rl(` cannot hide a url( from the check above
+fn css_normalize(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            '\\' => {
+                let mut hex = String::new();
+                while hex.len() < 6 && chars.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+                    hex.extend(chars.next());
+                }
+                if hex.is_empty() {
+                    // Any other escaped character stands for itself
+                    out.extend(chars.next());
+                } else {
+                    // One whitespace character ends a hex escape
+                    if chars.peek().is_some_and(|c| c.is_whitespace()) {
+                        chars.next();
+                    }
+                    let code = u32::from_str_radix(&hex, 16).unwrap_or(0);
+                    out.push(char::from_u32(code).filter(|c| *c != '\0').unwrap_or('\u{FFFD}'));
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strips_active_content() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10" onload="alert(1)">
+            <!-- hidden -->
+            <script>alert(1)</script>
+            <foreignObject><div>html</div></foreignObject>
+            <circle cx="5" cy="5" r="4" fill="url(#g)" onclick="steal()"/>
+            <use href="https://evil.example/x.svg#a"/>
+            <rect width="1" height="1" style="fill: url(http://evil.example/p)"/>
+            <text x="1" y="9">sun &amp; moon</text>
+        </svg>"##;
+
+        let result = sanitize(svg).unwrap();
+        assert!(!result.svg.contains("script"));
+        assert!(!result.svg.contains("foreignObject"));
+        assert!(!result.svg.contains("onclick"));
+        assert!(!result.svg.contains("evil"));
+        assert!(result.svg.contains("<circle cx=\"5\" cy=\"5\" r=\"4\" fill=\"url(#g)\"/>"));
+        assert!(result.svg.contains("sun &amp; moon"));
+
+        for expected in ["comment", "<script> element", "<foreignObject> element", "onload on <svg> (event handler)",
+            "href on <use> (external reference)", "style on <rect> (external url)"] {
+            assert!(result.removed.iter().any(|r| r == expected), "missing {}", expected);
+        }
+
+        // Sanitizing is idempotent
+        assert!(sanitize(&result.svg).unwrap().removed.is_empty());
+    }
+
+    #[test]
+    fn test_escaped_urls() {
+        for style in [r"fill:\75 rl(http://evil/p)", r"fill:\000055RL(http://evil/p)", "fill:u/**/rl(http://evil/p)",
+            r"fill:url(\68ttp://evil/p)", r"fill:\6a avascript:x"] {
+            let svg = format!(r#"<svg><rect style="{}"/></svg>"#, style);
+            let result = sanitize(&svg).unwrap();
+            assert_eq!(result.removed, vec!["style on <rect> (external url)"], "{}", style);
+        }
+
+        let local = sanitize(r#"<svg><rect style="fill:\75 rl(#g)"/></svg>"#).unwrap();
+        assert!(local.removed.is_empty());
+    }
+
+    #[test]
+    fn test_rejects_oversized_and_malformed() {
+        assert!(sanitize("<html/>").is_err());
+        assert!(sanitize("<svg><g></svg>").is_err());
+        assert!(sanitize(r#"<!DOCTYPE svg [<!ENTITY a "aaaa">]><svg>&a;</svg>"#).is_err());
+
+        let limits = SanitizeLimits { max_elements: 3, ..SanitizeLimits::default() };
+        let many = format!("<svg>{}</svg>", "<rect/>".repeat(5));
+        assert!(sanitize_with(&many, &limits).is_err());
+
+        let deep = format!("<svg>{}{}</svg>", "<g>".repeat(40), "</g>".repeat(40));
+        assert!(sanitize(&deep).is_err());
+        assert!(sanitize(&" ".repeat(70 * 1024)).is_err());
+    }
+}
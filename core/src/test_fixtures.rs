@@ -0,0 +1,149 @@
+// Glyph fixtures for unit tests.
+//
+// Tests describe only the fields they care about; everything else gets a
+// neutral default (glyph:core, empirical at 0.9, no aliases, classes or
+// relations):
+//
+//   let sun = glyph("sun").aliases(&["star"]).relation("opposite_of", "glyph:moon").build();
+
+use serde_json::{json, Value};
+
+use super::glyph_decode::Glyph;
+use super::glyph_encode;
+
+#[derive(Debug, Clone)]
+pub struct GlyphBuilder {
+    label: String,
+    creator: Option<String>,
+    aliases: Vec<String>,
+    classes: Vec<String>,
+    provision: String,
+    overlays: Vec<Value>,
+    mode: String,
+    confidence: f64,
+    verified_by: Vec<String>,
+    conflicts: Vec<String>,
+    phonetic: Option<String>,
+    relations: Vec<Value>,
+    wikidata: Option<String>,
+}
+
+/// Start a fixture with `label` and defaults for everything else
+pub fn glyph(label: &str) -> GlyphBuilder {
+    GlyphBuilder {
+        label: label.to_string(),
+        creator: None,
+        aliases: Vec::new(),
+        classes: Vec::new(),
+        provision: "glyph:core".to_string(),
+        overlays: Vec::new(),
+        mode: "empirical".to_string(),
+        confidence: 0.9,
+        verified_by: Vec::new(),
+        conflicts: Vec::new(),
+        phonetic: None,
+        relations: Vec::new(),
+        wikidata: None,
+    }
+}
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+impl GlyphBuilder {
+    pub fn creator(mut self, creator: &str) -> Self {
+        self.creator = Some(creator.to_string());
+        self
+    }
+
+    pub fn aliases(mut self, aliases: &[&str]) -> Self {
+        self.aliases = strings(aliases);
+        self
+    }
+
+    pub fn classes(mut self, classes: &[&str]) -> Self {
+        self.classes = strings(classes);
+        self
+    }
+
+    pub fn provision(mut self, provision: &str) -> Self {
+        self.provision = provision.to_string();
+        self
+    }
+
+    pub fn overlay(mut self, agent: &str, label: &str, mode: &str) -> Self {
+        self.overlays.push(json!({ "agent": agent, "label": label, "truth_mode": mode }));
+        self
+    }
+
+    pub fn mode(mut self, mode: &str) -> Self {
+        self.mode = mode.to_string();
+        self
+    }
+
+    pub fn confidence(mut self, confidence: f64) -> Self {
+        self.confidence = confidence;
+        self
+    }
+
+    pub fn verified_by(mut self, verified_by: &[&str]) -> Self {
+        self.verified_by = strings(verified_by);
+        self
+    }
+
+    pub fn conflicts(mut self, conflicts: &[&str]) -> Self {
+        self.conflicts = strings(conflicts);
+        self
+    }
+
+    pub fn phonetic(mut self, phonetic: &str) -> Self {
+        self.phonetic = Some(phonetic.to_string());
+        self
+    }
+
+    pub fn relation(mut self, r#type: &str, target: &str) -> Self {
+        self.relations.push(json!({ "type": r#type, "target": target }));
+        self
+    }
+
+    pub fn wikidata(mut self, wikidata: &str) -> Self {
+        self.wikidata = Some(wikidata.to_string());
+        self
+    }
+
+    /// The glyph as the encoder builds it, before it is serialized
+    pub fn encode(&self) -> glyph_encode::Glyph {
+        let origin = self.creator.as_ref().map(|creator| {
+            json!({ "creator": creator, "timestamp": "2025-01-15T10:30:00Z", "signature": null })
+        });
+        let payload = json!({
+            "origin": origin,
+            "label": self.label, "aliases": self.aliases, "classes": self.classes,
+            "namespace": { "provision": self.provision, "contextual_overlays": self.overlays },
+            "truth_mode": {
+                "type": self.mode, "confidence": self.confidence,
+                "verified_by": self.verified_by, "conflicts": self.conflicts,
+            },
+            "audio": { "file": null, "phonetic": self.phonetic },
+            "visual": { "svg": null, "sigil": null, "style": null },
+            "relations": self.relations,
+            "grounding": { "wikidata": self.wikidata, "sensors": [] },
+        });
+        glyph_encode::Glyph::from_json5(&payload.to_string()).unwrap()
+    }
+
+    pub fn cbor(&self) -> Vec<u8> {
+        self.encode().to_cbor().unwrap()
+    }
+
+    pub fn build(&self) -> Glyph {
+        Glyph::from_cbor(&self.cbor()).unwrap()
+    }
+
+    pub fn build_signed(&self, private_key: &[u8]) -> Glyph {
+        let mut glyph = self.encode();
+        glyph.sign(private_key).unwrap();
+        Glyph::from_cbor(&glyph.to_cbor().unwrap()).unwrap()
+    }
+}
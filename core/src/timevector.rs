@@ -0,0 +1,314 @@
+// Timevector: a timestamped sequence of glyph references mapped to media.
+//
+//   {
+//     "video": "https://youtu.be/abc123",
+//     "glyph_map": [
+//       { "glyph": "glyph:sun", "start": 0, "end": 4 },
+//       { "glyph": "glyph:tree", "start": 5, "end": 12 }
+//     ]
+//   }
+//
+// Like glyphs, timevectors are authored in JSON5, encoded to CBOR, hashed with
+// blake3 and optionally signed with Ed25519.
+
+use serde::{Deserialize, Serialize};
+use serde_json5;
+use ciborium::{into_writer, from_reader};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+
+use super::glyph_decode::Glyph;
+use super::glyph_ref::{GlyphRef, ResolveError, Resolver};
+use super::glyph_sign;
+
+// ============================================================================
+// DATA STRUCTURES
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimevectorHeader {
+    pub magic: String,
+    pub version: String,
+    pub id: String,
+    pub hash: String,
+    pub signature: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlyphMapEntry {
+    pub glyph: String,
+    pub start: f64,
+    pub end: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimevectorPayload {
+    pub video: String,
+    pub glyph_map: Vec<GlyphMapEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Timevector {
+    pub header: TimevectorHeader,
+    pub payload: TimevectorPayload,
+}
+
+// ============================================================================
+// ENCODING
+// ============================================================================
+
+impl Timevector {
+    pub fn from_json5(json5_content: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let payload: TimevectorPayload = serde_json5::from_str(json5_content)?;
+        Self::from_payload(payload)
+    }
+
+    pub fn from_payload(payload: TimevectorPayload) -> Result<Self, Box<dyn std::error::Error>> {
+        let header = TimevectorHeader {
+            magic: "TIMEVECTOR".to_string(),
+            version: "0.1".to_string(),
+            id: Uuid::new_v4().to_string(),
+            hash: Self::compute_content_hash(&payload)?,
+            signature: None,
+            created_at: Utc::now(),
+        };
+
+        Ok(Timevector { header, payload })
+    }
+
+    /// Recompute the content hash from the payload
+    pub fn content_hash(&self) -> Result<String, Box<dyn std::error::Error>> {
+        Self::compute_content_hash(&self.payload)
+    }
+
+    fn compute_content_hash(payload: &TimevectorPayload) -> Result<String, Box<dyn std::error::Error>> {
+        // Hash the CBOR encoding of the payload, so no choice of video or glyph
+        // names can make two different payloads hash alike
+        let mut buffer = Vec::new();
+        into_writer(payload, &mut buffer)?;
+        Ok(format!("blake3:{}", blake3::hash(&buffer).to_hex()))
+    }
+
+    pub fn sign(&mut self, private_key: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let signature = glyph_sign::sign_hash(private_key, &self.header.hash)?;
+        self.header.signature = Some(signature);
+        Ok(())
+    }
+
+    pub fn verify_signature(&self, public_key: &[u8]) -> Result<bool, Box<dyn std::error::Error>> {
+        if let Some(signature_str) = &self.header.signature {
+            glyph_sign::verify_hash(public_key, &self.header.hash, signature_str)
+        } else {
+            Ok(false) // No signature to verify
+        }
+    }
+
+    pub fn to_cbor(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut buffer = Vec::new();
+        into_writer(self, &mut buffer)?;
+        Ok(buffer)
+    }
+
+    pub fn from_cbor(cbor_data: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        let timevector: Timevector = from_reader(cbor_data)?;
+        Ok(timevector)
+    }
+
+    pub fn to_json(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let json = serde_json::to_string_pretty(self)?;
+        Ok(json)
+    }
+
+    pub fn to_yaml(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let yaml = serde_yaml::to_string(self)?;
+        Ok(yaml)
+    }
+
+    // ========================================================================
+    // VALIDATION
+    // ========================================================================
+
+    /// Validate structure and time ranges. When `glyphs` is given, every glyph
+    /// reference must also resolve to one of them.
+    pub fn validate(&self, glyphs: Option<&[Glyph]>) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        // Validate header
+        if self.header.magic != "TIMEVECTOR" {
+            errors.push("Invalid magic bytes".to_string());
+        }
+
+        if self.header.version != "0.1" {
+            errors.push("Unsupported version".to_string());
+        }
+
+        if Uuid::parse_str(&self.header.id).is_err() {
+            errors.push("Invalid UUID format".to_string());
+        }
+
+        match self.content_hash() {
+            Ok(hash) if hash == self.header.hash => {}
+            Ok(_) => errors.push("Hash does not match timevector content".to_string()),
+            Err(e) => errors.push(format!("Cannot hash timevector content: {}", e)),
+        }
+
+        // Validate payload
+        if self.payload.video.is_empty() {
+            errors.push("Video cannot be empty".to_string());
+        }
+
+        for (position, entry) in self.payload.glyph_map.iter().enumerate() {
+            if entry.glyph.is_empty() {
+                errors.push(format!("Entry {}: glyph reference cannot be empty", position));
+            }
+            if !entry.start.is_finite() || !entry.end.is_finite() {
+                errors.push(format!("Entry {}: start and end must be finite", position));
+                continue;
+            }
+            if entry.start < 0.0 || entry.end < 0.0 {
+                errors.push(format!("Entry {}: negative time range {}-{}", position, entry.start, entry.end));
+            }
+            if entry.end <= entry.start {
+                errors.push(format!("Entry {}: end {} is not after start {}", position, entry.end, entry.start));
+            }
+        }
+
+        // Overlapping ranges, checked in start order
+        let mut ordered: Vec<(usize, &GlyphMapEntry)> = self.payload.glyph_map.iter().enumerate().collect();
+        ordered.sort_by(|a, b| a.1.start.total_cmp(&b.1.start));
+        for pair in ordered.windows(2) {
+            let ((a_pos, a), (b_pos, b)) = (pair[0], pair[1]);
+            if b.start < a.end {
+                errors.push(format!(
+                    "Entries {} and {} overlap ({} {}-{} / {} {}-{})",
+                    a_pos, b_pos, a.glyph, a.start, a.end, b.glyph, b.start, b.end
+                ));
+            }
+        }
+
+        // Unresolved glyph references
+        if let Some(glyphs) = glyphs {
+            let resolver = Resolver::new(glyphs);
+            for (position, entry) in self.payload.glyph_map.iter().enumerate() {
+                if entry.glyph.is_empty() {
+                    continue;
+                }
+                if let Err(e) = resolve_reference(entry, &resolver) {
+                    errors.push(format!("Entry {}: unresolved glyph reference {} ({})", position, entry.glyph, e));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Find the glyph a map entry refers to. `glyph` is a `glyph:` reference
+/// (the prefix may be left out) resolved like any other, and a `hash` on the
+/// entry pins it to the glyph with that semantic hash.
+pub fn resolve_reference<'a>(entry: &GlyphMapEntry, resolver: &Resolver<'a>) -> Result<&'a Glyph, Box<dyn std::error::Error>> {
+    let uri = match entry.glyph.strip_prefix("glyph:") {
+        Some(_) => entry.glyph.clone(),
+        None => format!("glyph:{}", entry.glyph),
+    };
+    let mut reference: GlyphRef = uri.parse()?;
+    if let (GlyphRef::Label { hash, .. }, Some(pin)) = (&mut reference, &entry.hash) {
+        if hash.as_ref().is_some_and(|hash| hash != pin) {
+            return Err(format!("{} disagrees with the pinned hash {}", entry.glyph, pin).into());
+        }
+        *hash = Some(pin.clone());
+    }
+
+    let glyph = resolver.resolve(&reference)?;
+    // Id references carry no pin of their own
+    if let Some(pin) = &entry.hash {
+        if glyph.semantic_hash() != *pin {
+            return Err(ResolveError::HashMismatch { hash: pin.clone(), found: vec![glyph.semantic_hash()] }.into());
+        }
+    }
+    Ok(glyph)
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::glyph;
+
+    const TIMEVECTOR: &str = r#"
+    {
+        video: "https://youtu.be/abc123",
+        glyph_map: [
+            { glyph: "glyph:sun", start: 0, end: 4 },
+            { glyph: "glyph:tree", start: 5, end: 12 },
+        ],
+    }
+    "#;
+
+    #[test]
+    fn test_timevector_roundtrip_and_signature() {
+        let mut timevector = Timevector::from_json5(TIMEVECTOR).unwrap();
+        let private_key = [3u8; 32];
+        let public_key = ed25519_dalek::SigningKey::from_bytes(&private_key).verifying_key().to_bytes();
+        timevector.sign(&private_key).unwrap();
+
+        let decoded = Timevector::from_cbor(&timevector.to_cbor().unwrap()).unwrap();
+        assert_eq!(decoded.payload.glyph_map.len(), 2);
+        assert!(decoded.verify_signature(&public_key).unwrap());
+        assert!(decoded.validate(None).is_ok());
+
+        // Separators inside names cannot make different content hash alike
+        let entry = |glyph: &str, start: f64, end: f64, hash: Option<&str>| GlyphMapEntry {
+            glyph: glyph.to_string(), start, end, hash: hash.map(str::to_string),
+        };
+        let a = Timevector::from_payload(TimevectorPayload { video: "v".into(), glyph_map: vec![entry("g@0-1#", 2.0, 3.0, None)] }).unwrap();
+        let b = Timevector::from_payload(TimevectorPayload { video: "v".into(), glyph_map: vec![entry("g", 0.0, 1.0, Some("@2-3#"))] }).unwrap();
+        assert_ne!(a.header.hash, b.header.hash);
+    }
+
+    #[test]
+    fn test_timevector_validation_errors() {
+        let mut timevector = Timevector::from_json5(TIMEVECTOR).unwrap();
+        timevector.payload.glyph_map[1].start = 3.0;
+        timevector.payload.glyph_map.push(GlyphMapEntry {
+            glyph: "glyph:moon".to_string(),
+            start: -1.0,
+            end: -2.0,
+            hash: None,
+        });
+
+        let errors = timevector.validate(Some(&[])).unwrap_err();
+        assert!(errors.iter().any(|e| e.contains("Hash does not match")));
+        assert_ne!(timevector.header.hash, timevector.content_hash().unwrap());
+        assert!(errors.iter().any(|e| e.contains("overlap")));
+        assert!(errors.iter().any(|e| e.contains("negative time range")));
+        assert!(errors.iter().any(|e| e.contains("unresolved glyph reference glyph:sun")));
+    }
+
+    #[test]
+    fn test_reference_resolution() {
+        let sun = glyph("sun").build();
+        let mut forged = glyph("sun").aliases(&["helios"]).build();
+        forged.header.hash = sun.header.hash.clone();
+        let (id, hash) = (sun.header.id.clone(), sun.header.hash.clone());
+        let glyphs = [sun, forged];
+        let entry = |hash: Option<&str>| GlyphMapEntry {
+            glyph: "glyph:sun".to_string(), start: 0.0, end: 1.0, hash: hash.map(str::to_string),
+        };
+
+        // The pin selects by recomputed hash, so the forged header does not match
+        let resolver = Resolver::new(&glyphs);
+        assert_eq!(resolve_reference(&entry(Some(&hash)), &resolver).unwrap().header.id, id);
+        assert!(resolve_reference(&entry(None), &resolver).unwrap_err().to_string().contains("ambiguous"));
+    }
+}
@@ -0,0 +1,229 @@
+// Signer identification and trust policies.
+//
+// A keyring maps names to raw Ed25519 public keys, loaded from `.pub` files
+// (the name is the file stem) or directories of them. A glyph's signer is the
+// first key whose signature check passes over the header hash, and only when
+// that hash is the glyph's recomputed semantic hash.
+//
+// The semantic hash covers the label, aliases, classes, truth mode type,
+// wikidata id and media digests. Everything else - relations, confidence,
+// verifiers, overlays, origin, lineage - is outside it, so a known signer
+// vouches for the identity of a glyph, not for those fields.
+//
+// A trust policy weighs glyphs by who stands behind them:
+//
+//   {
+//     signers: { nasa: 1.0, esa: 0.9 },       // keyring names
+//     creators: { "agent:priest": 0.4 },      // origin.creator, for unlisted keyring signers
+//     unsigned: 0.2,
+//     default: 0.5,
+//   }
+//
+// A listed signer's weight comes first, then the creator's when the glyph is
+// signed by some other key of the keyring. origin.creator is not signed, so it
+// weighs nothing on its own: unsigned glyphs get `unsigned` and everything
+// else, including unknown signers, gets `default`.
+//
+// A glyph speaks for `agent:<keyring name>` when its signer is known, and for
+// the creator it claims otherwise.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use super::glyph_decode::Glyph;
+
+// ============================================================================
+// DATA STRUCTURES
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(tag = "status", content = "name", rename_all = "snake_case")]
+pub enum Signer {
+    Unsigned,
+    /// Signed by a key that is not in the keyring, the signature is invalid,
+    /// or the signed hash is not the hash of the payload
+    Unknown,
+    Known(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TrustPolicy {
+    #[serde(default)]
+    pub signers: BTreeMap<String, f64>,
+    #[serde(default)]
+    pub creators: BTreeMap<String, f64>,
+    /// Weight of unsigned glyphs; `default` when absent
+    #[serde(default)]
+    pub unsigned: Option<f64>,
+    #[serde(default = "default_weight")]
+    pub default: f64,
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Keyring {
+    keys: Vec<(String, Vec<u8>)>,
+}
+
+// ============================================================================
+// KEYRING
+// ============================================================================
+
+impl Keyring {
+    pub fn new() -> Self {
+        Keyring::default()
+    }
+
+    pub fn add(&mut self, name: &str, public_key: Vec<u8>) {
+        self.keys.push((name.to_string(), public_key));
+    }
+
+    /// Load `.pub` files, and every `.pub` file in the given directories
+    pub fn load(paths: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut keyring = Keyring::new();
+        for path in paths {
+            let path = Path::new(path);
+            let mut files = Vec::new();
+            if path.is_dir() {
+                for entry in fs::read_dir(path)? {
+                    let file = entry?.path();
+                    if file.extension().is_some_and(|ext| ext == "pub") {
+                        files.push(file);
+                    }
+                }
+                files.sort();
+            } else {
+                files.push(path.to_path_buf());
+            }
+            for file in files {
+                let name = file.file_stem().and_then(|stem| stem.to_str())
+                    .ok_or_else(|| format!("Invalid key path: {}", file.display()))?;
+                keyring.add(name, fs::read(&file).map_err(|e| format!("{}: {}", file.display(), e))?);
+            }
+        }
+        Ok(keyring)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn signer(&self, glyph: &Glyph) -> Signer {
+        if glyph.header.signature.is_none() {
+            return Signer::Unsigned;
+        }
+        if glyph.header.hash != glyph.semantic_hash() {
+            return Signer::Unknown;
+        }
+        self.keys.iter()
+            .find(|(_, key)| matches!(glyph.verify_signature(key), Ok(true)))
+            .map(|(name, _)| Signer::Known(name.clone()))
+            .unwrap_or(Signer::Unknown)
+    }
+}
+
+// ============================================================================
+// TRUST POLICY
+// ============================================================================
+
+impl Default for TrustPolicy {
+    /// Every glyph weighs the same
+    fn default() -> Self {
+        TrustPolicy {
+            signers: BTreeMap::new(),
+            creators: BTreeMap::new(),
+            unsigned: None,
+            default: default_weight(),
+        }
+    }
+}
+
+impl TrustPolicy {
+    pub fn from_json5(content: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let policy: TrustPolicy = serde_json5::from_str(content)?;
+        let weights = policy.signers.iter().chain(&policy.creators)
+            .map(|(name, weight)| (name.as_str(), *weight))
+            .chain(policy.unsigned.map(|weight| ("unsigned", weight)))
+            .chain([("default", policy.default)]);
+        for (name, weight) in weights {
+            if !weight.is_finite() || weight < 0.0 {
+                return Err(format!("Invalid trust weight for {}: {}", name, weight).into());
+            }
+        }
+        Ok(policy)
+    }
+
+    pub fn weight(&self, signer: &Signer, creator: Option<&str>) -> f64 {
+        match signer {
+            Signer::Known(name) => self.signers.get(name)
+                .or_else(|| creator.and_then(|creator| self.creators.get(creator)))
+                .copied()
+                .unwrap_or(self.default),
+            Signer::Unsigned => self.unsigned.unwrap_or(self.default),
+            Signer::Unknown => self.default,
+        }
+    }
+
+    /// Weight of a glyph, identifying its signer with `keyring`
+    pub fn weigh(&self, glyph: &Glyph, keyring: &Keyring) -> f64 {
+        let creator = glyph.payload.origin.as_ref().map(|origin| origin.creator.as_str());
+        self.weight(&keyring.signer(glyph), creator)
+    }
+}
+
+impl Signer {
+    /// Agent a glyph speaks for: its known signer, else the creator it claims
+    pub fn agent(&self, creator: Option<&str>) -> Option<String> {
+        match self {
+            Signer::Known(name) => Some(format!("agent:{}", name)),
+            _ => creator.map(str::to_string),
+        }
+    }
+}
+
+impl fmt::Display for Signer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Signer::Unsigned => write!(f, "unsigned"),
+            Signer::Unknown => write!(f, "unknown signer"),
+            Signer::Known(name) => write!(f, "signed by {}", name),
+        }
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::glyph;
+
+    #[test]
+    fn test_signer_needs_matching_hash() {
+        let private_key = [7u8; 32];
+        let mut keyring = Keyring::new();
+        keyring.add("nasa", ed25519_dalek::SigningKey::from_bytes(&private_key).verifying_key().to_bytes().to_vec());
+
+        let mut sun = glyph("sun").build_signed(&private_key);
+        assert_eq!(keyring.signer(&sun), Signer::Known("nasa".to_string()));
+        assert_eq!(Keyring::new().signer(&sun), Signer::Unknown);
+        assert_eq!(keyring.signer(&glyph("moon").build()), Signer::Unsigned);
+
+        // The signature still checks out over the old hash, but not for this payload
+        sun.payload.label = "moon".to_string();
+        assert_eq!(keyring.signer(&sun), Signer::Unknown);
+    }
+}